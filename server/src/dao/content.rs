@@ -0,0 +1,96 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+};
+
+use axum::body::Bytes;
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+
+/// One payload shared by every block whose bytes hash to the same digest
+struct SharedContent {
+    data: Bytes,
+    refs: usize,
+}
+
+lazy_static! {
+    static ref CONTENT_STORE: Mutex<HashMap<[u8; 32], SharedContent>> = Mutex::new(HashMap::new());
+}
+
+/// Counted handle to a content-addressed block payload. Cloning takes another
+/// reference; the payload leaves the store when the last handle is dropped,
+/// whether its block was consumed, cancelled or expired
+pub struct ContentRef {
+    digest: [u8; 32],
+}
+
+impl ContentRef {
+    /// Intern `data`, returning the shared payload and whether an identical
+    /// one was already buffered
+    pub fn intern(data: &Bytes) -> (Self, Bytes, bool) {
+        let digest: [u8; 32] = Sha256::digest(data).into();
+        let mut store = CONTENT_STORE.lock().unwrap_or_else(PoisonError::into_inner);
+        let reused = store.contains_key(&digest);
+        let shared = store.entry(digest).or_insert_with(|| SharedContent { data: data.clone(), refs: 0 });
+        shared.refs += 1;
+        (ContentRef { digest }, shared.data.clone(), reused)
+    }
+}
+
+impl Clone for ContentRef {
+    fn clone(&self) -> Self {
+        let mut store = CONTENT_STORE.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(shared) = store.get_mut(&self.digest) {
+            shared.refs += 1;
+        }
+        ContentRef { digest: self.digest }
+    }
+}
+
+impl Drop for ContentRef {
+    fn drop(&mut self) {
+        let mut store = CONTENT_STORE.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(shared) = store.get_mut(&self.digest) {
+            shared.refs -= 1;
+            if shared.refs == 0 {
+                store.remove(&self.digest);
+            }
+        }
+    }
+}
+
+/// Number of distinct payloads held and their total size in bytes
+pub fn content_usage() -> (usize, u64) {
+    let store = CONTENT_STORE.lock().unwrap_or_else(PoisonError::into_inner);
+    (store.len(), store.values().map(|shared| shared.data.len() as u64).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs(data: &Bytes) -> Option<usize> {
+        let digest: [u8; 32] = Sha256::digest(data).into();
+        CONTENT_STORE.lock().unwrap().get(&digest).map(|shared| shared.refs)
+    }
+
+    #[test]
+    fn test_intern_shares_payload_until_last_ref() {
+        let data = Bytes::from_static(b"content-ref-test-payload");
+        let (first, shared, reused) = ContentRef::intern(&data);
+        assert!(!reused);
+
+        let (second, again, reused) = ContentRef::intern(&Bytes::copy_from_slice(&data));
+        assert!(reused);
+        assert_eq!(shared.as_ptr(), again.as_ptr());
+
+        let third = second.clone();
+        assert_eq!(refs(&data), Some(3));
+
+        drop(first);
+        drop(second);
+        assert_eq!(refs(&data), Some(1));
+        drop(third);
+        assert_eq!(refs(&data), None);
+    }
+}
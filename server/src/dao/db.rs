@@ -1,20 +1,104 @@
-use std::{sync::Arc};
-use super::memdb::MemDB;
+use std::{collections::VecDeque, sync::Arc, time::{Duration, Instant}};
+use super::{content::ContentRef, memdb::{CleanupSchedule, Footprint, MemDB, CLEAN_UP_INTERVAL}};
+use crate::utils::env::read_env_string;
+
+pub use super::memdb::StoreStats;
 
 use axum::body::Bytes;
 use lazy_static::lazy_static;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Inclusive byte range of a file
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// A receiver holding a claim on an access code
+#[derive(Clone)]
+pub struct ReceiverClaim {
+    pub id: String, // nonce of the server-issued receiver token
+    pub cursor: u64, // end offset of the furthest block served to this receiver
+    pub approved: bool, // blocks are only served once the sender approved the claim
+    pub missing: Option<Vec<ByteRange>>, // ranges still needed after a reported partial failure
+}
 
 #[derive(Clone)]
 pub struct MetaInfo {
     pub is_using: bool,
-    pub used_by: String, // a random id gen by client
-    #[allow(dead_code)]
+    pub receivers: Vec<ReceiverClaim>,
+    pub max_receivers: u32, // receivers allowed to hold a claim at the same time
+    pub require_approval: bool, // new claims stay pending until the sender approves them
+    pub sender_nonce: String, // nonce of the sender token issued with the id
+    pub owners: Vec<String>, // owner tags of the creator, for listing its transfers
     pub block_size: u32,
     pub file_name: String,
     pub file_size: u64,
     pub done: bool,
+    pub paused: bool, // sender paused the relay; receivers are held off until it resumes
+    pub digest: RelayDigest, // running hash over the relayed prefix of the file
+    pub metrics: TransferMetrics, // relay throughput and timing
+}
+
+/// SHA-256 over the contiguous prefix of the file relayed so far
+#[derive(Clone, Default)]
+pub struct RelayDigest {
+    hasher: Sha256,
+    pub offset: u64, // bytes covered, i.e. the start of the next block to hash
+}
+
+impl RelayDigest {
+    /// Extend the digest with the block that starts at `offset`
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.offset += data.len() as u64;
+    }
+
+    pub fn hex(&self) -> String {
+        self.hasher.clone().finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Most recent blocks kept per direction for the current rate
+const FLOW_SAMPLES: usize = 64;
+
+/// Blocks moved in one direction of the relay
+#[derive(Clone, Default)]
+pub struct FlowMetrics {
+    pub bytes: u64,
+    pub blocks: u64,
+    pub first_at: Option<Instant>,
+    pub last_at: Option<Instant>,
+    pub busy: Duration, // time spent receiving block bodies, when measurable
+    pub samples: VecDeque<(Instant, u64)>, // completion time and size of the latest blocks
+}
+
+impl FlowMetrics {
+    pub fn record(&mut self, bytes: u64, busy: Duration) {
+        let now = Instant::now();
+        self.bytes += bytes;
+        self.blocks += 1;
+        self.first_at.get_or_insert(now);
+        self.last_at = Some(now);
+        self.busy += busy;
+        if self.samples.len() == FLOW_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, bytes));
+    }
+}
+
+/// Where the time of a relayed transfer went
+#[derive(Clone, Default)]
+pub struct TransferMetrics {
+    pub upload: FlowMetrics,
+    pub download: FlowMetrics,
+    pub relay_time: Duration, // server-side handling of uploaded blocks (inspection, dedup, buffering)
+    pub receiver_wait: Duration, // receivers blocked on blocks the sender had not uploaded yet
+    pub buffer_dwell: Duration, // uploaded blocks waiting at the relay for their first receiver
 }
 
 impl MetaInfo {
@@ -29,13 +113,28 @@ impl MetaInfo {
     ) -> Self {
         MetaInfo {
             is_using: false,
-            used_by: "".to_string(),
+            receivers: Vec::new(),
+            max_receivers: 1,
+            require_approval: false,
+            sender_nonce: "".to_string(),
+            owners: Vec::new(),
             block_size: 1024 * 1024,
-            file_name: file_name,
-            file_size: file_size,
+            file_name,
+            file_size,
             done: false,
+            paused: false,
+            digest: RelayDigest::default(),
+            metrics: TransferMetrics::default(),
         }
     }
+
+    pub fn receiver(&self, id: &str) -> Option<&ReceiverClaim> {
+        self.receivers.iter().find(|r| r.id == id)
+    }
+
+    pub fn has_free_slot(&self) -> bool {
+        self.receivers.len() < self.max_receivers as usize
+    }
 }
 
 #[derive(Clone)]
@@ -45,6 +144,10 @@ pub struct FileBlock {
     pub start: u64,
     pub end: u64,
     pub total: u64,
+    pub served_to: Vec<String>, // receivers that already consumed this block
+    pub content: Option<ContentRef>, // shared payload handle when blocks are deduplicated
+    pub stored_at: Instant,
+    pub consumed_at: Option<Instant>, // every receiver slot read it; kept only for re-fetches
 }
 
 impl FileBlock {
@@ -59,9 +162,21 @@ impl FileBlock {
             start,
             end,
             total,
+            served_to: Vec::new(),
+            content: None,
+            stored_at: Instant::now(),
+            consumed_at: None,
         }
     }
 
+    /// Swap the payload for a content-addressed shared copy; returns true when
+    /// an identical block was already buffered
+    pub fn deduplicate(&mut self) -> bool {
+        let (content, data, reused) = ContentRef::intern(&self.data);
+        self.data = data;
+        self.content = Some(content);
+        reused
+    }
 }
 
 #[derive(Clone, Serialize)]
@@ -71,12 +186,39 @@ pub struct SignalMessage {
     pub msg_type: String,
     pub data: Value,
     pub rid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    pub sent_at: u64,
+}
+
+/// An opaque binary frame relayed through a signaling room
+#[derive(Clone)]
+pub struct SignalFrame {
+    pub seq: u64,
+    pub from: String,
+    pub rid: Option<String>,
+    pub to: Option<String>,
+    pub data: Bytes,
+}
+
+/// A receiver registered in a signaling room
+#[derive(Clone)]
+pub struct SignalPeer {
+    pub id: String,
+    pub joined_at: Instant,
+    pub last_seen: Instant,
 }
 
 #[derive(Clone)]
 pub struct SignalState {
     pub seq: u64,
     pub messages: Vec<SignalMessage>,
+    pub created_at: Instant,
+    pub receivers: Vec<SignalPeer>,
+    pub sender_joined_at: Option<Instant>,
+    pub sender_last_seen: Option<Instant>,
+    pub frame_seq: u64,
+    pub frames: Vec<SignalFrame>,
 }
 
 impl SignalState {
@@ -88,18 +230,156 @@ impl SignalState {
         SignalState {
             seq: 0,
             messages: Vec::new(),
+            created_at: Instant::now(),
+            receivers: Vec::new(),
+            sender_joined_at: None,
+            sender_last_seen: None,
+            frame_seq: 0,
+            frames: Vec::new(),
         }
     }
 }
 
+/// Redemption counter for a signed download link, keyed by its signature
+#[derive(Clone)]
+pub struct LinkUsage {
+    pub uses: u32,
+}
+
+impl LinkUsage {
+    pub fn get_db() -> Arc<MemDB<LinkUsage>> {
+        LINK_USAGE_DB.clone()
+    }
+}
+
+/// Transfers created against an API key within the current quota window
+#[derive(Clone)]
+pub struct QuotaUsage {
+    pub count: u64,
+}
+
+impl QuotaUsage {
+    pub fn get_db() -> Arc<MemDB<QuotaUsage>> {
+        QUOTA_USAGE_DB.clone()
+    }
+}
+
+/// Suspicious activity attributed to one client IP within the abuse window
+#[derive(Clone, Default)]
+pub struct AbuseRecord {
+    pub idle_codes: u32, // codes created without a following upload
+    pub wrong_receiver: u32, // rejected receiver tokens and taken claims
+    pub misses: u32, // lookups of codes that do not exist
+}
+
+impl AbuseRecord {
+    pub fn get_db() -> Arc<MemDB<AbuseRecord>> {
+        ABUSE_RECORD_DB.clone()
+    }
+}
+
+/// A temporarily banned client IP
+#[derive(Clone)]
+pub struct AbuseBan {
+    pub reason: &'static str,
+}
+
+impl AbuseBan {
+    pub fn get_db() -> Arc<MemDB<AbuseBan>> {
+        ABUSE_BAN_DB.clone()
+    }
+}
+
+impl Footprint for MetaInfo {
+    fn footprint(&self) -> usize {
+        let receivers = self.receivers.iter().map(|r| {
+            std::mem::size_of::<ReceiverClaim>()
+                + r.id.capacity()
+                + r.missing.as_ref().map_or(0, |missing| missing.capacity() * std::mem::size_of::<ByteRange>())
+        });
+        let samples = (self.metrics.upload.samples.capacity() + self.metrics.download.samples.capacity())
+            * std::mem::size_of::<(Instant, u64)>();
+        let owners = self.owners.iter().map(|owner| std::mem::size_of::<String>() + owner.capacity()).sum::<usize>();
+        std::mem::size_of::<Self>() + self.file_name.capacity() + self.sender_nonce.capacity() + receivers.sum::<usize>() + samples + owners
+    }
+}
+
+impl Footprint for FileBlock {
+    fn footprint(&self) -> usize {
+        // Deduplicated payloads live in the shared content store instead
+        let payload = if self.content.is_some() { 0 } else { self.data.len() };
+        let served_to = self.served_to.iter().map(|r| std::mem::size_of::<String>() + r.capacity()).sum::<usize>();
+        std::mem::size_of::<Self>() + payload + self.filename.capacity() + served_to
+    }
+}
+
+impl Footprint for SignalState {
+    fn footprint(&self) -> usize {
+        let messages = self.messages.iter().map(|msg| {
+            std::mem::size_of::<SignalMessage>() + msg.from.capacity() + msg.msg_type.capacity() + msg.data.to_string().len()
+        });
+        let frames = self.frames.iter().map(|frame| std::mem::size_of::<SignalFrame>() + frame.data.len());
+        let peers = self.receivers.iter().map(|peer| std::mem::size_of::<SignalPeer>() + peer.id.capacity());
+        std::mem::size_of::<Self>() + messages.sum::<usize>() + frames.sum::<usize>() + peers.sum::<usize>()
+    }
+}
+
+impl Footprint for LinkUsage {}
+impl Footprint for QuotaUsage {}
+impl Footprint for AbuseRecord {}
+impl Footprint for AbuseBan {}
+
+/// Count and announce transfers whose metadata lapsed before they were marked done
+fn record_meta_expiry(id: &str, meta: &MetaInfo) {
+    if !meta.done {
+        crate::service::stats::record_expired(id);
+        crate::service::feed::publish(crate::service::feed::TRANSFER_EXPIRED, Some(id), serde_json::json!({
+            "file_size": meta.file_size,
+            "uploaded_blocks": meta.metrics.upload.blocks,
+        }));
+    }
+}
+
+fn read_env_ms(key: &str) -> Option<Duration> {
+    read_env_string(key).and_then(|v| v.parse().ok()).map(Duration::from_millis)
+}
+
+/// Cleanup cadence of one store: `<STORE>_CLEANUP_INTERVAL_MS`, else
+/// CLEANUP_INTERVAL_MS (default 1000); each sweep is delayed by up to
+/// CLEANUP_JITTER_MS more (default a tenth of the interval)
+fn cleanup_schedule(store: &str) -> CleanupSchedule {
+    let interval = read_env_ms(&format!("{}_CLEANUP_INTERVAL_MS", store))
+        .or_else(|| read_env_ms("CLEANUP_INTERVAL_MS"))
+        .filter(|interval| !interval.is_zero())
+        .unwrap_or(CLEAN_UP_INTERVAL);
+    let jitter = read_env_ms("CLEANUP_JITTER_MS").unwrap_or(interval / 10);
+    CleanupSchedule { interval, jitter }
+}
+
+lazy_static!{
+    pub static ref META_INFO_DB: Arc<MemDB<MetaInfo>> = Arc::new(MemDB::with_schedule(cleanup_schedule("META"), Some(record_meta_expiry)));
+}
+
+lazy_static!{
+    pub static ref FILE_BLOCK_DB: Arc<MemDB<FileBlock>> = Arc::new(MemDB::with_schedule(cleanup_schedule("BLOCKS"), None));
+}
+
+lazy_static!{
+    pub static ref SIGNAL_DB: Arc<MemDB<SignalState>> = Arc::new(MemDB::with_schedule(cleanup_schedule("SIGNAL"), None));
+}
+
+lazy_static!{
+    pub static ref LINK_USAGE_DB: Arc<MemDB<LinkUsage>> = Arc::new(MemDB::with_schedule(cleanup_schedule("LINK_USAGE"), None));
+}
+
 lazy_static!{
-    pub static ref META_INFO_DB: Arc<MemDB<MetaInfo>> = Arc::new(MemDB::new());
+    pub static ref QUOTA_USAGE_DB: Arc<MemDB<QuotaUsage>> = Arc::new(MemDB::with_schedule(cleanup_schedule("QUOTA_USAGE"), None));
 }
 
 lazy_static!{
-    pub static ref FILE_BLOCK_DB: Arc<MemDB<FileBlock>> = Arc::new(MemDB::new());
+    pub static ref ABUSE_RECORD_DB: Arc<MemDB<AbuseRecord>> = Arc::new(MemDB::with_schedule(cleanup_schedule("ABUSE_RECORDS"), None));
 }
 
 lazy_static!{
-    pub static ref SIGNAL_DB: Arc<MemDB<SignalState>> = Arc::new(MemDB::new());
+    pub static ref ABUSE_BAN_DB: Arc<MemDB<AbuseBan>> = Arc::new(MemDB::with_schedule(cleanup_schedule("ABUSE_BANS"), None));
 }
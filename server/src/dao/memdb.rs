@@ -1,10 +1,70 @@
-use std::{collections::HashMap, sync::Arc, time::Instant};
+use std::{collections::HashMap, sync::{Arc, Mutex, PoisonError}, time::Instant};
 use tokio::sync::RwLock;
 use tokio::time::Duration;
 use tracing::{event, Level};
 
+/// How often the cleanup loop sweeps expired entries unless configured otherwise
+pub const CLEAN_UP_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct MemDB<T> {
     pub store: Arc<RwLock<HashMap<String, CacheEntry<T>>>>,
+    cleanup: Arc<Mutex<CleanupStats>>,
+    schedule: CleanupSchedule,
+}
+
+/// Pause between cleanup sweeps; each pause adds a random share of `jitter`
+/// so stores do not all take their write locks on the same tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CleanupSchedule {
+    pub interval: Duration,
+    pub jitter: Duration,
+}
+
+impl Default for CleanupSchedule {
+    fn default() -> Self {
+        Self { interval: CLEAN_UP_INTERVAL, jitter: Duration::ZERO }
+    }
+}
+
+impl CleanupSchedule {
+    fn next_pause(&self) -> Duration {
+        let jitter = self.jitter.as_millis() as u64;
+        self.interval + Duration::from_millis(if jitter == 0 { 0 } else { rand::random_range(0..=jitter) })
+    }
+}
+
+/// Called by the cleanup loop for every entry it evicts; runs under the write lock
+pub type ExpiryHook<T> = fn(&str, &T);
+
+/// Approximate heap footprint of a stored value, for introspection. The
+/// default only counts the value itself; types owning buffers override it
+pub trait Footprint {
+    fn footprint(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+/// Timing of the cleanup loop
+#[derive(Clone, Copy, Default)]
+struct CleanupStats {
+    last_run: Option<Instant>,
+    last_sweep: Duration, // time the last sweep held the write lock
+    max_sweep: Duration,
+    last_removed: usize,
+}
+
+/// Point-in-time accounting of one store
+pub struct StoreStats {
+    pub entries: usize,
+    pub expired_pending: usize, // lapsed entries the cleanup loop has not swept yet
+    pub approx_bytes: usize,
+    pub next_expiry: Option<Duration>,
+    pub last_expiry: Option<Duration>,
+    pub cleanup_lag: Option<Duration>, // how far the cleanup loop is behind its interval
+    pub last_sweep: Duration,
+    pub max_sweep: Duration,
+    pub last_removed: usize,
+    pub schedule: CleanupSchedule,
 }
 
 #[derive(Clone)]
@@ -13,25 +73,49 @@ pub struct CacheEntry<T> {
     pub exp: Instant,
 }
 
-impl <T: Send + Sync + Clone + 'static> MemDB<T> {
+impl <T: Send + Sync + Clone + Footprint + 'static> MemDB<T> {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::with_schedule(CleanupSchedule::default(), None)
+    }
+
+    pub fn with_schedule(schedule: CleanupSchedule, on_expire: Option<ExpiryHook<T>>) -> Self {
         let cache = MemDB {
             store: Arc::new(RwLock::new(HashMap::new())),
+            cleanup: Arc::new(Mutex::new(CleanupStats::default())),
+            schedule,
         };
 
         let store_clone = cache.store.clone();
+        let cleanup_clone = cache.cleanup.clone();
         
         tokio::spawn(async move {
-            let clean_up_interval = Duration::from_secs(1);
             let mut last_count: usize = 0;
 
             loop {
-                tokio::time::sleep(clean_up_interval).await;
+                tokio::time::sleep(schedule.next_pause()).await;
                 let now = Instant::now();
                 let mut store = store_clone.write().await;
                 let count_before = store.len();
-                store.retain(|_, entry| entry.exp > now);
+                store.retain(|key, entry| {
+                    let live = entry.exp > now;
+                    if let (false, Some(hook)) = (live, on_expire) {
+                        hook(key, &entry.value);
+                    }
+                    live
+                });
                 let count_after = store.len();
+                drop(store);
+
+                let sweep = now.elapsed();
+                let mut cleanup = cleanup_clone.lock().unwrap_or_else(PoisonError::into_inner);
+                *cleanup = CleanupStats {
+                    last_run: Some(now),
+                    last_sweep: sweep,
+                    max_sweep: cleanup.max_sweep.max(sweep),
+                    last_removed: count_before - count_after,
+                };
+                drop(cleanup);
                 
                 // Only log when there are actual changes to reduce log noise
                 // Changed from DEBUG to TRACE to reduce log verbosity
@@ -46,6 +130,42 @@ impl <T: Send + Sync + Clone + 'static> MemDB<T> {
         cache
     }
 
+    /// Entry count, approximate memory use, expiry spread and cleanup timing
+    pub async fn stats(&self) -> StoreStats {
+        let now = Instant::now();
+        let store = self.store.read().await;
+        let mut stats = StoreStats {
+            entries: store.len(),
+            expired_pending: 0,
+            approx_bytes: 0,
+            next_expiry: None,
+            last_expiry: None,
+            cleanup_lag: None,
+            last_sweep: Duration::ZERO,
+            max_sweep: Duration::ZERO,
+            last_removed: 0,
+            schedule: self.schedule,
+        };
+        for (key, entry) in store.iter() {
+            stats.approx_bytes += key.len() + std::mem::size_of::<CacheEntry<T>>() + entry.value.footprint();
+            match entry.exp.checked_duration_since(now) {
+                Some(left) => {
+                    stats.next_expiry = Some(stats.next_expiry.map_or(left, |next| next.min(left)));
+                    stats.last_expiry = Some(stats.last_expiry.map_or(left, |last| last.max(left)));
+                }
+                None => stats.expired_pending += 1,
+            }
+        }
+        drop(store);
+
+        let cleanup = *self.cleanup.lock().unwrap_or_else(PoisonError::into_inner);
+        stats.cleanup_lag = cleanup.last_run.map(|run| now.duration_since(run).saturating_sub(self.schedule.interval + self.schedule.jitter));
+        stats.last_sweep = cleanup.last_sweep;
+        stats.max_sweep = cleanup.max_sweep;
+        stats.last_removed = cleanup.last_removed;
+        stats
+    }
+
     pub async fn insert(&self, key: &str, value: T, ttl_secs: u64) -> Result<(), String> {
         let exp = Instant::now() + Duration::from_secs(ttl_secs);
         let entry = CacheEntry { value, exp };
@@ -60,6 +180,25 @@ impl <T: Send + Sync + Clone + 'static> MemDB<T> {
         Ok(())
     }
 
+    /// Insert the value only if no live entry exists for the key.
+    /// Returns `Ok(false)` without touching the store when the key is taken.
+    pub async fn insert_if_absent(&self, key: &str, value: T, ttl_secs: u64) -> Result<bool, String> {
+        let now = Instant::now();
+        let mut store = self.store.write().await;
+
+        if store.get(key).is_some_and(|entry| entry.exp > now) {
+            event!(Level::TRACE, "Key already present, skipped insert: {}", key);
+            return Ok(false);
+        }
+
+        let exp = now + Duration::from_secs(ttl_secs);
+        store.insert(key.to_owned(), CacheEntry { value, exp });
+
+        event!(Level::TRACE, "Inserted absent key: {} with TTL: {}s", key, ttl_secs);
+        Ok(true)
+    }
+
+    #[cfg(test)]
     pub async fn update(&self, key: &str, value: T, exp: Instant) -> Result<(), String> {
         let entry = CacheEntry { value, exp };
         
@@ -73,6 +212,57 @@ impl <T: Send + Sync + Clone + 'static> MemDB<T> {
         Ok(())
     }
 
+    /// Bring a live entry's expiry forward to at most `ttl_secs` from now.
+    /// Returns false when the key is missing or expired
+    pub async fn shorten_ttl(&self, key: &str, ttl_secs: u64) -> bool {
+        let now = Instant::now();
+        let mut store = self.store.write().await;
+        let Some(entry) = store.get_mut(key).filter(|entry| entry.exp > now) else {
+            return false;
+        };
+        entry.exp = entry.exp.min(now + Duration::from_secs(ttl_secs));
+
+        event!(Level::TRACE, "Shortened TTL of key: {} to {}s", key, ttl_secs);
+        true
+    }
+
+    /// Push a live entry's expiry out to at least `ttl_secs` from now.
+    /// Returns false when the key is missing or expired
+    pub async fn touch(&self, key: &str, ttl_secs: u64) -> bool {
+        let now = Instant::now();
+        let mut store = self.store.write().await;
+        let Some(entry) = store.get_mut(key).filter(|entry| entry.exp > now) else {
+            return false;
+        };
+        entry.exp = entry.exp.max(now + Duration::from_secs(ttl_secs));
+
+        event!(Level::TRACE, "Extended TTL of key: {} to {}s", key, ttl_secs);
+        true
+    }
+
+    /// Remove every entry whose key starts with the prefix, returning how many were dropped
+    pub async fn remove_prefix(&self, prefix: &str) -> usize {
+        let mut store = self.store.write().await;
+        let count_before = store.len();
+        store.retain(|key, _| !key.starts_with(prefix));
+        let removed = count_before - store.len();
+
+        event!(Level::TRACE, "Removed {} keys with prefix: {}", removed, prefix);
+        removed
+    }
+
+    /// Atomically mutate a live entry in place, keeping its expiry.
+    /// Returns `None` when the key is missing or expired.
+    pub async fn modify<R>(&self, key: &str, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let now = Instant::now();
+        let mut store = self.store.write().await;
+        let entry = store.get_mut(key).filter(|entry| entry.exp > now)?;
+        let result = f(&mut entry.value);
+
+        event!(Level::TRACE, "Modified key: {}", key);
+        Some(result)
+    }
+
     pub async fn get(&self, key: &str) -> Option<CacheEntry<T>> {
         let store = self.store.read().await;
         let result = store.get(key).cloned();
@@ -102,4 +292,79 @@ impl <T: Send + Sync + Clone + 'static> MemDB<T> {
         
         result
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Footprint for u32 {}
+
+    #[tokio::test]
+    async fn test_stats() {
+        let db: MemDB<u32> = MemDB::new();
+        db.insert("a", 1, 60).await.unwrap();
+        db.update("b", 2, Instant::now()).await.unwrap();
+        let stats = db.stats().await;
+        assert_eq!((stats.entries, stats.expired_pending), (2, 1));
+        assert!(stats.next_expiry.is_some_and(|left| left <= Duration::from_secs(60)));
+        assert!(stats.approx_bytes >= 2 * std::mem::size_of::<CacheEntry<u32>>());
+    }
+
+    #[tokio::test]
+    async fn test_insert_if_absent() {
+        let db: MemDB<u32> = MemDB::new();
+        assert!(db.insert_if_absent("a", 1, 60).await.unwrap());
+        assert!(!db.insert_if_absent("a", 2, 60).await.unwrap());
+        assert_eq!(db.get("a").await.unwrap().value, 1);
+    }
+
+    #[tokio::test]
+    async fn test_modify() {
+        let db: MemDB<u32> = MemDB::new();
+        assert_eq!(db.modify("a", |v| *v += 1).await, None);
+        db.insert("a", 1, 60).await.unwrap();
+        assert_eq!(db.modify("a", |v| { *v += 1; *v }).await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_shorten_ttl() {
+        let db: MemDB<u32> = MemDB::new();
+        assert!(!db.shorten_ttl("a", 0).await);
+        db.insert("a", 1, 60).await.unwrap();
+        assert!(db.shorten_ttl("a", 0).await);
+        assert_eq!(db.modify("a", |_| ()).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_touch() {
+        let db: MemDB<u32> = MemDB::new();
+        assert!(!db.touch("a", 60).await);
+        db.insert("a", 1, 1).await.unwrap();
+        assert!(db.touch("a", 60).await);
+        let exp = db.get("a").await.unwrap().exp;
+        assert!(exp > Instant::now() + Duration::from_secs(30));
+        // Never shortens
+        assert!(db.touch("a", 1).await);
+        assert_eq!(db.get("a").await.unwrap().exp, exp);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_schedule() {
+        let schedule = CleanupSchedule { interval: Duration::from_millis(10), jitter: Duration::from_millis(5) };
+        let db: MemDB<u32> = MemDB::with_schedule(schedule, None);
+        db.update("a", 1, Instant::now()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let stats = db.stats().await;
+        assert_eq!((stats.entries, stats.schedule), (0, schedule));
+        assert!((0..5).all(|_| (schedule.interval..=schedule.interval + schedule.jitter).contains(&schedule.next_pause())));
+    }
+
+    #[tokio::test]
+    async fn test_insert_if_absent_replaces_expired() {
+        let db: MemDB<u32> = MemDB::new();
+        db.update("a", 1, Instant::now()).await.unwrap();
+        assert!(db.insert_if_absent("a", 2, 60).await.unwrap());
+        assert_eq!(db.get("a").await.unwrap().value, 2);
+    }
+}
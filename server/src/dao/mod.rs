@@ -1,2 +1,3 @@
+pub mod content;
 pub mod db;
 mod memdb;
\ No newline at end of file
@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::serve::Listener;
+use lazy_static::lazy_static;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{sleep, Instant, Sleep},
+};
+use tracing::{event, Level};
+
+use crate::utils::env::{read_env_u64, read_env_usize};
+
+lazy_static! {
+    static ref MAX_CONNECTIONS: usize = read_env_usize("MAX_CONNECTIONS", 0);
+    static ref MAX_CONNECTIONS_PER_IP: usize = read_env_usize("MAX_CONNECTIONS_PER_IP", 0);
+    static ref KEEP_ALIVE_TIMEOUT_SECS: u64 = read_env_u64("KEEP_ALIVE_TIMEOUT_SECS", 90);
+    static ref CONNECTIONS_PER_IP: Mutex<HashMap<IpAddr, usize>> = Mutex::new(HashMap::new());
+    // Shared by every listener so the cap covers the whole process
+    static ref CONNECTION_PERMITS: Option<Arc<Semaphore>> =
+        (*MAX_CONNECTIONS > 0).then(|| Arc::new(Semaphore::new(*MAX_CONNECTIONS)));
+}
+
+/// Cap on simultaneously open client connections across all listeners; further
+/// clients wait in the accept backlog (default unlimited, configurable via MAX_CONNECTIONS)
+pub fn max_connections() -> Option<usize> {
+    (*MAX_CONNECTIONS > 0).then_some(*MAX_CONNECTIONS)
+}
+
+/// Cap on open connections from one peer address; excess connections are closed
+/// right away. Behind a reverse proxy every connection comes from the proxy
+/// (default unlimited, configurable via MAX_CONNECTIONS_PER_IP)
+fn max_connections_per_ip() -> Option<usize> {
+    (*MAX_CONNECTIONS_PER_IP > 0).then_some(*MAX_CONNECTIONS_PER_IP)
+}
+
+/// How long a connection may go without reading or writing a byte before it is
+/// closed, covering idle keep-alive sockets and stalled peers; longer than every
+/// request timeout (default 90s, configurable via KEEP_ALIVE_TIMEOUT_SECS)
+pub fn keep_alive_timeout() -> Duration {
+    Duration::from_secs(*KEEP_ALIVE_TIMEOUT_SECS)
+}
+
+/// Connections currently held open, when a cap is configured
+pub fn open_connections() -> Option<usize> {
+    CONNECTION_PERMITS.as_ref().map(|permits| *MAX_CONNECTIONS - permits.available_permits())
+}
+
+/// Take a slot for `ip` unless it already holds `cap` connections
+fn claim_slot(counts: &mut HashMap<IpAddr, usize>, ip: IpAddr, cap: usize) -> bool {
+    let count = counts.entry(ip).or_insert(0);
+    if *count >= cap {
+        return false;
+    }
+    *count += 1;
+    true
+}
+
+fn release_slot(counts: &mut HashMap<IpAddr, usize>, ip: IpAddr) {
+    if let Some(count) = counts.get_mut(&ip) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(&ip);
+        }
+    }
+}
+
+/// Peer address of an accepted connection, where it has one
+pub trait PeerIp {
+    fn peer_ip(&self) -> Option<IpAddr>;
+}
+
+impl PeerIp for SocketAddr {
+    fn peer_ip(&self) -> Option<IpAddr> {
+        Some(self.ip())
+    }
+}
+
+#[cfg(unix)]
+impl PeerIp for tokio::net::unix::SocketAddr {
+    fn peer_ip(&self) -> Option<IpAddr> {
+        None
+    }
+}
+
+/// Listener enforcing MAX_CONNECTIONS, MAX_CONNECTIONS_PER_IP and the
+/// keep-alive idle timeout on what it accepts
+pub struct Limited<L> {
+    inner: L,
+}
+
+impl<L> Limited<L> {
+    pub fn new(inner: L) -> Self {
+        Limited { inner }
+    }
+}
+
+impl<L> Listener for Limited<L>
+where
+    L: Listener,
+    L::Addr: PeerIp,
+{
+    type Io = Tracked<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            // Waiting for a slot before accepting leaves the excess queued in the kernel
+            let permit = match CONNECTION_PERMITS.as_ref() {
+                Some(permits) => {
+                    if permits.available_permits() == 0 {
+                        event!(Level::DEBUG, "Connection limit of {} reached, holding new clients", *MAX_CONNECTIONS);
+                    }
+                    permits.clone().acquire_owned().await.ok()
+                }
+                None => None,
+            };
+            let (io, addr) = self.inner.accept().await;
+
+            let ip = match (addr.peer_ip(), max_connections_per_ip()) {
+                (Some(ip), Some(cap)) => {
+                    if !claim_slot(&mut CONNECTIONS_PER_IP.lock().unwrap_or_else(PoisonError::into_inner), ip, cap) {
+                        // Dropping the stream closes it and frees the global slot
+                        event!(Level::DEBUG, "Closed connection from {}: {} already open", ip, cap);
+                        continue;
+                    }
+                    Some(ip)
+                }
+                _ => None,
+            };
+            let timeout = keep_alive_timeout();
+            return (Tracked { io, ip, timeout, idle: Box::pin(sleep(timeout)), _permit: permit }, addr);
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+/// An accepted connection; its slots are released when the connection is dropped
+pub struct Tracked<T> {
+    io: T,
+    ip: Option<IpAddr>,
+    timeout: Duration,
+    /// Fires once the connection has been silent for `timeout`
+    idle: Pin<Box<Sleep>>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<T> Tracked<T> {
+    /// Push the idle deadline out after bytes moved
+    fn touch(&mut self) {
+        let deadline = Instant::now() + self.timeout;
+        self.idle.as_mut().reset(deadline);
+    }
+
+    /// Turn a stalled operation into an error once the idle deadline passed;
+    /// hyper then drops the connection
+    fn check_idle<R>(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<R>> {
+        match self.idle.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "connection idle"))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        if let Some(ip) = self.ip {
+            release_slot(&mut CONNECTIONS_PER_IP.lock().unwrap_or_else(PoisonError::into_inner), ip);
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Tracked<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.io).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                if buf.filled().len() > filled {
+                    self.touch();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => self.check_idle(cx),
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Tracked<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match Pin::new(&mut self.io).poll_write(cx, buf) {
+            Poll::Ready(result) => {
+                if result.as_ref().is_ok_and(|written| *written > 0) {
+                    self.touch();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => self.check_idle(cx),
+        }
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match Pin::new(&mut self.io).poll_write_vectored(cx, bufs) {
+            Poll::Ready(result) => {
+                if result.as_ref().is_ok_and(|written| *written > 0) {
+                    self.touch();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => self.check_idle(cx),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_ip_slots() {
+        let mut counts = HashMap::new();
+        let ip: IpAddr = "192.0.2.7".parse().unwrap();
+        assert!(claim_slot(&mut counts, ip, 2));
+        assert!(claim_slot(&mut counts, ip, 2));
+        assert!(!claim_slot(&mut counts, ip, 2));
+        release_slot(&mut counts, ip);
+        assert!(claim_slot(&mut counts, ip, 2));
+        release_slot(&mut counts, ip);
+        release_slot(&mut counts, ip);
+        assert!(counts.is_empty());
+    }
+}
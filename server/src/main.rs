@@ -1,9 +1,11 @@
-use std::env;
+use std::{env, num::NonZeroUsize, process, thread};
+use tokio::runtime::{Builder, Runtime};
 use tracing::{event, Level};
 use tracing_subscriber::{EnvFilter, fmt::format::FmtSpan};
 use dotenvy::dotenv;
 
 mod dao;
+mod listener;
 mod router;
 mod service;
 mod utils;
@@ -11,8 +13,26 @@ mod utils;
 const DEFAULT_HOST: &str = "0.0.0.0";
 const DEFAULT_PORT: &str = "5000";
 
-#[tokio::main]
-async fn main() {
+/// Worker threads driving async tasks (default one per CPU, configurable via WORKER_THREADS)
+fn worker_threads() -> usize {
+    let cpus = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    utils::env::read_env_usize("WORKER_THREADS", cpus)
+}
+/// Upper bound of the pool running blocking work such as file reads (default 512, configurable via MAX_BLOCKING_THREADS)
+fn max_blocking_threads() -> usize {
+    utils::env::read_env_usize("MAX_BLOCKING_THREADS", 512)
+}
+
+fn build_runtime() -> std::io::Result<Runtime> {
+    Builder::new_multi_thread()
+        .worker_threads(worker_threads())
+        .max_blocking_threads(max_blocking_threads())
+        .thread_name("fileflow-worker")
+        .enable_all()
+        .build()
+}
+
+fn main() {
     // Load environment variables from .env if present
     dotenv().ok();
 
@@ -28,10 +48,41 @@ async fn main() {
         .with_span_events(FmtSpan::NONE) // 减少span事件的日志输出
         .init();
 
+    let runtime = match build_runtime() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            event!(Level::ERROR, "Failed to build runtime: {}", e);
+            process::exit(1);
+        }
+    };
+    event!(
+        Level::DEBUG,
+        "Runtime with {} worker threads, up to {} blocking threads, {} connections",
+        worker_threads(),
+        max_blocking_threads(),
+        listener::max_connections().map_or("unlimited".to_string(), |max| max.to_string())
+    );
+
     let host = env::var("FILEFLOW_HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string());
     let port = env::var("FILEFLOW_PORT").unwrap_or_else(|_| DEFAULT_PORT.to_string());
 
+    // FILEFLOW_LISTEN takes a comma-separated address list; otherwise a Unix
+    // socket path replaces the single host:port TCP listener
+    let listens = match utils::env::read_env_string("FILEFLOW_LISTEN") {
+        Some(raw) => raw
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(router::ListenAddr::parse)
+            .collect(),
+        None => match utils::env::read_env_string("FILEFLOW_UNIX_SOCKET") {
+            #[cfg(unix)]
+            Some(path) => vec![router::ListenAddr::Unix(path.into())],
+            _ => vec![router::ListenAddr::Tcp(format!("{}:{}", host, port))],
+        },
+    };
+
     event!(Level::INFO, "FileFlow server started");
     
-    router::start_server(&host, &port).await;
+    runtime.block_on(router::start_server(listens));
 }
@@ -1,14 +1,100 @@
-use axum::{routing::{get, post, put}, serve, Router};
-use tokio::net::{TcpListener};
-use tower_http::timeout::TimeoutLayer;
+use axum::{
+    http::{header, Extensions, HeaderMap, StatusCode, Version},
+    middleware, routing::{any, delete, get, post, put}, serve, serve::ListenerExt, Router,
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{net::{lookup_host, TcpListener}, task::JoinSet};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::{predicate::{Predicate, SizeAbove}, CompressionLayer},
+    timeout::TimeoutLayer,
+};
+use lazy_static::lazy_static;
 use tracing::{event, instrument, Level};
-use std::time::Duration;
+use std::{io, net::SocketAddr, time::Duration};
+#[cfg(unix)]
+use axum::{extract::ConnectInfo, Extension};
+#[cfg(unix)]
+use std::{net::{Ipv4Addr, SocketAddrV4}, os::unix::fs::FileTypeExt, path::PathBuf};
 
+use crate::listener::Limited;
+use crate::service::{abuse, access_log, portmap, proxy, tenant};
+use crate::utils::env::{read_env_u64, read_env_usize};
+use crate::service::admin::{close_room, get_stats, get_stores, list_rooms};
+use crate::service::delta::{get_missing_ranges, report_ranges, seek};
+use crate::service::discovery::{self, get_discover};
+use crate::service::events::receiver_events;
+use crate::service::feed::admin_events;
 use crate::service::handler::{*};
-use tower_http::services::ServeDir;
+use crate::service::history::list_my_transfers;
+use crate::service::info::{get_flags, get_info};
+use crate::service::static_files::panic_page;
+use crate::service::signaling::{get_signal, get_signal_frame, leave_signal, ping_signal, post_signal, post_signal_frame};
+use crate::service::webdav::{dav_collection, dav_file};
+use crate::service::webrtc::{get_p2p_config, get_turn_credentials};
+
+lazy_static! {
+    static ref API_TIMEOUT_SECS: u64 = read_env_u64("API_TIMEOUT_SECS", 20);
+    static ref SIGNAL_TIMEOUT_SECS: u64 = read_env_u64("SIGNAL_TIMEOUT_SECS", 20);
+    static ref UPLOAD_TIMEOUT_SECS: u64 = read_env_u64("UPLOAD_TIMEOUT_SECS", 120);
+    static ref DOWNLOAD_TIMEOUT_SECS: u64 = read_env_u64("DOWNLOAD_TIMEOUT_SECS", 60);
+    static ref ACCEPTORS: usize = read_env_usize("ACCEPTORS", 1);
+}
+
+/// Sockets bound with SO_REUSEPORT per TCP address, each with its own accept
+/// loop so the kernel spreads new connections across them (default 1, configurable via ACCEPTORS)
+fn acceptors() -> usize {
+    if cfg!(unix) {
+        *ACCEPTORS
+    } else {
+        1
+    }
+}
+
+/// Request timeout for control endpoints (default 20s, configurable via API_TIMEOUT_SECS)
+fn api_timeout() -> TimeoutLayer {
+    TimeoutLayer::new(Duration::from_secs(*API_TIMEOUT_SECS))
+}
+/// Request timeout for signaling endpoints (default 20s, configurable via SIGNAL_TIMEOUT_SECS)
+fn signal_timeout() -> TimeoutLayer {
+    TimeoutLayer::new(Duration::from_secs(*SIGNAL_TIMEOUT_SECS))
+}
+/// Request timeout for block uploads, which carry a whole block body over the
+/// sender's uplink (default 120s, configurable via UPLOAD_TIMEOUT_SECS)
+fn upload_timeout() -> TimeoutLayer {
+    TimeoutLayer::new(Duration::from_secs(*UPLOAD_TIMEOUT_SECS))
+}
+/// Request timeout for block downloads, including the wait for a block that is
+/// still being uploaded (default 60s, configurable via DOWNLOAD_TIMEOUT_SECS)
+fn download_timeout() -> TimeoutLayer {
+    TimeoutLayer::new(Duration::from_secs(*DOWNLOAD_TIMEOUT_SECS))
+}
+
+/// Bodies smaller than this are not worth a gzip frame
+const COMPRESS_MIN_BYTES: u16 = 256;
+
+/// Whether a response is markup or JSON that shrinks well; event streams are
+/// left alone so every event goes out as soon as it is written
+fn is_compressible(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    (mime.starts_with("text/") && mime != "text/event-stream")
+        || matches!(mime.as_str(), "application/json" | "application/javascript" | "application/xml" | "image/svg+xml")
+}
+
+/// Gzip for pages and API replies; relayed blocks must go out byte for byte, so
+/// this is only layered onto routes that never serve them
+fn compression() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .gzip(true)
+        .compress_when(SizeAbove::new(COMPRESS_MIN_BYTES).and(is_compressible))
+}
 
 fn api_router() -> Router {
-    Router::new()
+    // Each group carries its own timeout; merging keeps the layers scoped to their routes
+    let control = Router::new()
         .route("/hello", get(|| async {
             // Changed from DEBUG to TRACE to reduce log verbosity
             event!(Level::TRACE, "Hello endpoint accessed");
@@ -16,21 +102,81 @@ fn api_router() -> Router {
         }))
         .route("/id", get(get_id))
         .route("/p2p-config", get(get_p2p_config))
+        .route("/webrtc/turn", get(get_turn_credentials))
         .route("/{id}/status", get(get_status))
-        .route("/{id}/signal", get(get_signal).post(post_signal))
-        // Add timeout layer specifically for upload api
-        .route("/{id}/upload", post(upload_file))
-        .layer(TimeoutLayer::new(Duration::from_secs(20)))
-        // Add timeout layer specifically for download api
-        .route("/{id}/file", get(get_file))
-        .layer(TimeoutLayer::new(Duration::from_secs(20)))
+        .route("/{id}/meta", get(get_meta).post(update_meta))
+        .route("/{id}/claim", post(claim))
+        .route("/{id}/receivers", get(list_receivers))
+        .route("/{id}/receivers/{rid}", post(review_receiver))
+        .route("/{id}/ranges", get(get_missing_ranges).post(report_ranges))
+        .route("/{id}/seek", post(seek))
+        .route("/{id}/link", post(create_link))
+        .route("/{id}/cancel", post(cancel))
+        .route("/{id}/pause", post(pause))
+        .route("/{id}/resume", post(resume))
+        .route("/{id}/redeem", post(redeem_link))
+        .route("/{id}/handoff", post(create_handoff))
+        .route("/{id}/handoff/redeem", post(redeem_handoff))
         .route("/{id}/done", put(done))
-        .layer(TimeoutLayer::new(Duration::from_secs(20)))
+        .layer(api_timeout())
+        .layer(compression());
+
+    let signaling = Router::new()
+        .route("/{id}/signal", get(get_signal).post(post_signal))
+        .route("/{id}/signal/ping", post(ping_signal))
+        .route("/{id}/signal/binary", get(get_signal_frame).post(post_signal_frame))
+        .route("/{id}/signal/leave", post(leave_signal))
+        .route("/{id}/receiver-events", get(receiver_events))
+        .layer(signal_timeout())
+        .layer(compression());
+
+    let transfer = Router::new()
+        .route("/{id}/upload", post(upload_file).layer(upload_timeout()))
+        .route("/{id}/file", get(get_file).layer(download_timeout()));
+
+    control
+        .merge(signaling)
+        .merge(transfer)
+        // Abuse heuristics see every transfer API call, including its outcome
+        .layer(middleware::from_fn(abuse::guard))
+}
+
+fn admin_router() -> Router {
+    Router::new()
+        .route("/rooms", get(list_rooms))
+        .route("/rooms/{id}", delete(close_room))
+        .route("/stats", get(get_stats))
+        .route("/stores", get(get_stores))
+        .route("/events", get(admin_events))
+}
+
+/// Current API revision; mounted under `/api/v1` and, unversioned, under
+/// `/api` so bookmarked pages and older CLI builds keep working
+fn api_v1_router() -> Router {
+    Router::new()
+        .route("/info", get(get_info).layer(compression()))
+        .route("/flags", get(get_flags).layer(compression()))
+        .route("/discover", get(get_discover).layer(compression()))
+        .route("/my/transfers", get(list_my_transfers).layer(compression()))
+        .nest("/fileflow", api_router())
+        .nest("/admin", admin_router().layer(compression()))
+        .fallback(api_not_found)
+        // API panics answer with a JSON 500 rather than the HTML error page
+        .layer(CatchPanicLayer::custom(api_panic))
 }
 
 fn assets_router() -> Router {
     Router::new()
-        .route("/{path}", get(get_assets))
+        .route("/{*path}", get(get_assets))
+}
+
+/// Read-only WebDAV view of a claimed transfer; PROPFIND and friends are not
+/// axum method filters, so the handlers dispatch on the method themselves
+fn dav_router() -> Router {
+    Router::new()
+        .route("/{id}/{token}", any(dav_collection))
+        .route("/{id}/{token}/", any(dav_collection))
+        .route("/{id}/{token}/{name}", any(dav_file))
 }
 
 fn view_router() -> Router {
@@ -38,39 +184,187 @@ fn view_router() -> Router {
         .route("/", get(home))
         .route("/upload", get(upload))
         .route("/download", get(download))
-        .route("/{id}/file", get(download))
+        .route("/{id}/file", get(download_transfer))
+}
+
+/// Peer address reported for connections accepted on a Unix socket, which
+/// carry no IP; they all come from the local reverse proxy
+#[cfg(unix)]
+const UNIX_PEER: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+
+/// Where the server accepts connections
+#[derive(Debug)]
+pub enum ListenAddr {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl ListenAddr {
+    /// Parse one FILEFLOW_LISTEN entry: `host:port`, `[v6]:port` or `unix:/path`
+    pub fn parse(raw: &str) -> Self {
+        #[cfg(unix)]
+        if let Some(path) = raw.strip_prefix("unix:") {
+            return ListenAddr::Unix(path.into());
+        }
+        ListenAddr::Tcp(raw.to_string())
+    }
+}
+
+/// A bound listener waiting for the router
+enum Bound {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
 }
 
 #[instrument(skip_all)]
-pub async fn start_server(ip: &str, port: &str) {
+pub async fn start_server(listens: Vec<ListenAddr>) {
     // Changed from INFO to DEBUG to reduce log verbosity
-    event!(Level::INFO, "Initializing server with ip: {} and port: {}", ip, port);
+    event!(Level::INFO, "Initializing server on {:?}", listens);
     
-    let app = Router::new()
+    let routes = Router::new()
         .merge(view_router())
-        .nest("/api/fileflow", api_router())
         .nest("/assets", assets_router())
-        // 添加静态文件服务，用于提供web/dist目录中的文件
-        .fallback_service(ServeDir::new("../web/dist"));
-
-    let addr = format!("{}:{}", ip, port);
-    let listener = match TcpListener::bind(&addr).await {
-        Ok(l) => l,
-        Err(e) => {
-            event!(Level::ERROR, "Failed to bind to address {}: {}", addr, e);
-            return;
-        }
-    };
+        // 从内嵌的 StaticFiles 提供前端文件，未匹配的路由回退到 index.html
+        .fallback(spa_fallback)
+        // Assets with a precompressed variant already carry Content-Encoding and pass through
+        .layer(compression())
+        // 页面类路由发生 panic 时返回内嵌的 500 页面
+        .layer(CatchPanicLayer::custom(panic_page))
+        .nest("/api/v1", api_v1_router())
+        // 未带版本号的旧路径作为 v1 的别名保留
+        .nest("/api", api_v1_router())
+        .nest("/dav", dav_router());
+    // The tenant prefix is stripped before the inner router matches the path
+    let app = Router::new()
+        .fallback_service(routes)
+        .layer(middleware::from_fn(tenant::resolve))
+        .layer(middleware::from_fn(access_log::log))
+        // Resolve the real client behind trusted proxies before anything reads the peer address
+        .layer(middleware::from_fn(proxy::resolve_client));
 
-    // Changed from INFO to DEBUG to reduce log verbosity
-    event!(Level::DEBUG, "Server listening on {}", addr);
-    match serve(listener, app).await {
-        Ok(_) => {
-            // Changed from INFO to DEBUG to reduce log verbosity
-            event!(Level::DEBUG, "Server stopped");
+    // `[::]` is dual-stack by default, which would collide with an explicit IPv4 listener
+    let only_v6 = listens.iter().any(|listen| {
+        matches!(listen, ListenAddr::Tcp(addr) if addr.parse::<SocketAddr>().is_ok_and(|addr| addr.is_ipv4()))
+    });
+
+    // Bind everything up front so a bad address fails startup instead of running half the listeners
+    let mut bound = Vec::with_capacity(listens.len());
+    let acceptors = acceptors();
+    for listen in &listens {
+        let listeners = match listen {
+            ListenAddr::Tcp(addr) => bind_tcp_acceptors(addr, only_v6, acceptors).await,
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => bind_unix(path).map(|listener| vec![Bound::Unix(listener)]),
+        };
+        match listeners {
+            Ok(listeners) => bound.extend(listeners),
+            Err(e) => {
+                event!(Level::ERROR, "Failed to bind to {:?}: {}", listen, e);
+                return;
+            }
         }
-        Err(e) => {
-            event!(Level::ERROR, "Server error: {}", e);
+        // Changed from INFO to DEBUG to reduce log verbosity
+        event!(Level::DEBUG, "Server listening on {:?}", listen);
+    }
+    if acceptors > 1 {
+        event!(Level::INFO, "Accepting TCP connections with {} SO_REUSEPORT sockets per address", acceptors);
+    }
+
+    if bound.is_empty() {
+        event!(Level::ERROR, "No listen address configured");
+        return;
+    }
+
+    // LAN clients and the router mapping are pointed at the first TCP listener
+    if let Some(port) = bound.iter().find_map(|listener| match listener {
+        Bound::Tcp(listener) => listener.local_addr().ok().map(|addr| addr.port()),
+        #[cfg(unix)]
+        Bound::Unix(_) => None,
+    }) {
+        discovery::start(port);
+        portmap::start(port);
+    }
+
+    let mut servers = JoinSet::new();
+    for listener in bound {
+        let app = app.clone();
+        match listener {
+            // The no-op tap is what lets a wrapped listener still report the peer address
+            Bound::Tcp(listener) => servers.spawn(async move {
+                serve(Limited::new(listener).tap_io(|_| {}), app.into_make_service_with_connect_info::<SocketAddr>()).await
+            }),
+            // Handlers expect a peer address, so every connection gets the loopback placeholder
+            #[cfg(unix)]
+            Bound::Unix(listener) => {
+                servers.spawn(async move { serve(Limited::new(listener), app.layer(Extension(ConnectInfo(UNIX_PEER)))).await })
+            }
+        };
+    }
+
+    while let Some(result) = servers.join_next().await {
+        match result {
+            Ok(Ok(_)) => {
+                // Changed from INFO to DEBUG to reduce log verbosity
+                event!(Level::DEBUG, "Server stopped");
+            }
+            Ok(Err(e)) => {
+                event!(Level::ERROR, "Server error: {}", e);
+            }
+            Err(e) => {
+                event!(Level::ERROR, "Listener task failed: {}", e);
+            }
         }
     }
 }
+
+/// Bind `count` listeners sharing one TCP address; they all take the port the
+/// first one got, which matters when the configured port is 0
+async fn bind_tcp_acceptors(addr: &str, only_v6: bool, count: usize) -> io::Result<Vec<Bound>> {
+    let reuse_port = count > 1;
+    let first = bind_tcp(addr, only_v6, reuse_port).await?;
+    let addr = first.local_addr()?.to_string();
+    let mut listeners = vec![Bound::Tcp(first)];
+    for _ in 1..count {
+        listeners.push(Bound::Tcp(bind_tcp(&addr, only_v6, reuse_port).await?));
+    }
+    Ok(listeners)
+}
+
+/// Bind a TCP listener; IPv6 sockets are restricted to IPv6 when `only_v6`
+async fn bind_tcp(addr: &str, only_v6: bool, reuse_port: bool) -> io::Result<TcpListener> {
+    let addr = match addr.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) => lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address did not resolve"))?,
+    };
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() && only_v6 {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    if reuse_port {
+        socket.set_reuse_port(true)?;
+    }
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Bind a Unix socket, replacing a stale socket file left by a previous run
+#[cfg(unix)]
+fn bind_unix(path: &PathBuf) -> io::Result<tokio::net::UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, "path exists and is not a socket")),
+        Err(_) => {}
+    }
+    tokio::net::UnixListener::bind(path)
+}
@@ -0,0 +1,378 @@
+use std::{net::SocketAddr, time::{Duration, Instant}};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use lazy_static::lazy_static;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::{event, Level};
+
+use crate::{
+    dao::db::{AbuseBan, AbuseRecord},
+    service::{audit, errors, handler::normalize_id},
+    utils::{env::{read_env_string, read_env_u64}, time::unix_now, token},
+};
+
+lazy_static! {
+    static ref ABUSE_WINDOW_SECS: u64 = read_env_u64("ABUSE_WINDOW_SECS", 10 * 60).max(1);
+    static ref ABUSE_MAX_IDLE_CODES: u64 = read_env_u64("ABUSE_MAX_IDLE_CODES", 30);
+    static ref ABUSE_MAX_WRONG_RECEIVER: u64 = read_env_u64("ABUSE_MAX_WRONG_RECEIVER", 20);
+    static ref ABUSE_MAX_MISSES: u64 = read_env_u64("ABUSE_MAX_MISSES", 50);
+    static ref ABUSE_BAN_SECS: u64 = read_env_u64("ABUSE_BAN_SECS", 15 * 60).max(1);
+    static ref ABUSE_DELAY_STEP_MS: u64 = read_env_u64("ABUSE_DELAY_STEP_MS", 200);
+    // 0 is a valid value here (no padding), so the positive-only helper does not fit
+    static ref LOOKUP_MIN_RESPONSE_MS: u64 = read_env_string("LOOKUP_MIN_RESPONSE_MS")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(50);
+    static ref ENUM_POW_BITS: u64 = read_env_u64("ENUM_POW_BITS", 0).min(MAX_POW_BITS);
+    static ref ENUM_POW_AFTER: u64 = read_env_u64("ENUM_POW_AFTER", 5);
+}
+
+/// Longest delay applied before a client is banned outright (milliseconds)
+const MAX_DELAY_MS: u64 = 5000;
+/// Token scope of proof-of-work challenges
+const POW_SCOPE: &str = "lookup-pow";
+/// How long a challenge may be solved and reused for its id (seconds)
+const POW_CHALLENGE_TTL_SECS: u64 = 5 * 60;
+/// Hardest difficulty accepted from config; each bit doubles the client's work
+const MAX_POW_BITS: u64 = 28;
+const POW_CHALLENGE_HEADER: &str = "x-pow-challenge";
+const POW_SOLUTION_HEADER: &str = "x-pow-solution";
+
+/// Window over which suspicious activity is counted (default 10min, configurable via ABUSE_WINDOW_SECS)
+fn window_secs() -> u64 {
+    *ABUSE_WINDOW_SECS
+}
+/// How long a flagged client stays banned (default 15min, configurable via ABUSE_BAN_SECS)
+fn ban_secs() -> u64 {
+    *ABUSE_BAN_SECS
+}
+
+/// Per-heuristic ceilings; 0 disables a heuristic
+#[derive(Clone, Copy)]
+struct Thresholds {
+    idle_codes: u64,
+    wrong_receiver: u64,
+    misses: u64,
+    delay_step_ms: u64,
+}
+
+/// Lookups and claims take at least this long, plus up to a quarter of it in
+/// jitter, so a hit cannot be told from a miss by timing (default 50ms, configurable via LOOKUP_MIN_RESPONSE_MS)
+fn lookup_min_response() -> Duration {
+    let floor = *LOOKUP_MIN_RESPONSE_MS;
+    Duration::from_millis(floor + rand::random_range(0..=floor / 4))
+}
+/// Leading zero bits a proof of work must reach; 0 disables it (default 0, configurable via ENUM_POW_BITS)
+fn pow_bits() -> u32 {
+    *ENUM_POW_BITS as u32
+}
+/// Misses within the window after which lookups need a proof of work (default 5, configurable via ENUM_POW_AFTER)
+fn pow_after() -> u64 {
+    *ENUM_POW_AFTER
+}
+
+/// Thresholds from ABUSE_MAX_IDLE_CODES (default 30), ABUSE_MAX_WRONG_RECEIVER (default 20),
+/// ABUSE_MAX_MISSES (default 50) and ABUSE_DELAY_STEP_MS (default 200)
+fn thresholds() -> Thresholds {
+    Thresholds {
+        idle_codes: *ABUSE_MAX_IDLE_CODES,
+        wrong_receiver: *ABUSE_MAX_WRONG_RECEIVER,
+        misses: *ABUSE_MAX_MISSES,
+        delay_step_ms: *ABUSE_DELAY_STEP_MS,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Allow,
+    Delay(u64),
+    Ban(&'static str),
+}
+
+/// Past half a threshold every further strike adds a delay step; past the
+/// threshold itself the client is banned
+fn assess(record: &AbuseRecord, limits: Thresholds) -> Verdict {
+    let counters = [
+        (record.idle_codes as u64, limits.idle_codes, "rapid code creation without uploads"),
+        (record.wrong_receiver as u64, limits.wrong_receiver, "repeated wrong receiver attempts"),
+        (record.misses as u64, limits.misses, "code enumeration"),
+    ];
+
+    let mut delay_ms = 0;
+    for (count, limit, reason) in counters {
+        if limit == 0 {
+            continue;
+        }
+        if count > limit {
+            return Verdict::Ban(reason);
+        }
+        delay_ms = delay_ms.max(count.saturating_sub(limit / 2) * limits.delay_step_ms);
+    }
+
+    match delay_ms.min(MAX_DELAY_MS) {
+        0 => Verdict::Allow,
+        ms => Verdict::Delay(ms),
+    }
+}
+
+/// Requests whose outcome feeds the heuristics
+enum Action {
+    Create,
+    Upload,
+    Lookup,
+    Download,
+    Claim,
+    Other,
+}
+
+/// Response extension marking the upload that accepted a code's first block
+#[derive(Clone, Copy, Debug)]
+pub struct FirstUpload;
+
+fn classify(method: &Method, path: &str) -> Action {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    match (method, segments.as_slice()) {
+        (&Method::GET, [.., "id"]) => Action::Create,
+        (&Method::POST, [.., _, "upload"]) => Action::Upload,
+        (&Method::GET, [.., _, "status" | "meta"]) => Action::Lookup,
+        (&Method::GET, [.., _, "file"]) => Action::Download,
+        (&Method::POST, [.., _, "claim"]) => Action::Claim,
+        _ => Action::Other,
+    }
+}
+
+/// Transfer id a lookup or claim targets: the segment before the action
+fn target_id(path: &str) -> String {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    normalize_id(segments.len().checked_sub(2).map_or("", |at| segments[at]))
+}
+
+/// A challenge binds the client, the id it looks up and an expiry:
+/// `<expires>.<signature>`
+fn pow_challenge(client: &SocketAddr, id: &str) -> String {
+    let expires = (unix_now() + POW_CHALLENGE_TTL_SECS).to_string();
+    let ip = client.ip().to_string();
+    format!("{}.{}", expires, token::sign(POW_SCOPE, &[&ip, id, &expires]))
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Whether SHA-256 of `<challenge>:<solution>` starts with `bits` zero bits
+fn meets_difficulty(challenge: &str, solution: &str, bits: u32) -> bool {
+    let hash = Sha256::digest(format!("{}:{}", challenge, solution).as_bytes());
+    leading_zero_bits(&hash) >= bits
+}
+
+/// Check the challenge and solution headers of a lookup
+fn pow_solved(headers: &HeaderMap, client: &SocketAddr, id: &str, bits: u32) -> bool {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(challenge), Some(solution)) = (header(POW_CHALLENGE_HEADER), header(POW_SOLUTION_HEADER)) else {
+        return false;
+    };
+    let Some((expires, signature)) = challenge.split_once('.') else {
+        return false;
+    };
+    let ip = client.ip().to_string();
+    expires.parse::<u64>().is_ok_and(|expires| expires > unix_now())
+        && token::verify(POW_SCOPE, &[&ip, id, expires], signature)
+        && meets_difficulty(challenge, solution, bits)
+}
+
+fn pow_required(client: &SocketAddr, id: &str, bits: u32) -> Response {
+    (
+        StatusCode::PRECONDITION_REQUIRED,
+        Json(json!({
+            "code": 428,
+            "success": false,
+            "error": errors::POW_REQUIRED,
+            "message": "Proof of work required",
+            "details": {
+                "algorithm": "sha256",
+                "challenge": pow_challenge(client, id),
+                "bits": bits,
+                "headers": [POW_CHALLENGE_HEADER, POW_SOLUTION_HEADER],
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// What the heuristics read from a response
+struct Outcome {
+    status: StatusCode,
+    error: Option<&'static str>,
+    first_upload: bool,
+}
+
+impl Outcome {
+    fn of(response: &Response) -> Self {
+        Outcome {
+            status: response.status(),
+            error: response.extensions().get::<errors::ErrorCode>().map(|code| code.0),
+            first_upload: response.extensions().get::<FirstUpload>().is_some(),
+        }
+    }
+}
+
+/// Apply an outcome to the record; returns false when nothing changed
+fn observe(record: &mut AbuseRecord, action: &Action, outcome: &Outcome) -> bool {
+    match (action, outcome.status) {
+        (Action::Create, status) if status.is_success() => record.idle_codes += 1,
+        // Only the first block of a code makes it no longer idle
+        (Action::Upload, status) if status.is_success() && outcome.first_upload => {
+            record.idle_codes = record.idle_codes.saturating_sub(1)
+        }
+        (Action::Lookup | Action::Claim, StatusCode::NOT_FOUND) => record.misses += 1,
+        // Receivers awaiting approval or retrying a block are not guessing tokens
+        (Action::Download, _) if matches!(outcome.error, Some(errors::INVALID_RECEIVER_TOKEN | errors::WRONG_RECEIVER)) => {
+            record.wrong_receiver += 1
+        }
+        (Action::Claim, StatusCode::CONFLICT) => record.wrong_receiver += 1,
+        _ => return false,
+    }
+    true
+}
+
+fn banned_response() -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, ban_secs().to_string())],
+        Json(json!({
+            "code": 429,
+            "success": false,
+            "error": errors::RATE_LIMITED,
+            "message": "Too many suspicious requests, try again later"
+        })),
+    )
+        .into_response()
+}
+
+/// Middleware flagging abusive patterns per client IP, answering with
+/// escalating delays and then a temporary ban. Lookups and claims, which an
+/// id scan would hammer, are also padded to a minimum response time and,
+/// once a client keeps missing, gated behind a proof of work
+pub async fn guard(request: Request, next: Next) -> Response {
+    let Some(client) = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0) else {
+        return next.run(request).await;
+    };
+    let key = client.ip().to_string();
+
+    if let Some(ban) = AbuseBan::get_db().get(&key).await {
+        event!(Level::DEBUG, "Rejecting banned client {}: {}", key, ban.value.reason);
+        return banned_response();
+    }
+
+    let record_db = AbuseRecord::get_db();
+    let limits = thresholds();
+    let record = record_db.get(&key).await.map(|entry| entry.value);
+    if let Some(record) = &record
+        && let Verdict::Delay(ms) = assess(record, limits)
+    {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+    }
+
+    let action = classify(request.method(), request.uri().path());
+    let is_lookup = matches!(action, Action::Lookup | Action::Claim);
+    let bits = pow_bits();
+    if is_lookup && bits > 0 && record.is_some_and(|record| record.misses as u64 >= pow_after()) {
+        let id = target_id(request.uri().path());
+        if !pow_solved(request.headers(), &client, &id, bits) {
+            event!(Level::DEBUG, "Proof of work required from {} for lookup of {}", key, id);
+            return pow_required(&client, &id, bits);
+        }
+    }
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    if matches!(action, Action::Other) {
+        return response;
+    }
+    if is_lookup {
+        let floor = lookup_min_response();
+        tokio::time::sleep(floor.saturating_sub(started.elapsed())).await;
+    }
+
+    let outcome = Outcome::of(&response);
+    let _ = record_db.insert_if_absent(&key, AbuseRecord::default(), window_secs()).await;
+    let verdict = record_db
+        .modify(&key, |record| observe(record, &action, &outcome).then(|| assess(record, limits)))
+        .await
+        .flatten();
+
+    if let Some(Verdict::Ban(reason)) = verdict {
+        event!(Level::WARN, "Banning {} for {}s: {}", key, ban_secs(), reason);
+        let _ = AbuseBan::get_db().insert(&key, AbuseBan { reason }, ban_secs()).await;
+        record_db.remove(&key).await;
+        audit::record(audit::CLIENT_BANNED, None, client, json!({ "reason": reason, "ban_secs": ban_secs() }));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: Thresholds = Thresholds { idle_codes: 10, wrong_receiver: 4, misses: 0, delay_step_ms: 100 };
+
+    #[test]
+    fn test_assess_escalates_from_delay_to_ban() {
+        let mut record = AbuseRecord::default();
+        assert_eq!(assess(&record, LIMITS), Verdict::Allow);
+
+        record.idle_codes = 7;
+        assert_eq!(assess(&record, LIMITS), Verdict::Delay(200));
+
+        record.wrong_receiver = 5;
+        assert_eq!(assess(&record, LIMITS), Verdict::Ban("repeated wrong receiver attempts"));
+
+        // A zero threshold disables the heuristic
+        let record = AbuseRecord { misses: 1000, ..AbuseRecord::default() };
+        assert_eq!(assess(&record, LIMITS), Verdict::Allow);
+    }
+
+    #[test]
+    fn test_observe_counts_only_guessing() {
+        let outcome = |status, error, first_upload| Outcome { status, error, first_upload };
+        let mut record = AbuseRecord { idle_codes: 3, ..AbuseRecord::default() };
+
+        assert!(!observe(&mut record, &Action::Download, &outcome(StatusCode::FORBIDDEN, Some(errors::AWAITING_APPROVAL), false)));
+        assert!(!observe(&mut record, &Action::Download, &outcome(StatusCode::BAD_REQUEST, Some(errors::WRONG_START), false)));
+        assert!(observe(&mut record, &Action::Download, &outcome(StatusCode::FORBIDDEN, Some(errors::INVALID_RECEIVER_TOKEN), false)));
+        assert_eq!(record.wrong_receiver, 1);
+
+        assert!(!observe(&mut record, &Action::Upload, &outcome(StatusCode::OK, None, false)));
+        assert!(observe(&mut record, &Action::Upload, &outcome(StatusCode::OK, None, true)));
+        assert_eq!(record.idle_codes, 2);
+    }
+
+    #[test]
+    fn test_proof_of_work() {
+        assert_eq!(leading_zero_bits(&[0, 0x1f, 0xff]), 11);
+        let solution = (0..).map(|n: u32| n.to_string()).find(|n| meets_difficulty("c", n, 8)).unwrap();
+        assert!(meets_difficulty("c", &solution, 8));
+        assert_eq!(target_id("/api/fileflow/AbC12/status"), "abc12");
+    }
+
+    #[test]
+    fn test_classify_ignores_mount_prefix() {
+        assert!(matches!(classify(&Method::GET, "/api/v1/fileflow/id"), Action::Create));
+        assert!(matches!(classify(&Method::GET, "/abc12/status"), Action::Lookup));
+        assert!(matches!(classify(&Method::POST, "/abc12/claim"), Action::Claim));
+        assert!(matches!(classify(&Method::GET, "/abc12/signal"), Action::Other));
+    }
+}
@@ -0,0 +1,128 @@
+use std::{net::SocketAddr, time::Instant};
+
+use axum::{
+    body::HttpBody,
+    extract::{ConnectInfo, Request},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use lazy_static::lazy_static;
+use tracing::{event, Level};
+
+use crate::{service::tenant, utils::env::read_env_string};
+
+/// Tracing target of access lines, e.g. `RUST_LOG=info,fileflow::access=off`
+const ACCESS_LOG_TARGET: &str = "fileflow::access";
+/// Stand-ins for path segments that must not reach the log
+const REDACTED_ID: &str = ":id";
+const REDACTED_TOKEN: &str = ":token";
+const REDACTED_NAME: &str = ":name";
+/// Routes under `/fileflow/` whose next segment is not a transfer id
+const FIXED_ROUTES: [&str; 4] = ["hello", "id", "p2p-config", "webrtc"];
+
+lazy_static! {
+    static ref ACCESS_LOG: bool = read_env_string("ACCESS_LOG").is_some_and(|v| v == "true" || v == "1");
+    static ref ACCESS_LOG_REDACT_IDS: bool =
+        read_env_string("ACCESS_LOG_REDACT_IDS").is_some_and(|v| v == "true" || v == "1");
+}
+
+/// Whether every request is logged under the `fileflow::access` target (default off, configurable via ACCESS_LOG)
+fn access_log_enabled() -> bool {
+    *ACCESS_LOG
+}
+/// Whether transfer ids and file names are masked in logged paths (default off, configurable via ACCESS_LOG_REDACT_IDS)
+fn redact_ids() -> bool {
+    *ACCESS_LOG_REDACT_IDS
+}
+
+/// The request path as logged; WebDAV mount tokens are always masked since
+/// they grant access on their own, transfer ids only when asked to. Runs
+/// before tenant resolution, so a `/t/{tenant}` prefix is kept and the rest
+/// redacted as usual
+fn redact_path(path: &str, redact_ids: bool) -> String {
+    if let Some((name, rest)) = tenant::split_path(path) {
+        return format!("/t/{}{}", name, redact_path(rest, redact_ids));
+    }
+    let segments = path.split('/').collect::<Vec<_>>();
+    // Positions of the id, the mount token and the file name, where present
+    let (id_at, token_at, name_at) = match segments.as_slice() {
+        ["", "dav", _, ..] => (Some(2), Some(3), Some(4)),
+        ["", "api", "fileflow", id, ..] if !FIXED_ROUTES.contains(id) => (Some(3), None, None),
+        ["", "api", "v1", "fileflow", id, ..] if !FIXED_ROUTES.contains(id) => (Some(4), None, None),
+        // Share links of the download page: /{id}/file
+        ["", id, "file"] if !matches!(*id, "api" | "assets" | "dav") => (Some(1), None, None),
+        _ => (None, None, None),
+    };
+    segments
+        .iter()
+        .enumerate()
+        .map(|(at, segment)| match Some(at) {
+            _ if segment.is_empty() => *segment,
+            at if at == token_at => REDACTED_TOKEN,
+            at if redact_ids && at == id_at => REDACTED_ID,
+            at if redact_ids && at == name_at => REDACTED_NAME,
+            _ => *segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Middleware writing one access line per request once the response head is
+/// ready; streamed bodies have no length to report and log `-`
+pub async fn log(request: Request, next: Next) -> Response {
+    if !access_log_enabled() {
+        return next.run(request).await;
+    }
+    let started = Instant::now();
+    let method = request.method().clone();
+    let path = redact_path(request.uri().path(), redact_ids());
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or("-".to_string(), |info| info.0.ip().to_string());
+
+    let response = next.run(request).await;
+
+    // Most bodies know their size up front; hyper only adds Content-Length later
+    let bytes = response
+        .body()
+        .size_hint()
+        .exact()
+        .map(|len| len.to_string())
+        .or_else(|| response.headers().get(header::CONTENT_LENGTH).and_then(|v| v.to_str().ok()).map(str::to_string))
+        .unwrap_or_else(|| "-".to_string());
+    event!(
+        target: ACCESS_LOG_TARGET,
+        Level::INFO,
+        client = %client,
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        latency_ms = started.elapsed().as_millis() as u64,
+        bytes = %bytes,
+        "access"
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_path() {
+        assert_eq!(redact_path("/api/fileflow/ab3de/status", true), "/api/fileflow/:id/status");
+        assert_eq!(redact_path("/api/v1/fileflow/ab3de/file", true), "/api/v1/fileflow/:id/file");
+        assert_eq!(redact_path("/api/fileflow/ab3de/status", false), "/api/fileflow/ab3de/status");
+        assert_eq!(redact_path("/api/fileflow/webrtc/turn", true), "/api/fileflow/webrtc/turn");
+        assert_eq!(redact_path("/dav/ab3de/secret/a.txt", false), "/dav/ab3de/:token/a.txt");
+        assert_eq!(redact_path("/dav/ab3de/secret/a.txt", true), "/dav/:id/:token/:name");
+        assert_eq!(redact_path("/ab3de/file", true), "/:id/file");
+        assert_eq!(redact_path("/assets/app.js", true), "/assets/app.js");
+        assert_eq!(redact_path("/t/team-a/dav/ab3de/secret/a.txt", false), "/t/team-a/dav/ab3de/:token/a.txt");
+        assert_eq!(redact_path("/t/team-a/dav/ab3de/secret/a.txt", true), "/t/team-a/dav/:id/:token/:name");
+        assert_eq!(redact_path("/t/team-a/api/fileflow/ab3de/status", true), "/t/team-a/api/fileflow/:id/status");
+        assert_eq!(redact_path("/t/team-a/ab3de/file", true), "/t/team-a/:id/file");
+    }
+}
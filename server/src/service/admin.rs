@@ -0,0 +1,212 @@
+use std::{net::SocketAddr, time::Instant};
+
+use axum::{extract::{ConnectInfo, Path}, http::{HeaderMap, StatusCode}, response::IntoResponse, Json};
+use serde_json::json;
+use tracing::{event, instrument, Level};
+
+use crate::{
+    listener,
+    dao::{
+        content::content_usage,
+        db::{AbuseBan, AbuseRecord, FileBlock, LinkUsage, MetaInfo, QuotaUsage, SignalState, StoreStats},
+    },
+    service::{audit, auth, errors, feed, handler::normalize_id, signaling::is_connected, stats, tenant},
+};
+
+/// Handler for listing active signaling rooms
+/// Reports peers with their connection age and liveness plus message counts
+#[instrument(skip_all)]
+pub async fn list_rooms(ConnectInfo(client): ConnectInfo<SocketAddr>, headers: HeaderMap) -> impl IntoResponse {
+    if let Err(err) = auth::authorize_admin(&headers) {
+        return err.into_response();
+    }
+    audit::record(audit::ADMIN_ACTION, None, client, json!({ "action": "list_rooms" }));
+
+    let now = Instant::now();
+    let signal_db = SignalState::get_db();
+    let store = signal_db.store.read().await;
+    let mut rooms = store
+        .iter()
+        .filter(|(id, entry)| entry.exp > now && tenant::owns(id))
+        .map(|(id, entry)| {
+            let state = &entry.value;
+            let mut peers = state
+                .receivers
+                .iter()
+                .map(|peer| json!({
+                    "role": "receiver",
+                    "id": peer.id,
+                    "age_secs": now.duration_since(peer.joined_at).as_secs(),
+                    "idle_secs": now.duration_since(peer.last_seen).as_secs(),
+                    "connected": is_connected(peer.last_seen, now),
+                }))
+                .collect::<Vec<_>>();
+            if let (Some(joined_at), Some(last_seen)) = (state.sender_joined_at, state.sender_last_seen) {
+                peers.insert(0, json!({
+                    "role": "sender",
+                    "age_secs": now.duration_since(joined_at).as_secs(),
+                    "idle_secs": now.duration_since(last_seen).as_secs(),
+                    "connected": is_connected(last_seen, now),
+                }));
+            }
+
+            json!({
+                "id": id,
+                "age_secs": now.duration_since(state.created_at).as_secs(),
+                "expires_in_secs": entry.exp.duration_since(now).as_secs(),
+                "peers": peers,
+                "message_count": state.seq,
+                "buffered_messages": state.messages.len(),
+                "frame_count": state.frame_seq,
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(store);
+
+    rooms.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "rooms": rooms
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for force-closing a signaling room
+/// Drops all buffered messages and peer registrations for the id
+#[instrument(skip(headers))]
+pub async fn close_room(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(err) = auth::authorize_admin(&headers) {
+        return err.into_response();
+    }
+
+    let id = normalize_id(&id);
+    match SignalState::get_db().remove(&id).await {
+        Some(_) => {
+            event!(Level::INFO, "Signaling room force-closed by admin: {}", id);
+            audit::record(audit::ADMIN_ACTION, Some(&id), client, json!({ "action": "close_room" }));
+            feed::publish(feed::ROOM_CLOSED, Some(&id), json!({ "reason": "admin" }));
+            Json(json!({
+                "code": 200,
+                "success": true,
+                "message": "Room closed"
+            }))
+            .into_response()
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::ROOM_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        )
+        .into_response(),
+    }
+}
+
+fn store_report(name: &str, stats: StoreStats) -> serde_json::Value {
+    json!({
+        "name": name,
+        "entries": stats.entries,
+        "expired_pending": stats.expired_pending,
+        "approx_bytes": stats.approx_bytes,
+        "next_expiry_secs": stats.next_expiry.map(|left| left.as_secs()),
+        "last_expiry_secs": stats.last_expiry.map(|left| left.as_secs()),
+        "cleanup": {
+            "interval_ms": stats.schedule.interval.as_millis() as u64,
+            "jitter_ms": stats.schedule.jitter.as_millis() as u64,
+            "lag_ms": stats.cleanup_lag.map(|lag| lag.as_millis() as u64),
+            "last_sweep_us": stats.last_sweep.as_micros() as u64,
+            "max_sweep_us": stats.max_sweep.as_micros() as u64,
+            "last_removed": stats.last_removed,
+        },
+    })
+}
+
+/// Handler for in-memory store introspection
+/// Entry counts, approximate memory use, expiry spread and cleanup lag per store
+#[instrument(skip_all)]
+pub async fn get_stores(ConnectInfo(client): ConnectInfo<SocketAddr>, headers: HeaderMap) -> impl IntoResponse {
+    if let Err(err) = auth::authorize_admin(&headers) {
+        return err.into_response();
+    }
+    audit::record(audit::ADMIN_ACTION, None, client, json!({ "action": "get_stores" }));
+
+    let stores = vec![
+        store_report("meta", MetaInfo::get_db().stats().await),
+        store_report("blocks", FileBlock::get_db().stats().await),
+        store_report("signal", SignalState::get_db().stats().await),
+        store_report("link_usage", LinkUsage::get_db().stats().await),
+        store_report("quota_usage", QuotaUsage::get_db().stats().await),
+        store_report("abuse_records", AbuseRecord::get_db().stats().await),
+        store_report("abuse_bans", AbuseBan::get_db().stats().await),
+    ];
+    let (shared_payloads, shared_bytes) = content_usage();
+    let total = stores.iter().filter_map(|store| store["approx_bytes"].as_u64()).sum::<u64>() + shared_bytes;
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "stores": stores,
+            // Deduplicated block payloads, counted once here rather than per block
+            "shared_content": {
+                "payloads": shared_payloads,
+                "bytes": shared_bytes,
+            },
+            "approx_bytes": total,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for instance-wide transfer statistics
+/// Lifetime and last-hour counters plus current store occupancy
+#[instrument(skip_all)]
+pub async fn get_stats(ConnectInfo(client): ConnectInfo<SocketAddr>, headers: HeaderMap) -> impl IntoResponse {
+    if let Err(err) = auth::authorize_admin(&headers) {
+        return err.into_response();
+    }
+    audit::record(audit::ADMIN_ACTION, None, client, json!({ "action": "get_stats" }));
+
+    let mut data = stats::snapshot();
+    let (shared_payloads, shared_bytes) = content_usage();
+    let now = Instant::now();
+    let meta_db = MetaInfo::get_db();
+    let metas = meta_db.store.read().await;
+    // Under a tenant only its own transfers count
+    let (transfers, upload_bps, download_bps) = metas.iter().filter(|(id, _)| tenant::owns(id)).fold((0, 0, 0), |(count, up, down), (_, entry)| {
+        let metrics = &entry.value.metrics;
+        (count + 1, up + stats::recent_bps(&metrics.upload, now), down + stats::recent_bps(&metrics.download, now))
+    });
+    drop(metas);
+    data["active"] = json!({
+        "transfers": transfers,
+        // Current relay throughput summed over all transfers
+        "upload_bps": upload_bps,
+        "download_bps": download_bps,
+        "buffered_blocks": FileBlock::get_db().store.read().await.keys().filter(|key| tenant::owns(key)).count(),
+        "rooms": SignalState::get_db().store.read().await.keys().filter(|id| tenant::owns(id)).count(),
+        // Open client connections and the cap; null when MAX_CONNECTIONS is unset
+        "connections": listener::open_connections(),
+        "max_connections": listener::max_connections(),
+        "shared_payloads": shared_payloads,
+        "shared_bytes": shared_bytes,
+    });
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": data,
+    }))
+    .into_response()
+}
@@ -0,0 +1,181 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use lazy_static::lazy_static;
+use serde_json::json;
+use tracing::{event, Level};
+
+use crate::{
+    service::feed,
+    utils::{env::{read_env_string, read_env_u64, read_env_usize}, time::unix_now},
+};
+
+lazy_static! {
+    static ref AUDIT_SINK: Option<Sender<String>> = start_sink();
+}
+
+/// Audit event kinds
+pub const TRANSFER_CREATED: &str = "transfer.created";
+pub const TRANSFER_CLAIMED: &str = "transfer.claimed";
+pub const LINK_REDEEMED: &str = "transfer.link_redeemed";
+pub const CLAIM_HANDED_OFF: &str = "transfer.handed_off";
+pub const BLOCK_RELAYED: &str = "block.relayed";
+pub const TRANSFER_COMPLETED: &str = "transfer.completed";
+pub const TRANSFER_CANCELLED: &str = "transfer.cancelled";
+pub const TRANSFER_PAUSED: &str = "transfer.paused";
+pub const TRANSFER_RESUMED: &str = "transfer.resumed";
+pub const ADMIN_ACTION: &str = "admin.action";
+pub const CLIENT_BANNED: &str = "abuse.banned";
+
+/// Where audit records go: `AUDIT_LOG=-` writes to stdout, any other value is a
+/// file path rotated at AUDIT_LOG_MAX_BYTES (default 10MB) keeping
+/// AUDIT_LOG_KEEP old files (default 5). Unset disables auditing
+enum AuditTarget {
+    Stdout,
+    File { path: PathBuf, max_bytes: u64, keep: usize },
+}
+
+fn audit_target() -> Option<AuditTarget> {
+    match read_env_string("AUDIT_LOG")?.as_str() {
+        "-" => Some(AuditTarget::Stdout),
+        path => Some(AuditTarget::File {
+            path: PathBuf::from(path),
+            max_bytes: read_env_u64("AUDIT_LOG_MAX_BYTES", 10 * 1024 * 1024).max(1),
+            keep: read_env_usize("AUDIT_LOG_KEEP", 5),
+        }),
+    }
+}
+
+/// Spawn the writer thread; records are queued so handlers never block on disk
+fn start_sink() -> Option<Sender<String>> {
+    let target = audit_target()?;
+    let (tx, rx) = mpsc::channel::<String>();
+
+    let spawned = thread::Builder::new().name("audit-log".to_string()).spawn(move || {
+        match target {
+            AuditTarget::Stdout => {
+                let mut stdout = io::stdout();
+                for line in rx {
+                    let _ = writeln!(stdout, "{}", line);
+                }
+            }
+            AuditTarget::File { path, max_bytes, keep } => {
+                let mut writer = match RotatingFile::open(path, max_bytes, keep) {
+                    Ok(writer) => writer,
+                    Err(err) => {
+                        event!(Level::ERROR, "Failed to open audit log: {}", err);
+                        return;
+                    }
+                };
+                for line in rx {
+                    if let Err(err) = writer.write_line(&line) {
+                        event!(Level::ERROR, "Failed to write audit log: {}", err);
+                    }
+                }
+            }
+        }
+    });
+
+    match spawned {
+        Ok(_) => Some(tx),
+        Err(err) => {
+            event!(Level::ERROR, "Failed to start audit log writer: {}", err);
+            None
+        }
+    }
+}
+
+/// Append-only file that shifts itself to `<path>.1`, `<path>.2`, ... when full
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { path, file, size, max_bytes, keep })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Record an audit event for transfer `id` (or `None` for instance-wide
+/// actions) originating from `client`, with event-specific `details`; the
+/// admin event stream sees it too
+pub fn record(kind: &'static str, id: Option<&str>, client: SocketAddr, details: serde_json::Value) {
+    feed::publish(kind, id, json!({ "ip": client.ip().to_string(), "details": &details }));
+    let Some(sink) = AUDIT_SINK.as_ref() else {
+        return;
+    };
+    let line = json!({
+        "ts": unix_now(),
+        "event": kind,
+        "id": id,
+        "ip": client.ip().to_string(),
+        "details": details,
+    });
+    let _ = sink.send(line.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotating_file_shifts_old_logs() {
+        let dir = std::env::temp_dir().join(format!("fileflow-audit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.log");
+
+        let mut writer = RotatingFile::open(path.clone(), 16, 2).unwrap();
+        for line in ["first-line", "second-line", "third-line"] {
+            writer.write_line(line).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third-line\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "second-line\n");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 2)).unwrap(), "first-line\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
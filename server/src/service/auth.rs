@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use axum::{http::{HeaderMap, StatusCode}, Json};
+use lazy_static::lazy_static;
+use serde_json::json;
+use tracing::{event, Level};
+
+use crate::{
+    dao::db::{MetaInfo, QuotaUsage},
+    service::{errors, tenant},
+    utils::{env::read_env_string, token},
+};
+
+/// Token scope for sender sessions
+pub const SENDER_TOKEN_SCOPE: &str = "sender";
+/// Length of the per-key quota window (seconds)
+const QUOTA_WINDOW_SECS: u64 = 60 * 60 * 24;
+/// Token scope of owner tags stored with transfers
+const OWNER_SCOPE: &str = "owner";
+/// Shortest accepted `X-Sender-Key`, so keys cannot be guessed
+const MIN_SENDER_KEY_LEN: usize = 16;
+
+lazy_static! {
+    static ref API_KEYS: HashMap<String, ApiKey> = read_api_keys();
+    static ref ADMIN_TOKEN: Option<String> = read_env_string("ADMIN_TOKEN");
+}
+
+/// A configured API key: its daily quota and the tenant it is bound to
+struct ApiKey {
+    quota: Option<u64>,
+    tenant: Option<String>,
+}
+
+/// Parse API_KEYS as a comma separated list of `key` or `key:daily_quota`,
+/// either optionally followed by `@tenant`
+fn read_api_keys() -> HashMap<String, ApiKey> {
+    let raw = match read_env_string("API_KEYS") {
+        Some(raw) => raw,
+        None => return HashMap::new(),
+    };
+
+    let mut keys = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (entry, tenant) = match entry.rsplit_once('@') {
+            Some((entry, tenant)) if tenant::valid_name(tenant.trim()) => (entry.trim(), Some(tenant.trim().to_string())),
+            _ => (entry, None),
+        };
+        let (key, quota) = match entry.split_once(':') {
+            Some((key, quota)) => match quota.trim().parse::<u64>() {
+                Ok(quota) => (key.trim(), Some(quota)),
+                Err(_) => {
+                    event!(Level::WARN, "Invalid quota for API key entry '{}', ignoring quota", key.trim());
+                    (key.trim(), None)
+                }
+            },
+            None => (entry, None),
+        };
+        keys.insert(key.to_string(), ApiKey { quota, tenant });
+    }
+
+    event!(Level::INFO, "API key authentication enabled with {} keys", keys.len());
+    keys
+}
+
+/// Whether transfer creation is restricted to configured API keys
+pub fn api_keys_enabled() -> bool {
+    !API_KEYS.is_empty()
+}
+
+/// Extract the API key from `X-API-Key` or an `Authorization: Bearer` header
+pub fn api_key_from_headers(headers: &HeaderMap) -> Option<String> {
+    if let Some(key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+
+    headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+}
+
+/// Tenant the presented API key is bound to, if any
+pub fn api_key_tenant(headers: &HeaderMap) -> Option<&'static str> {
+    API_KEYS.get(&api_key_from_headers(headers)?)?.tenant.as_deref()
+}
+
+/// Authorize a transfer creation; the quota is charged separately once the
+/// request is known to be valid. Returns the key used (if any) so callers can
+/// attribute and charge the transfer
+pub fn authorize_creation(headers: &HeaderMap) -> Result<Option<String>, (StatusCode, Json<serde_json::Value>)> {
+    if !api_keys_enabled() {
+        return Ok(None);
+    }
+
+    let key = api_key_from_headers(headers).ok_or_else(|| {
+        event!(Level::WARN, "Missing API key for transfer creation");
+        (StatusCode::UNAUTHORIZED, Json(json!({
+            "code": 401,
+            "success": false,
+            "error": errors::API_KEY_REQUIRED,
+            "message": "API key required"
+        })))
+    })?;
+
+    if !API_KEYS.contains_key(&key) {
+        event!(Level::WARN, "Unknown API key used for transfer creation");
+        return Err((StatusCode::UNAUTHORIZED, Json(json!({
+            "code": 401,
+            "success": false,
+            "error": errors::INVALID_API_KEY,
+            "message": "Invalid API key"
+        }))));
+    }
+
+    Ok(Some(key))
+}
+
+/// Charge a transfer creation against the key's daily quota
+pub async fn charge_creation(key: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if let Some(limit) = API_KEYS.get(key).and_then(|api_key| api_key.quota) {
+        let usage_db = QuotaUsage::get_db();
+        let _ = usage_db.insert_if_absent(key, QuotaUsage { count: 0 }, QUOTA_WINDOW_SECS).await;
+        let within_quota = usage_db.modify(key, |usage| {
+            if usage.count >= limit {
+                return false;
+            }
+            usage.count += 1;
+            true
+        }).await;
+
+        if within_quota != Some(true) {
+            event!(Level::WARN, "API key quota of {} transfers exhausted", limit);
+            return Err((StatusCode::TOO_MANY_REQUESTS, Json(json!({
+                "code": 429,
+                "success": false,
+                "error": errors::QUOTA_EXHAUSTED,
+                "message": format!("API key quota exhausted ({} transfers per day)", limit),
+                "details": { "scope": "api_key", "limit": limit },
+            }))));
+        }
+    }
+
+    Ok(())
+}
+
+/// Give back a creation charged to the key when a later step refused it
+pub async fn refund_creation(key: &str) {
+    QuotaUsage::get_db().modify(key, |usage| usage.count = usage.count.saturating_sub(1)).await;
+}
+
+/// Extract the client-chosen `X-Sender-Key` that groups one sender's transfers
+pub fn sender_key_from_headers(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-sender-key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|key| key.len() >= MIN_SENDER_KEY_LEN)
+}
+
+/// Tags naming the caller as the owner of transfers, one per credential it
+/// presents (API key, sender key); only these HMACs are kept with a transfer
+pub fn owner_tags(headers: &HeaderMap) -> Vec<String> {
+    let api_key = api_key_from_headers(headers).map(|key| token::sign(OWNER_SCOPE, &["api-key", &key]));
+    let sender_key = sender_key_from_headers(headers).map(|key| token::sign(OWNER_SCOPE, &["sender-key", key]));
+    api_key.into_iter().chain(sender_key).collect()
+}
+
+/// Issue the sender session token for a transfer
+pub fn issue_sender_token(id: &str, meta: &MetaInfo) -> String {
+    token::issue(SENDER_TOKEN_SCOPE, id, &meta.sender_nonce)
+}
+
+/// Extract the sender session token from the `X-Sender-Token` header
+pub fn sender_token_from_headers(headers: &HeaderMap) -> Option<&str> {
+    headers.get("x-sender-token").and_then(|v| v.to_str().ok()).map(str::trim)
+}
+
+/// Check that the request carries the sender token issued for this transfer
+pub fn authorize_sender(id: &str, headers: &HeaderMap, meta: &MetaInfo) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let sender_token = sender_token_from_headers(headers).ok_or_else(|| {
+        event!(Level::WARN, "Missing sender token for ID: {}", id);
+        (StatusCode::UNAUTHORIZED, Json(json!({
+            "code": 401,
+            "success": false,
+            "error": errors::SENDER_TOKEN_REQUIRED,
+            "message": "Sender token required"
+        })))
+    })?;
+
+    match token::open(SENDER_TOKEN_SCOPE, id, sender_token) {
+        Some(nonce) if !meta.sender_nonce.is_empty() && nonce == meta.sender_nonce => Ok(()),
+        _ => {
+            event!(Level::WARN, "Invalid sender token for ID: {}", id);
+            Err((StatusCode::FORBIDDEN, Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_SENDER_TOKEN,
+                "message": "Invalid sender token"
+            }))))
+        }
+    }
+}
+
+/// Check the `Authorization: Bearer` / `X-Admin-Token` header against ADMIN_TOKEN
+/// The admin API is hidden entirely (404) when no token is configured
+pub fn authorize_admin(headers: &HeaderMap) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let expected = match ADMIN_TOKEN.as_deref() {
+        Some(expected) => expected,
+        None => {
+            return Err((StatusCode::NOT_FOUND, Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::NOT_FOUND,
+                "message": "Not Found"
+            }))));
+        }
+    };
+
+    let presented = headers
+        .get("x-admin-token")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .or_else(|| api_key_from_headers(headers))
+        .unwrap_or_default();
+
+    // Compare digests so the check does not leak the token length or prefix
+    if token::sign("admin", &[&presented]) == token::sign("admin", &[expected]) {
+        Ok(())
+    } else {
+        event!(Level::WARN, "Rejected admin request with invalid token");
+        Err((StatusCode::UNAUTHORIZED, Json(json!({
+            "code": 401,
+            "success": false,
+            "error": errors::INVALID_ADMIN_TOKEN,
+            "message": "Invalid admin token"
+        }))))
+    }
+}
@@ -0,0 +1,351 @@
+use axum::{
+    extract::Path,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{event, instrument, Level};
+
+use crate::{
+    dao::db::{ByteRange, MetaInfo},
+    service::{auth, entity::ReceiverQuery, errors, extract::ValidQuery, handler::{normalize_id, RECEIVER_TOKEN_SCOPE}, signaling},
+    utils::token,
+};
+
+/// Most ranges a receiver may report at once
+const MAX_REPORTED_RANGES: usize = 4096;
+
+#[derive(Debug, Deserialize)]
+pub struct ReportRangesPayload {
+    /// Inclusive byte ranges the receiver already persisted
+    pub ranges: Vec<ByteRange>,
+}
+
+/// Sort and coalesce overlapping or adjacent ranges
+fn merge_ranges(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Gaps of a `total`-byte file left uncovered by `persisted`
+fn missing_ranges(persisted: Vec<ByteRange>, total: u64) -> Vec<ByteRange> {
+    let mut missing = Vec::new();
+    let mut next = 0;
+    for range in merge_ranges(persisted) {
+        if range.start > next {
+            missing.push(ByteRange { start: next, end: range.start - 1 });
+        }
+        next = next.max(range.end.saturating_add(1));
+    }
+    if next < total {
+        missing.push(ByteRange { start: next, end: total - 1 });
+    }
+    missing
+}
+
+fn missing_bytes(ranges: &[ByteRange]) -> u64 {
+    ranges.iter().map(|range| range.end - range.start + 1).sum()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeekPayload {
+    /// Byte offset to resume from; everything before it is already held
+    pub offset: u64,
+}
+
+/// A seek target must start a block and lie inside the file
+fn valid_seek(offset: u64, block_size: u64, file_size: u64) -> bool {
+    block_size > 0 && offset.is_multiple_of(block_size) && offset < file_size
+}
+
+/// Handler for a receiver reporting the byte ranges it already persisted
+/// after a partial failure; the sender is told to push only the rest
+#[instrument(skip(query, payload))]
+pub async fn report_ranges(
+    Path(id): Path<String>,
+    ValidQuery(query): ValidQuery<ReceiverQuery>,
+    Json(payload): Json<ReportRangesPayload>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let Some(receive_id) = token::open(RECEIVER_TOKEN_SCOPE, &id, &query.token).map(str::to_string) else {
+        event!(Level::WARN, "Invalid receiver token for ID: {}", id);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_RECEIVER_TOKEN,
+                "message": "Invalid receiver token"
+            }))
+        )
+        .into_response();
+    };
+
+    let meta_db = MetaInfo::get_db();
+    let Some(meta_info) = meta_db.get(&id).await else {
+        event!(Level::WARN, "Range report failed - ID not found: {}", id);
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        )
+        .into_response();
+    };
+
+    let file_size = meta_info.value.file_size;
+    if file_size == 0 {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "code": 409,
+                "success": false,
+                "error": errors::SIZE_UNKNOWN,
+                "message": "File size not known yet"
+            }))
+        )
+        .into_response();
+    }
+    let out_of_bounds = payload.ranges.iter().any(|range| range.end < range.start || range.end >= file_size);
+    if payload.ranges.len() > MAX_REPORTED_RANGES || out_of_bounds {
+        event!(Level::WARN, "Invalid persisted ranges reported for ID: {}", id);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::INVALID_PARAMETER,
+                "message": "Invalid byte ranges",
+                "details": { "field": "ranges" },
+            }))
+        )
+        .into_response();
+    }
+
+    let missing = missing_ranges(payload.ranges, file_size);
+    let stored = meta_db.modify(&id, |meta| {
+        let receiver = meta.receivers.iter_mut().find(|r| r.id == receive_id)?;
+        receiver.missing = Some(missing.clone());
+        Some(())
+    }).await.flatten();
+    if stored.is_none() {
+        event!(Level::WARN, "Wrong Receive ID for ID: {}", id);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::WRONG_RECEIVER,
+                "message": "Wrong Receive ID"
+            }))
+        )
+        .into_response();
+    }
+
+    let missing_bytes = missing_bytes(&missing);
+    event!(Level::DEBUG, "Receiver {} of ID {} is missing {} bytes in {} ranges", receive_id, id, missing_bytes, missing.len());
+    signaling::notify_sender(&id, signaling::RESEND_RANGES, json!({
+        "receiver": receive_id,
+        "missing": missing,
+    })).await;
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "missing": missing,
+            "missing_bytes": missing_bytes,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for a receiver resuming at a block-aligned offset, skipping the
+/// bytes it already has; the sender is told where to continue
+#[instrument(skip(query, payload))]
+pub async fn seek(
+    Path(id): Path<String>,
+    ValidQuery(query): ValidQuery<ReceiverQuery>,
+    Json(payload): Json<SeekPayload>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let Some(receive_id) = token::open(RECEIVER_TOKEN_SCOPE, &id, &query.token).map(str::to_string) else {
+        event!(Level::WARN, "Invalid receiver token for ID: {}", id);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_RECEIVER_TOKEN,
+                "message": "Invalid receiver token"
+            }))
+        )
+        .into_response();
+    };
+
+    let meta_db = MetaInfo::get_db();
+    let Some(meta_info) = meta_db.get(&id).await else {
+        event!(Level::WARN, "Seek failed - ID not found: {}", id);
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        )
+        .into_response();
+    };
+
+    let (file_size, block_size) = (meta_info.value.file_size, u64::from(meta_info.value.block_size));
+    if file_size == 0 {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "code": 409,
+                "success": false,
+                "error": errors::SIZE_UNKNOWN,
+                "message": "File size not known yet"
+            }))
+        )
+        .into_response();
+    }
+    if !valid_seek(payload.offset, block_size, file_size) {
+        event!(Level::WARN, "Invalid seek offset {} for ID: {}", payload.offset, id);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::INVALID_PARAMETER,
+                "message": format!("Offset must be a multiple of {} below {}", block_size, file_size),
+                "details": { "field": "offset", "block_size": block_size, "file_size": file_size },
+            }))
+        )
+        .into_response();
+    }
+
+    let offset = payload.offset;
+    let missing = vec![ByteRange { start: offset, end: file_size - 1 }];
+    let stored = meta_db.modify(&id, |meta| {
+        let receiver = meta.receivers.iter_mut().find(|r| r.id == receive_id)?;
+        receiver.missing = Some(missing.clone());
+        // The skipped prefix counts as received
+        receiver.cursor = receiver.cursor.max(offset.saturating_sub(1));
+        Some(())
+    }).await.flatten();
+    if stored.is_none() {
+        event!(Level::WARN, "Wrong Receive ID for ID: {}", id);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::WRONG_RECEIVER,
+                "message": "Wrong Receive ID"
+            }))
+        )
+        .into_response();
+    }
+
+    event!(Level::DEBUG, "Receiver {} of ID {} seeks to {}", receive_id, id, offset);
+    signaling::notify_sender(&id, signaling::RECEIVER_SEEK, json!({
+        "receiver": receive_id,
+        "offset": offset,
+    })).await;
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "offset": offset,
+            "block_size": block_size,
+            "missing_bytes": missing_bytes(&missing),
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for the sender fetching which ranges still have to be pushed
+/// Requires the sender token and returns the union of the ranges every reporting receiver is missing
+#[instrument(skip_all)]
+pub async fn get_missing_ranges(Path(id): Path<String>, headers: HeaderMap) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    let Some(meta_info) = MetaInfo::get_db().get(&id).await else {
+        event!(Level::WARN, "Missing range lookup failed - ID not found: {}", id);
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        )
+        .into_response();
+    };
+
+    if let Err(err) = auth::authorize_sender(&id, &headers, &meta_info.value) {
+        return err.into_response();
+    }
+
+    let reported = meta_info.value.receivers.iter().filter_map(|r| Some((r, r.missing.as_ref()?))).collect::<Vec<_>>();
+    let missing = merge_ranges(reported.iter().flat_map(|(_, missing)| missing.iter().copied()).collect());
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "missing_bytes": missing_bytes(&missing),
+            "missing": missing,
+            "receivers": reported.iter().map(|(r, missing)| json!({
+                "id": r.id,
+                "missing": missing,
+            })).collect::<Vec<_>>(),
+        }
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    #[test]
+    fn test_missing_ranges() {
+        assert_eq!(missing_ranges(vec![], 10), vec![range(0, 9)]);
+        assert_eq!(missing_ranges(vec![range(0, 9)], 10), vec![]);
+        // Overlapping and adjacent reports coalesce before the gaps are taken
+        assert_eq!(
+            missing_ranges(vec![range(6, 7), range(2, 3), range(3, 4), range(5, 5)], 10),
+            vec![range(0, 1), range(8, 9)]
+        );
+    }
+
+    #[test]
+    fn test_valid_seek() {
+        assert!(valid_seek(0, 4, 10));
+        assert!(valid_seek(8, 4, 10));
+        assert!(!valid_seek(6, 4, 10));
+        assert!(!valid_seek(12, 4, 10));
+        assert!(!valid_seek(0, 0, 10));
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+use axum::{response::IntoResponse, Json};
+use lazy_static::lazy_static;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde_json::json;
+use tracing::{event, instrument, Level};
+
+use crate::{
+    service::info::PROTOCOL_REVISION,
+    utils::{env::read_env_string, token},
+};
+
+/// DNS-SD service type FileFlow instances advertise and browse for
+const SERVICE_TYPE: &str = "_fileflow._tcp.local.";
+
+lazy_static! {
+    static ref MDNS_ENABLED: bool = read_env_string("MDNS").is_some_and(|v| v == "true" || v == "1");
+    static ref MDNS_NAME: String = read_env_string("MDNS_NAME").unwrap_or_else(|| "FileFlow".to_string());
+    static ref MDNS_HOSTNAME: String = read_env_string("MDNS_HOSTNAME")
+        .unwrap_or_else(|| format!("fileflow-{}", token::random_hex(3)));
+    static ref RESPONDER: Mutex<Option<Responder>> = Mutex::new(None);
+    static ref LAN_INSTANCES: Mutex<HashMap<String, LanInstance>> = Mutex::new(HashMap::new());
+}
+
+/// Whether the instance advertises itself and browses the LAN (default off, configurable via MDNS)
+pub fn mdns_enabled() -> bool {
+    *MDNS_ENABLED
+}
+
+/// The running daemon and the name this instance registered under
+struct Responder {
+    _daemon: ServiceDaemon,
+    fullname: String,
+    port: u16,
+}
+
+/// Another FileFlow instance seen on the LAN
+struct LanInstance {
+    name: String,
+    addresses: Vec<IpAddr>,
+    port: u16,
+    version: Option<String>,
+    seen_at: Instant,
+}
+
+/// Advertise this instance as `MDNS_NAME` on `port` and start tracking other
+/// instances on the LAN; a no-op unless MDNS is enabled
+pub fn start(port: u16) {
+    if !mdns_enabled() {
+        return;
+    }
+    let daemon = match ServiceDaemon::new() {
+        Ok(daemon) => daemon,
+        Err(err) => {
+            event!(Level::ERROR, "Failed to start mDNS responder: {}", err);
+            return;
+        }
+    };
+
+    let properties = [
+        ("version", env!("CARGO_PKG_VERSION").to_string()),
+        ("protocol", PROTOCOL_REVISION.to_string()),
+    ];
+    let host = format!("{}.local.", MDNS_HOSTNAME.as_str());
+    let service = match ServiceInfo::new(SERVICE_TYPE, &MDNS_NAME, &host, "", port, &properties[..]) {
+        Ok(service) => service.enable_addr_auto(),
+        Err(err) => {
+            event!(Level::ERROR, "Invalid mDNS service definition: {}", err);
+            return;
+        }
+    };
+    let fullname = service.get_fullname().to_string();
+    if let Err(err) = daemon.register(service) {
+        event!(Level::ERROR, "Failed to register mDNS service: {}", err);
+        return;
+    }
+
+    match daemon.browse(SERVICE_TYPE) {
+        Ok(events) => {
+            tokio::spawn(async move {
+                while let Ok(service_event) = events.recv_async().await {
+                    track(service_event);
+                }
+            });
+        }
+        Err(err) => event!(Level::WARN, "Failed to browse for LAN instances: {}", err),
+    }
+
+    event!(Level::INFO, "Advertising {} on port {} via mDNS", fullname, port);
+    *RESPONDER.lock().unwrap_or_else(PoisonError::into_inner) = Some(Responder { _daemon: daemon, fullname, port });
+}
+
+fn track(service_event: ServiceEvent) {
+    let mut instances = LAN_INSTANCES.lock().unwrap_or_else(PoisonError::into_inner);
+    match service_event {
+        ServiceEvent::ServiceResolved(service) => {
+            event!(Level::DEBUG, "Discovered LAN instance {}", service.fullname);
+            let name = service.fullname.strip_suffix(SERVICE_TYPE).unwrap_or(&service.fullname);
+            let instance = LanInstance {
+                name: name.trim_end_matches('.').to_string(),
+                addresses: service.addresses.iter().map(|addr| addr.to_ip_addr()).collect(),
+                port: service.port,
+                version: service.txt_properties.get_property_val_str("version").map(str::to_string),
+                seen_at: Instant::now(),
+            };
+            instances.insert(service.fullname.clone(), instance);
+        }
+        ServiceEvent::ServiceRemoved(_, fullname) => {
+            event!(Level::DEBUG, "LAN instance left: {}", fullname);
+            instances.remove(&fullname);
+        }
+        _ => {}
+    }
+}
+
+/// Base URL of an instance, preferring IPv4 since link-local IPv6 needs a zone
+fn instance_url(addresses: &[IpAddr], port: u16) -> Option<String> {
+    let addr = addresses.iter().find(|addr| addr.is_ipv4()).or(addresses.first())?;
+    Some(match addr {
+        IpAddr::V4(v4) => format!("http://{}:{}", v4, port),
+        IpAddr::V6(v6) => format!("http://[{}]:{}", v6, port),
+    })
+}
+
+/// Handler for listing FileFlow instances found on the LAN via mDNS, so
+/// clients can prefer a nearby server for direct high-speed transfers
+#[instrument(skip_all)]
+pub async fn get_discover() -> impl IntoResponse {
+    let (advertised, own_name) = match RESPONDER.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        Some(responder) => (json!({ "name": MDNS_NAME.as_str(), "port": responder.port }), Some(responder.fullname.clone())),
+        None => (serde_json::Value::Null, None),
+    };
+
+    let instances = LAN_INSTANCES.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut listed = instances
+        .iter()
+        .filter(|(fullname, _)| own_name.as_ref() != Some(*fullname))
+        .map(|(_, instance)| json!({
+            "name": instance.name,
+            "addresses": instance.addresses,
+            "port": instance.port,
+            "url": instance_url(&instance.addresses, instance.port),
+            "version": instance.version,
+            "seen_secs_ago": instance.seen_at.elapsed().as_secs(),
+        }))
+        .collect::<Vec<_>>();
+    drop(instances);
+    listed.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "enabled": mdns_enabled(),
+            "advertised": advertised,
+            "instances": listed,
+        }
+    }))
+}
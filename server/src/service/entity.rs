@@ -4,4 +4,17 @@ use serde::Deserialize;
 pub struct UpdateMetaSchema {
     pub file_name: String,
     pub file_size: u64,
-}
\ No newline at end of file
+}
+
+/// Query of a block download: `?token=<receiver token>&start=<block offset>`
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    pub token: String,
+    pub start: u64,
+}
+
+/// Query of a receiver-scoped stream: `?token=<receiver token>`
+#[derive(Debug, Deserialize)]
+pub struct ReceiverQuery {
+    pub token: String,
+}
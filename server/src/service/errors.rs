@@ -0,0 +1,72 @@
+// Stable error codes carried as `error` in every failed API response, next to
+// the HTTP `code` and an English `message`; parameters of the failure go into
+// `details`. Clients branch on and localize by these, so never rename one.
+
+/// Response extension repeating the `error` code, for middleware that reacts
+/// to specific failures without parsing the body
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorCode(pub &'static str);
+
+// Lookups
+pub const NOT_FOUND: &str = "ERR_NOT_FOUND";
+pub const TRANSFER_NOT_FOUND: &str = "ERR_TRANSFER_NOT_FOUND";
+pub const ROOM_NOT_FOUND: &str = "ERR_ROOM_NOT_FOUND";
+pub const RECEIVER_NOT_FOUND: &str = "ERR_RECEIVER_NOT_FOUND";
+pub const UNKNOWN_TENANT: &str = "ERR_UNKNOWN_TENANT";
+
+// Request shape; `details.field` names the parameter
+pub const MISSING_PARAMETER: &str = "ERR_MISSING_PARAMETER";
+pub const INVALID_PARAMETER: &str = "ERR_INVALID_PARAMETER";
+pub const MALFORMED_UPLOAD: &str = "ERR_MALFORMED_UPLOAD";
+pub const FILE_TOO_LARGE: &str = "ERR_FILE_TOO_LARGE";
+pub const SIZE_UNKNOWN: &str = "ERR_SIZE_UNKNOWN";
+
+// Credentials
+pub const API_KEY_REQUIRED: &str = "ERR_API_KEY_REQUIRED";
+pub const INVALID_API_KEY: &str = "ERR_INVALID_API_KEY";
+pub const TENANT_MISMATCH: &str = "ERR_TENANT_MISMATCH";
+pub const SENDER_TOKEN_REQUIRED: &str = "ERR_SENDER_TOKEN_REQUIRED";
+pub const INVALID_SENDER_TOKEN: &str = "ERR_INVALID_SENDER_TOKEN";
+pub const SENDER_KEY_REQUIRED: &str = "ERR_SENDER_KEY_REQUIRED";
+pub const INVALID_RECEIVER_TOKEN: &str = "ERR_INVALID_RECEIVER_TOKEN";
+pub const WRONG_RECEIVER: &str = "ERR_WRONG_RECEIVER";
+pub const INVALID_ADMIN_TOKEN: &str = "ERR_INVALID_ADMIN_TOKEN";
+pub const INVALID_LINK: &str = "ERR_INVALID_LINK";
+pub const LINK_USED: &str = "ERR_LINK_USED";
+pub const INVALID_HANDOFF: &str = "ERR_INVALID_HANDOFF";
+pub const HANDOFF_USED: &str = "ERR_HANDOFF_USED";
+
+// Transfer state
+pub const ROOM_TAKEN: &str = "ERR_ROOM_TAKEN";
+pub const AWAITING_APPROVAL: &str = "ERR_AWAITING_APPROVAL";
+pub const TRANSFER_STARTED: &str = "ERR_TRANSFER_STARTED";
+pub const TRANSFER_DONE: &str = "ERR_TRANSFER_DONE";
+pub const TRANSFER_PAUSED: &str = "ERR_TRANSFER_PAUSED";
+pub const CONTENT_REJECTED: &str = "ERR_CONTENT_REJECTED";
+
+// Blocks
+pub const BLOCK_NOT_READY: &str = "ERR_BLOCK_NOT_READY";
+pub const BLOCK_WINDOW_FULL: &str = "ERR_BLOCK_WINDOW_FULL";
+pub const BLOCK_TOO_LARGE: &str = "ERR_BLOCK_TOO_LARGE";
+pub const BLOCK_SIZE_MISMATCH: &str = "ERR_BLOCK_SIZE_MISMATCH";
+pub const WRONG_START: &str = "ERR_WRONG_START";
+pub const BLOCK_RANGE_INVALID: &str = "ERR_BLOCK_RANGE_INVALID";
+pub const BLOCK_TOTAL_MISMATCH: &str = "ERR_BLOCK_TOTAL_MISMATCH";
+
+// Signaling
+pub const ROOM_FULL: &str = "ERR_ROOM_FULL";
+pub const RECEIVER_ID_TAKEN: &str = "ERR_RECEIVER_ID_TAKEN";
+pub const INVALID_ROLE: &str = "ERR_INVALID_ROLE";
+pub const INVALID_SESSION: &str = "ERR_INVALID_SESSION";
+pub const SIGNAL_TOO_LARGE: &str = "ERR_SIGNAL_TOO_LARGE";
+pub const SIGNAL_UNSUPPORTED: &str = "ERR_SIGNAL_UNSUPPORTED";
+pub const SIGNAL_MALFORMED: &str = "ERR_SIGNAL_MALFORMED";
+pub const TURN_UNAVAILABLE: &str = "ERR_TURN_UNAVAILABLE";
+
+// Limits
+pub const QUOTA_EXHAUSTED: &str = "ERR_QUOTA_EXHAUSTED";
+pub const RATE_LIMITED: &str = "ERR_RATE_LIMITED";
+pub const POW_REQUIRED: &str = "ERR_POW_REQUIRED";
+
+pub const READ_ONLY: &str = "ERR_READ_ONLY";
+pub const INTERNAL: &str = "ERR_INTERNAL";
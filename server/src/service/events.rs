@@ -0,0 +1,215 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse},
+    Json,
+};
+use futures_util::{future, stream, StreamExt};
+use lazy_static::lazy_static;
+use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{event, instrument, Level};
+
+use crate::{
+    dao::db::{FileBlock, MetaInfo, SignalState},
+    service::{entity::ReceiverQuery, errors, extract::ValidQuery, handler::{normalize_id, RECEIVER_TOKEN_SCOPE}, signaling},
+    utils::token,
+};
+
+/// Receiver event kinds
+pub const SENDER_CONNECTED: &str = "sender-connected";
+pub const METADATA_UPDATED: &str = "metadata-updated";
+pub const BLOCK_AVAILABLE: &str = "block-available";
+pub const TRANSFER_CANCELLED: &str = "transfer-cancelled";
+pub const TRANSFER_PAUSED: &str = "transfer-paused";
+pub const TRANSFER_RESUMED: &str = "transfer-resumed";
+
+/// Events buffered per transfer for a subscriber that fell behind
+const EVENT_BUFFER: usize = 256;
+/// Comment line sent on idle streams so proxies keep them open
+const KEEP_ALIVE_SECS: u64 = 15;
+
+#[derive(Clone, Debug)]
+struct ReceiverEvent {
+    kind: &'static str,
+    data: serde_json::Value,
+}
+
+lazy_static! {
+    static ref CHANNELS: Mutex<HashMap<String, broadcast::Sender<ReceiverEvent>>> = Mutex::new(HashMap::new());
+}
+
+/// Push an event to every receiver page streaming this transfer; a no-op when
+/// nobody is listening
+pub fn publish(id: &str, kind: &'static str, data: serde_json::Value) {
+    let mut channels = CHANNELS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(sender) = channels.get(id)
+        && sender.send(ReceiverEvent { kind, data }).is_err()
+    {
+        channels.remove(id);
+    }
+}
+
+/// A subscriber of one transfer; the channel goes away with its last subscriber
+struct Subscription {
+    id: String,
+    events: broadcast::Receiver<ReceiverEvent>,
+}
+
+impl Subscription {
+    fn new(id: &str) -> Self {
+        let mut channels = CHANNELS.lock().unwrap_or_else(PoisonError::into_inner);
+        let sender = channels.entry(id.to_string()).or_insert_with(|| broadcast::channel(EVENT_BUFFER).0);
+        Subscription { id: id.to_string(), events: sender.subscribe() }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut channels = CHANNELS.lock().unwrap_or_else(PoisonError::into_inner);
+        // The count still includes this receiver
+        if channels.get(&self.id).is_some_and(|sender| sender.receiver_count() <= 1) {
+            channels.remove(&self.id);
+        }
+    }
+}
+
+/// State a freshly connected page would otherwise have missed
+async fn snapshot(id: &str, meta: &MetaInfo, receive_id: &str) -> Vec<ReceiverEvent> {
+    let mut events = vec![ReceiverEvent {
+        kind: METADATA_UPDATED,
+        data: json!({ "file_name": meta.file_name, "file_size": meta.file_size }),
+    }];
+
+    if let Some(state) = SignalState::get_db().get(id).await
+        && state.value.sender_last_seen.is_some_and(|seen| signaling::is_connected(seen, std::time::Instant::now()))
+    {
+        events.push(ReceiverEvent { kind: SENDER_CONNECTED, data: json!({}) });
+    }
+    if meta.paused {
+        events.push(ReceiverEvent { kind: TRANSFER_PAUSED, data: json!({}) });
+    }
+
+    let prefix = format!("{}:", id);
+    let mut buffered = FileBlock::get_db()
+        .store
+        .read()
+        .await
+        .iter()
+        .filter(|(key, block)| key.starts_with(&prefix) && !block.value.served_to.iter().any(|r| r == receive_id))
+        .map(|(_, block)| (block.value.start, block.value.end, block.value.total))
+        .collect::<Vec<_>>();
+    buffered.sort_unstable();
+    events.extend(buffered.into_iter().map(|(start, end, total)| ReceiverEvent {
+        kind: BLOCK_AVAILABLE,
+        data: json!({ "start": start, "end": end, "total": total }),
+    }));
+    events
+}
+
+/// Handler for the receiver page's event stream (SSE)
+/// Pushes sender presence, metadata changes, uploaded blocks, pauses and cancellation
+/// so the page reacts without polling `/status` or probing `/file`
+#[instrument(skip_all)]
+pub async fn receiver_events(
+    Path(id): Path<String>,
+    ValidQuery(query): ValidQuery<ReceiverQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let Some(receive_id) = token::open(RECEIVER_TOKEN_SCOPE, &id, &query.token).map(str::to_string) else {
+        event!(Level::WARN, "Invalid receiver token for event stream of ID: {}", id);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_RECEIVER_TOKEN,
+                "message": "Invalid receiver token"
+            }))
+        )
+        .into_response();
+    };
+
+    // Subscribe before taking the snapshot so nothing slips in between
+    let subscription = Subscription::new(&id);
+    let Some(meta_info) = MetaInfo::get_db().get(&id).await else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        )
+        .into_response();
+    };
+    if meta_info.value.receiver(&receive_id).is_none() {
+        event!(Level::WARN, "Wrong Receive ID for event stream of ID: {}", id);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::WRONG_RECEIVER,
+                "message": "Wrong Receive ID"
+            }))
+        )
+        .into_response();
+    }
+
+    event!(Level::DEBUG, "Receiver {} subscribed to events of ID: {}", receive_id, id);
+    let initial = snapshot(&id, &meta_info.value, &receive_id).await;
+    let live = stream::unfold(subscription, |mut subscription| async move {
+        loop {
+            match subscription.events.recv().await {
+                Ok(event) => return Some((event, subscription)),
+                Err(RecvError::Lagged(skipped)) => {
+                    event!(Level::DEBUG, "Event stream of ID: {} skipped {} events", subscription.id, skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    // A cancelled transfer is the last thing the page hears
+    let events = stream::iter(initial)
+        .chain(live)
+        .scan(false, |ended, event| {
+            let next = (!*ended).then(|| {
+                *ended = event.kind == TRANSFER_CANCELLED;
+                event
+            });
+            future::ready(next)
+        })
+        .map(|event| Event::default().event(event.kind).json_data(event.data));
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(KEEP_ALIVE_SECS)))
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_channel_lives_as_long_as_subscribers() {
+        let id = "events-test";
+        publish(id, SENDER_CONNECTED, json!({}));
+        assert!(!CHANNELS.lock().unwrap().contains_key(id));
+
+        let mut subscription = Subscription::new(id);
+        publish(id, BLOCK_AVAILABLE, json!({ "start": 0 }));
+        assert_eq!(subscription.events.recv().await.unwrap().kind, BLOCK_AVAILABLE);
+
+        drop(subscription);
+        assert!(!CHANNELS.lock().unwrap().contains_key(id));
+    }
+}
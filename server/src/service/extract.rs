@@ -0,0 +1,66 @@
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tracing::{event, Level};
+
+use crate::service::errors;
+
+/// `Query` extractor whose rejections use the API's JSON error shape, with
+/// the same "Missing Parameter" / "Invalid Parameter" wording as the handlers
+pub struct ValidQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Query::<T>::from_request_parts(parts, state).await {
+            Ok(Query(value)) => Ok(ValidQuery(value)),
+            Err(rejection) => {
+                let (error, field, message) = describe(&rejection.body_text());
+                event!(Level::WARN, "Rejected query for {}: {}", parts.uri.path(), message);
+                Err((StatusCode::BAD_REQUEST, Json(json!({
+                    "code": 400,
+                    "success": false,
+                    "error": error,
+                    "message": message,
+                    "details": { "field": field },
+                }))))
+            }
+        }
+    }
+}
+
+/// Turn a serde deserialization error into an error code, the offending field
+/// when serde names one, and a client-facing message
+fn describe(detail: &str) -> (&'static str, Option<String>, String) {
+    let detail = detail.trim_start_matches("Failed to deserialize query string: ");
+    match detail.strip_prefix("missing field `").and_then(|rest| rest.strip_suffix('`')) {
+        Some(field) => (errors::MISSING_PARAMETER, Some(field.to_string()), format!("Missing Parameter: {}", field)),
+        None => (errors::INVALID_PARAMETER, None, format!("Invalid Parameter: {}", detail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe("Failed to deserialize query string: missing field `start`"),
+            (errors::MISSING_PARAMETER, Some("start".to_string()), "Missing Parameter: start".to_string())
+        );
+        assert_eq!(
+            describe("Failed to deserialize query string: invalid digit found in string"),
+            (errors::INVALID_PARAMETER, None, "Invalid Parameter: invalid digit found in string".to_string())
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{ConnectInfo, Query},
+    http::HeaderMap,
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse},
+};
+use futures_util::stream;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{event, instrument, Level};
+
+use crate::{
+    service::{audit, auth, tenant},
+    utils::time::unix_now,
+};
+
+/// Signaling room event kinds, alongside the audit kinds the feed also carries
+pub const ROOM_JOINED: &str = "room.joined";
+pub const ROOM_LEFT: &str = "room.left";
+pub const ROOM_CLOSED: &str = "room.closed";
+/// Metadata of an unfinished transfer lapsed; no client is behind it, so it is not audited
+pub const TRANSFER_EXPIRED: &str = "transfer.expired";
+
+/// Events buffered for a dashboard that fell behind
+const FEED_BUFFER: usize = 1024;
+/// Comment line sent on idle streams so proxies keep them open
+const KEEP_ALIVE_SECS: u64 = 15;
+/// Kind prefixes streamed when the dashboard does not pick; per-block events
+/// are left out as they would drown everything else
+const DEFAULT_KINDS: &[&str] = &["transfer.", "room.", "admin.", "abuse."];
+
+/// One instance-wide event as a dashboard receives it
+#[derive(Clone, Debug)]
+pub struct FeedEvent {
+    pub kind: &'static str,
+    pub id: Option<String>,
+    pub data: serde_json::Value,
+}
+
+lazy_static! {
+    static ref FEED: broadcast::Sender<Arc<FeedEvent>> = broadcast::channel(FEED_BUFFER).0;
+}
+
+/// Push an event to every connected admin stream; a no-op when nobody is listening
+pub fn publish(kind: &'static str, id: Option<&str>, data: serde_json::Value) {
+    if FEED.receiver_count() == 0 {
+        return;
+    }
+    let _ = FEED.send(Arc::new(FeedEvent { kind, id: id.map(str::to_string), data }));
+}
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    /// Comma separated kind prefixes, e.g. `transfer.,block.relayed`
+    pub kinds: Option<String>,
+}
+
+/// Whether a subscriber scoped to `scope` with the given kind prefixes sees `event`
+fn wanted(event: &FeedEvent, kinds: &[String], scope: Option<&str>) -> bool {
+    let visible = match scope {
+        Some(scope) => event.id.as_deref().and_then(tenant::of) == Some(scope),
+        None => true,
+    };
+    visible && kinds.iter().any(|prefix| event.kind.starts_with(prefix.as_str()))
+}
+
+/// Handler streaming transfer lifecycle and signaling room events as
+/// server-sent events while they happen; under a tenant only its own
+#[instrument(skip_all)]
+pub async fn admin_events(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<FeedQuery>,
+) -> impl IntoResponse {
+    if let Err(err) = auth::authorize_admin(&headers) {
+        return err.into_response();
+    }
+    audit::record(audit::ADMIN_ACTION, None, client, json!({ "action": "admin_events" }));
+
+    let kinds = match query.kinds.as_deref() {
+        Some(raw) => raw.split(',').map(str::trim).filter(|kind| !kind.is_empty()).map(str::to_string).collect(),
+        None => DEFAULT_KINDS.iter().map(|kind| kind.to_string()).collect::<Vec<_>>(),
+    };
+    // The body is polled after the handler returns, outside the request's tenant scope
+    let scope = tenant::current();
+    event!(Level::DEBUG, "Admin event stream opened for kinds {:?}", kinds);
+
+    let events = stream::unfold(FEED.subscribe(), move |mut feed| {
+        let kinds = kinds.clone();
+        let scope = scope.clone();
+        async move {
+            loop {
+                match feed.recv().await {
+                    Ok(event) if wanted(&event, &kinds, scope.as_deref()) => {
+                        let sse = Event::default().event(event.kind).json_data(json!({
+                            "ts": unix_now(),
+                            "id": event.id,
+                            "data": event.data,
+                        }));
+                        return Some((sse, feed));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        event!(Level::DEBUG, "Admin event stream skipped {} events", skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(KEEP_ALIVE_SECS)))
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_feed_filters() {
+        publish(ROOM_JOINED, Some("unheard"), json!({}));
+        let mut feed = FEED.subscribe();
+        publish(ROOM_JOINED, Some("team-a~abc"), json!({ "role": "sender" }));
+        let event = feed.recv().await.unwrap();
+        assert_eq!((event.kind, event.id.as_deref()), (ROOM_JOINED, Some("team-a~abc")));
+
+        let kinds = vec!["room.".to_string()];
+        assert!(wanted(&event, &kinds, None));
+        assert!(wanted(&event, &kinds, Some("team-a")));
+        assert!(!wanted(&event, &kinds, Some("team-b")));
+        assert!(!wanted(&event, &["transfer.".to_string()], None));
+    }
+}
@@ -1,14 +1,13 @@
-use std::{collections::HashMap, env};
+use std::{collections::HashMap, net::SocketAddr, time::{Duration, Instant}};
 
 use crate::{
-    dao::db::{FileBlock, MetaInfo, SignalMessage, SignalState},
-    service::static_files::StaticFiles,
-    utils::nanoid,
+    dao::db::{FileBlock, LinkUsage, MetaInfo, ReceiverClaim, SignalState},
+    service::{abuse, audit, auth, events, entity::{DownloadQuery, ReceiverQuery, UpdateMetaSchema}, errors, extract::ValidQuery, feed, inspect, signaling, stats, tenant, i18n::{localized_page, negotiate_locale, PageQuery}, static_files::{error_page, panic_message, safe_join, serve_embedded, StaticFiles}, webdav},
+    utils::{env::{read_env_string, read_env_u64, read_env_usize}, nanoid, time::unix_now, token},
 };
 use axum::{
-    body::Body, extract::{Multipart, Path, Query}, http::{header, StatusCode}, response::{AppendHeaders, Html, IntoResponse}, Json
+    body::Body, extract::{ConnectInfo, Multipart, Path, Query}, http::{header, HeaderMap, StatusCode, Uri}, response::{AppendHeaders, Html, IntoResponse}, Extension, Json
 };
-use mime_guess;
 use serde::Deserialize;
 use serde_json::json;
 use tracing::{event, instrument, Level};
@@ -17,42 +16,120 @@ use lazy_static::lazy_static;
 lazy_static! {
     static ref MAX_BLOCK_SIZE: u64 = read_env_u64("MAX_BLOCK_SIZE", 1024 * 1024);
     static ref MAX_BLOCKS_PER_FILE: usize = read_env_usize("MAX_BLOCKS_PER_FILE", 1024);
+    // 0 is a valid value here (drop served blocks at once), so the positive-only helper does not fit
+    static ref SERVED_BLOCK_GRACE_SECS: u64 = read_env_string("SERVED_BLOCK_GRACE_SECS")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10);
+    static ref ID_MIN_LENGTH: usize = read_env_usize("ID_MIN_LENGTH", 4);
+    static ref ID_MAX_LENGTH: usize = read_env_usize("ID_MAX_LENGTH", 12).max(*ID_MIN_LENGTH);
+    static ref ID_LENGTH: usize = read_env_usize("ID_LENGTH", nanoid::DEFAULT_SIZE).clamp(*ID_MIN_LENGTH, *ID_MAX_LENGTH);
+    static ref ID_ALPHABET: String = read_id_alphabet();
+    static ref MAX_RECEIVERS_PER_CODE: usize = read_env_usize("MAX_RECEIVERS_PER_CODE", 8).max(1);
+    static ref BLOCK_DEDUP: bool = read_env_string("BLOCK_DEDUP").is_some_and(|v| v == "true" || v == "1");
 }
 
-/// Maximum number of retry attempts for database operations
-const MAX_RETRIES: u32 = 5;
-/// Interval between retry attempts in milliseconds
-const RETRY_INTERVAL: u64 = 250;
 /// TTL for metadata entries (seconds)
-const META_TTL_SECS: u64 = 60 * 60 * 24;
+pub(crate) const META_TTL_SECS: u64 = 60 * 60 * 24;
 /// TTL for file block entries (seconds)
 const BLOCK_TTL_SECS: u64 = 60;
-/// TTL for signaling data (seconds)
-const SIGNAL_TTL_SECS: u64 = 60 * 60;
 /// Retry settings for fetching file blocks (kept below client timeout)
 const BLOCK_FETCH_MAX_RETRIES: u32 = 60;
 const BLOCK_FETCH_RETRY_INTERVAL: u64 = 250;
+/// How often a held WebDAV stream checks whether a paused transfer resumed (ms)
+const PAUSE_POLL_INTERVAL: u64 = 500;
+/// Retry-After for a full window while no receiver is consuming it (seconds)
+const WINDOW_RETRY_IDLE_SECS: u64 = 2;
+/// Upper bound of the Retry-After for a full window (seconds)
+const WINDOW_RETRY_MAX_SECS: u64 = 30;
+/// Token scope for receiver claims
+pub(crate) const RECEIVER_TOKEN_SCOPE: &str = "receiver";
+/// Response header carrying the relay digest of the whole file
+const CONTENT_SHA256_HEADER: &str = "X-Content-SHA256";
+/// Signature scope for pre-authorized download links
+const LINK_SCOPE: &str = "link";
+/// Token scope of receiver handoffs between devices
+const HANDOFF_SCOPE: &str = "handoff";
+/// How long a handoff token can be redeemed (seconds)
+const HANDOFF_TTL_SECS: u64 = 2 * 60;
+/// Default lifetime of a pre-authorized download link (seconds)
+const DEFAULT_LINK_TTL_SECS: u64 = 60 * 60;
+/// Upper bound for redemptions of a single download link
+const MAX_LINK_USES: u32 = 16;
+/// Attempts per id length before escalating to a longer id
+const ID_COLLISION_RETRIES: u32 = 3;
 
 /// Aggregate file size limit derived from block constraints
-fn max_total_size() -> u64 {
+pub(crate) fn max_total_size() -> u64 {
     max_block_size() * max_blocks_per_file() as u64
 }
 /// Maximum size of each file block in bytes (default 1MB, configurable via MAX_BLOCK_SIZE)
-fn max_block_size() -> u64 {
+pub(crate) fn max_block_size() -> u64 {
     *MAX_BLOCK_SIZE
 }
 /// Maximum number of blocks allowed per file (default 1024, configurable via MAX_BLOCKS_PER_FILE)
-fn max_blocks_per_file() -> usize {
+pub(crate) fn max_blocks_per_file() -> usize {
     *MAX_BLOCKS_PER_FILE
 }
 
+/// Allowed id lengths (configurable via ID_MIN_LENGTH / ID_MAX_LENGTH)
+pub(crate) fn id_length_bounds() -> (usize, usize) {
+    (*ID_MIN_LENGTH, *ID_MAX_LENGTH)
+}
+/// Default id length (default 5, configurable via ID_LENGTH)
+pub(crate) fn default_id_length() -> usize {
+    *ID_LENGTH
+}
+/// Upper bound for concurrent receivers of one code (default 8, configurable via MAX_RECEIVERS_PER_CODE)
+pub(crate) fn max_receivers_per_code() -> usize {
+    *MAX_RECEIVERS_PER_CODE
+}
+/// How long a block every receiver read stays available for re-fetching a
+/// failed response (default 10s, configurable via SERVED_BLOCK_GRACE_SECS; 0 drops it at once)
+fn served_block_grace_secs() -> u64 {
+    *SERVED_BLOCK_GRACE_SECS
+}
+/// Whether identical blocks share one buffered payload (default off, configurable via BLOCK_DEDUP)
+fn block_dedup() -> bool {
+    *BLOCK_DEDUP
+}
+/// Alphabet used for ids (default digits + lowercase letters, configurable via ID_ALPHABET)
+fn id_alphabet() -> &'static str {
+    ID_ALPHABET.as_str()
+}
+
+/// Canonical form of a client-supplied id, so codes typed with
+/// auto-capitalization or stray whitespace still resolve
+pub fn normalize_id(id: &str) -> String {
+    tenant::scoped(&id.trim().to_lowercase())
+}
+
+fn read_id_alphabet() -> String {
+    match read_env_string("ID_ALPHABET").map(|raw| raw.to_lowercase()) {
+        Some(raw) if raw == "unambiguous" => nanoid::UNAMBIGUOUS_ALPHABET.to_string(),
+        Some(raw) => {
+            let mut chars = raw.chars().collect::<Vec<_>>();
+            chars.sort_unstable();
+            chars.dedup();
+            if raw.is_ascii() && !raw.contains(tenant::SEPARATOR) && chars.len() >= 2 && chars.len() == raw.len() {
+                raw
+            } else {
+                event!(Level::WARN, "ID_ALPHABET is invalid (value: '{}'), using default", raw);
+                nanoid::ALPHABET.to_string()
+            }
+        }
+        None => nanoid::ALPHABET.to_string(),
+    }
+}
+
 fn parse_u64_param(value: Option<&String>, field: &str) -> Result<u64, (StatusCode, Json<serde_json::Value>)> {
     let raw = value.ok_or_else(|| {
         event!(Level::WARN, "Missing Parameter: {}", field);
         (StatusCode::BAD_REQUEST, Json(json!({
             "code": 400,
             "success": false,
-            "message": format!("Missing Parameter: {}", field)
+            "error": errors::MISSING_PARAMETER,
+            "message": format!("Missing Parameter: {}", field),
+            "details": { "field": field },
         })))
     })?;
 
@@ -61,37 +138,13 @@ fn parse_u64_param(value: Option<&String>, field: &str) -> Result<u64, (StatusCo
         (StatusCode::BAD_REQUEST, Json(json!({
             "code": 400,
             "success": false,
-            "message": format!("Invalid Parameter: {}", field)
+            "error": errors::INVALID_PARAMETER,
+            "message": format!("Invalid Parameter: {}", field),
+            "details": { "field": field },
         })))
     })
 }
 
-fn read_env_u64(key: &str, default: u64) -> u64 {
-    match env::var(key) {
-        Ok(raw) => match raw.trim().parse::<u64>() {
-            Ok(value) if value > 0 => value,
-            _ => {
-                event!(Level::WARN, "{} is invalid (value: '{}'), using default {}", key, raw, default);
-                default
-            }
-        },
-        Err(_) => default,
-    }
-}
-
-fn read_env_usize(key: &str, default: usize) -> usize {
-    match env::var(key) {
-        Ok(raw) => match raw.trim().parse::<u64>() {
-            Ok(value) if value > 0 => value.min(usize::MAX as u64) as usize,
-            _ => {
-                event!(Level::WARN, "{} is invalid (value: '{}'), using default {}", key, raw, default);
-                default
-            }
-        },
-        Err(_) => default,
-    }
-}
-
 /// Data transfer object for file information
 #[derive(Debug, Deserialize)]
 struct FileInfo {
@@ -102,238 +155,188 @@ struct FileInfo {
 }
 
 #[derive(Debug, Deserialize)]
-pub struct SignalPostPayload {
-    pub role: String,
-    #[serde(rename = "type")]
-    pub msg_type: String,
-    pub data: serde_json::Value,
-    pub rid: Option<String>,
+pub struct ClaimQuery {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewReceiverPayload {
+    pub approve: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLinkPayload {
+    pub ttl_secs: Option<u64>,
+    pub max_uses: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedeemLinkQuery {
+    pub exp: u64,
+    pub uses: u32,
+    pub sig: String,
 }
 
+/// Query of a handoff redemption: `?handoff=<rid>.<expires>.<signature>`
 #[derive(Debug, Deserialize)]
-pub struct SignalQuery {
-    pub role: String,
-    pub since: Option<u64>,
+pub struct HandoffQuery {
+    pub handoff: String,
 }
 
 
+
 /// Handler for serving the landing page
 /// Returns the index HTML page or 404 if not found
 #[instrument]
 pub async fn home() -> impl IntoResponse {
-    match StaticFiles::get("index.html") {
-        Some(content) => {
-            let html = String::from_utf8(content.data.to_vec()).unwrap();
-            Html(html).into_response()
-        }
-        None => {
-            event!(Level::ERROR, "Landing page not found");
-            (StatusCode::NOT_FOUND, "Page not found").into_response()
-        },
+    match embedded_html("index.html") {
+        Ok(html) => Html(html).into_response(),
+        Err(status) => error_page(status),
     }
 }
 
-/// Handler for returning P2P configuration (STUN/TURN)
-pub async fn get_p2p_config() -> impl IntoResponse {
-    let stun_server = env::var("STUN_SERVER").ok().and_then(|raw| {
-        let trimmed = raw.trim().to_string();
-        if trimmed.is_empty() { None } else { Some(trimmed) }
-    });
-    let turn_server = env::var("TURN_SERVER").ok().and_then(|raw| {
-        let trimmed = raw.trim().to_string();
-        if trimmed.is_empty() { None } else { Some(trimmed) }
-    });
-    let turn_username = env::var("TURN_USERNAME").ok().and_then(|raw| {
-        let trimmed = raw.trim().to_string();
-        if trimmed.is_empty() { None } else { Some(trimmed) }
-    });
-    let turn_credential = env::var("TURN_CREDENTIAL").ok().and_then(|raw| {
-        let trimmed = raw.trim().to_string();
-        if trimmed.is_empty() { None } else { Some(trimmed) }
-    });
-
-    Json(json!({
-        "code": 200,
-        "success": true,
-        "data": {
-            "stun": stun_server,
-            "turn": turn_server,
-            "turn_username": turn_username,
-            "turn_credential": turn_credential,
-        }
-    }))
-    .into_response()
+/// Load an embedded HTML page; a missing page maps to 404 and one that is
+/// not valid UTF-8 to 500, rendered by the caller with `error_page`
+fn embedded_html(path: &str) -> Result<String, StatusCode> {
+    let content = StaticFiles::get(path).ok_or_else(|| {
+        event!(Level::ERROR, "Page not found: {}", path);
+        StatusCode::NOT_FOUND
+    })?;
+    String::from_utf8(content.data.into_owned()).map_err(|err| {
+        event!(Level::ERROR, "Page {} is not valid UTF-8: {}", path, err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
 }
 
-/// Handler for posting WebRTC signaling messages
-pub async fn post_signal(
-    Path(id): Path<String>,
-    Json(payload): Json<SignalPostPayload>,
-) -> impl IntoResponse {
-    let role = payload.role.trim();
-    if role != "sender" && role != "receiver" {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "code": 400,
-                "success": false,
-                "message": "Invalid role"
-            })),
-        )
-            .into_response();
-    }
+/// Wrap a localized page, advertising its language and that it varies by
+/// `Accept-Language` so caches don't serve one locale to everyone
+fn localized_html(html: String, locale: &'static str) -> axum::response::Response {
+    (
+        AppendHeaders([
+            (header::CONTENT_LANGUAGE, locale),
+            (header::VARY, "Accept-Language"),
+        ]),
+        Html(html),
+    )
+        .into_response()
+}
 
-    let msg_type = payload.msg_type.trim();
-    if msg_type.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "code": 400,
-                "success": false,
-                "message": "Invalid message type"
-            })),
-        )
-            .into_response();
+/// Handler for serving the upload page
+/// Returns the upload HTML page in the negotiated locale or 404 if not found
+#[instrument(skip_all)]
+pub async fn upload(Query(query): Query<PageQuery>, headers: HeaderMap) -> impl IntoResponse {
+    let locale = negotiate_locale(query.lang.as_deref(), &headers);
+    match embedded_html(&localized_page("upload/", locale)) {
+        Ok(html) => localized_html(html, locale),
+        Err(status) => error_page(status),
     }
+}
 
-    if msg_type == "ready" && role == "receiver" {
-        if let Some(receive_id) = payload.rid.clone() {
-            if let Some(mut meta_info) = MetaInfo::get_db().get(&id).await {
-                meta_info.value.is_using = true;
-                meta_info.value.used_by = receive_id;
-                let _ = MetaInfo::get_db().update(&id, meta_info.value, meta_info.exp).await;
-            }
-        }
+/// Handler for serving the download page
+/// Returns the download HTML page in the negotiated locale or 404 if not found
+pub async fn download(Query(query): Query<PageQuery>, headers: HeaderMap) -> impl IntoResponse {
+    let locale = negotiate_locale(query.lang.as_deref(), &headers);
+    match embedded_html(&localized_page("download/", locale)) {
+        Ok(html) => localized_html(html, locale),
+        Err(status) => error_page(status),
     }
+}
 
-    let mut state = SignalState::get_db()
-        .get(&id)
-        .await
-        .map(|entry| entry.value)
-        .unwrap_or_else(SignalState::new);
-
-    state.seq = state.seq.saturating_add(1);
-    let seq = state.seq;
-    state.messages.push(SignalMessage {
-        seq,
-        from: role.to_string(),
-        msg_type: msg_type.to_string(),
-        data: payload.data,
-        rid: payload.rid,
-    });
-
-    if state.messages.len() > 200 {
-        let drain_len = state.messages.len() - 200;
-        state.messages.drain(0..drain_len);
-    }
+/// Wall-clock unix timestamp of a MemDB expiry instant
+fn unix_expiry(exp: std::time::Instant) -> u64 {
+    unix_now() + exp.saturating_duration_since(std::time::Instant::now()).as_secs()
+}
 
-    match SignalState::get_db().insert(&id, state, SIGNAL_TTL_SECS).await {
-        Ok(_) => Json(json!({
-            "code": 200,
-            "success": true,
-            "data": {
-                "seq": seq
-            }
-        }))
-        .into_response(),
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "code": 500,
-                "success": false,
-                "message": err
-            })),
-        )
-            .into_response(),
-    }
+/// Marker the download page is rendered around; the transfer blob goes right before it
+const TRANSFER_SCRIPT_ANCHOR: &str = "</head>";
+
+/// Serialize `value` for embedding inside a `<script>` block; escaping the
+/// HTML-significant characters keeps a hostile file name from closing the tag
+fn inline_json(value: &serde_json::Value) -> String {
+    value
+        .to_string()
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
 }
 
-/// Handler for fetching WebRTC signaling messages
-pub async fn get_signal(
+/// Handler for serving the download page of a specific transfer
+/// Injects the file name, size and expiry from `MetaInfo` as an inline JSON
+/// blob so the receiver sees what they are about to accept on first paint
+#[instrument(skip_all)]
+pub async fn download_transfer(
     Path(id): Path<String>,
-    Query(query): Query<SignalQuery>,
+    Query(query): Query<PageQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let role = query.role.trim();
-    if role != "sender" && role != "receiver" {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "code": 400,
-                "success": false,
-                "message": "Invalid role"
-            })),
-        )
-            .into_response();
-    }
-
-    let since = query.since.unwrap_or(0);
-    let state = SignalState::get_db().get(&id).await;
-
-    let (messages, latest) = match state {
-        Some(entry) => {
-            let latest = entry.value.seq;
-            let messages = entry
-                .value
-                .messages
-                .iter()
-                .filter(|msg| msg.seq > since && msg.from != role)
-                .cloned()
-                .collect::<Vec<_>>();
-            (messages, latest)
-        }
-        None => (Vec::new(), since),
+    let id = normalize_id(&id);
+    let locale = negotiate_locale(query.lang.as_deref(), &headers);
+    let mut html = match embedded_html(&localized_page("download/", locale)) {
+        Ok(html) => html,
+        Err(status) => return error_page(status),
     };
 
-    Json(json!({
-        "code": 200,
-        "success": true,
-        "data": {
-            "latest": latest,
-            "messages": messages
-        }
-    }))
-    .into_response()
-}
-
-/// Handler for serving the upload page
-/// Returns the upload HTML page or 404 if not found
-#[instrument]
-pub async fn upload() -> impl IntoResponse {
-    match StaticFiles::get("upload/index.html") {
-        Some(content) => {
-            let html = String::from_utf8(content.data.to_vec()).unwrap();
-            Html(html).into_response()
+    if let Some(entry) = MetaInfo::get_db().get(&id).await {
+        let expires_at = unix_expiry(entry.exp);
+        let transfer = json!({
+            "id": tenant::bare(&id),
+            "file_name": entry.value.file_name,
+            "file_size": entry.value.file_size,
+            "expires_at": expires_at,
+            "done": entry.value.done,
+        });
+        let script = format!(
+            "<script id=\"fileflow-transfer\" type=\"application/json\">{}</script>",
+            inline_json(&transfer)
+        );
+        match html.find(TRANSFER_SCRIPT_ANCHOR) {
+            Some(pos) => html.insert_str(pos, &script),
+            None => event!(Level::WARN, "Download page has no </head>, skipping transfer metadata"),
         }
-        None => {
-            event!(Level::ERROR, "Upload page not found");
-            (StatusCode::NOT_FOUND, "Page not found").into_response()
-        },
     }
+
+    let mut response = localized_html(html, locale);
+    // Rendered per transfer, so never let a shared cache keep it
+    response.headers_mut().insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-store"));
+    response
 }
 
-/// Handler for serving the download page
-/// Returns the download HTML page or 404 if not found
-pub async fn download() -> impl IntoResponse {
-    match StaticFiles::get("download/index.html") {
-        Some(content) => {
-            let html = String::from_utf8(content.data.to_vec()).unwrap();
-            Html(html).into_response()
+/// Register metadata under a freshly minted id without overwriting a live transfer
+/// Retries on collision and escalates the id length when collisions persist;
+/// returns the store key, which carries the tenant when there is one
+async fn mint_id(meta_info: MetaInfo, length: usize) -> Result<String, String> {
+    let (_, max_length) = id_length_bounds();
+    let mut length = length;
+
+    while length <= max_length {
+        for _ in 0..ID_COLLISION_RETRIES {
+            let id = tenant::scoped(&nanoid::generate_custom(length, id_alphabet()));
+            if MetaInfo::get_db().insert_if_absent(&id, meta_info.clone(), META_TTL_SECS).await? {
+                return Ok(id);
+            }
+            event!(Level::WARN, "ID collision on '{}', retrying", id);
         }
-        None => {
-            event!(Level::ERROR, "Download page not found");
-            (StatusCode::NOT_FOUND, "Page not found").into_response()
-        },
+        length += 1;
+        event!(Level::WARN, "Persistent ID collisions, escalating ID length to {}", length);
     }
+
+    Err(format!("Unable to mint a unique ID within {} characters", max_length))
 }
 
 /// Handler for generating a unique file ID
-/// Accepts file name and size as query parameters
+/// Accepts file name, size and an optional id length as query parameters
+/// Requires a configured API key when API_KEYS is set
 /// Returns a unique ID that can be used for file transfer
-#[instrument]
+#[instrument(skip(headers))]
 pub async fn get_id(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Query(query): Query<HashMap<String, String>>
 ) -> impl IntoResponse {
-    let id = nanoid::generate();
+    let api_key = match auth::authorize_creation(&headers) {
+        Ok(api_key) => api_key,
+        Err(err) => return err.into_response(),
+    };
 
     let file_name = query.get("file_name").unwrap_or(&String::new()).to_string();
     let file_size = match parse_u64_param(query.get("file_size"), "file_size") {
@@ -348,7 +351,55 @@ pub async fn get_id(
             Json(json!({
                 "code": 400,
                 "success": false,
-                "message": "File exceeds maximum allowed size"
+                "error": errors::FILE_TOO_LARGE,
+                "message": "File exceeds maximum allowed size",
+                "details": { "max_file_size": max_total_size() },
+            }))
+        )
+        .into_response();
+    }
+
+    let id_length = match query.get("length") {
+        Some(_) => match parse_u64_param(query.get("length"), "length") {
+            Ok(length) => length.min(usize::MAX as u64) as usize,
+            Err(err) => return err.into_response(),
+        },
+        None => default_id_length(),
+    };
+
+    let (min_length, max_length) = id_length_bounds();
+    if id_length < min_length || id_length > max_length {
+        event!(Level::WARN, "Requested ID length {} outside bounds {}-{}", id_length, min_length, max_length);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::INVALID_PARAMETER,
+                "message": format!("ID length must be between {} and {}", min_length, max_length),
+                "details": { "field": "length", "min": min_length, "max": max_length },
+            }))
+        )
+        .into_response();
+    }
+
+    let max_receivers = match query.get("receivers") {
+        Some(_) => match parse_u64_param(query.get("receivers"), "receivers") {
+            Ok(receivers) => receivers.min(u32::MAX as u64) as u32,
+            Err(err) => return err.into_response(),
+        },
+        None => 1,
+    };
+    if max_receivers == 0 || max_receivers as usize > max_receivers_per_code() {
+        event!(Level::WARN, "Requested receiver count {} outside bounds 1-{}", max_receivers, max_receivers_per_code());
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::INVALID_PARAMETER,
+                "message": format!("receivers must be between 1 and {}", max_receivers_per_code()),
+                "details": { "field": "receivers", "min": 1, "max": max_receivers_per_code() },
             }))
         )
         .into_response();
@@ -357,12 +408,36 @@ pub async fn get_id(
     // Changed from INFO to DEBUG to reduce log verbosity
     event!(Level::DEBUG, "Generating new ID for file '{}' with size {}", file_name, file_size);
 
-    let meta_info = MetaInfo::new(file_name, file_size);
+    let mut meta_info = MetaInfo::new(file_name, file_size);
+    meta_info.max_receivers = max_receivers;
+    meta_info.require_approval = query.get("approval").is_some_and(|v| v == "true" || v == "1");
+    meta_info.sender_nonce = token::random_hex(16);
+    meta_info.owners = auth::owner_tags(&headers);
+    let sender_token_meta = meta_info.clone();
+
+    // Quotas are charged only for requests that passed validation
+    if let Some(key) = api_key.as_deref()
+        && let Err(err) = auth::charge_creation(key).await
+    {
+        return err.into_response();
+    }
+    if let Err(err) = tenant::charge_creation().await {
+        if let Some(key) = api_key.as_deref() {
+            auth::refund_creation(key).await;
+        }
+        return err.into_response();
+    }
 
-    match MetaInfo::get_db().insert(&id, meta_info, META_TTL_SECS).await {
-        Ok(_) => {
+    let id = match mint_id(meta_info, id_length).await {
+        Ok(id) => {
+            stats::record_created(&id);
+            audit::record(audit::TRANSFER_CREATED, Some(&id), client, json!({
+                "file_size": file_size,
+                "max_receivers": max_receivers,
+            }));
             // Changed from INFO to DEBUG to reduce log verbosity
             event!(Level::DEBUG, "Successfully generated ID: {}", id);
+            id
         },
         Err(e) => {
             event!(Level::ERROR, "Failed to insert meta info into DB: {}", e);
@@ -371,6 +446,7 @@ pub async fn get_id(
                 Json(json!({
                     "code": 500,
                     "success": false,
+                    "error": errors::INTERNAL,
                     "message": "Internal Server Error"
                 }))
             ).into_response();
@@ -381,7 +457,10 @@ pub async fn get_id(
         "code": 200,
         "success": true,
         "data": {
-            "id": id
+            "id": tenant::bare(&id),
+            "sender_token": auth::issue_sender_token(&id, &sender_token_meta),
+            // Links for this transfer only resolve under /t/{tenant}
+            "tenant": tenant::of(&id),
         }
     }))
     .into_response()
@@ -391,6 +470,7 @@ pub async fn get_id(
 /// Returns file metadata and transfer status
 #[instrument]
 pub async fn get_status(Path(id): Path<String>) -> impl IntoResponse {
+    let id = normalize_id(&id);
     // Changed from DEBUG to TRACE to reduce log verbosity
     event!(Level::TRACE, "Checking status for ID: {}", id);
     
@@ -409,6 +489,20 @@ pub async fn get_status(Path(id): Path<String>) -> impl IntoResponse {
                         "file_size": meta_info.value.file_size,
                         "is_using": meta_info.value.is_using,
                         "done": meta_info.value.done,
+                        "paused": meta_info.value.paused,
+                        // Per-receiver progress; token nonces are deliberately not exposed
+                        "receivers": meta_info.value.receivers.iter().map(|r| json!({
+                            "cursor": r.cursor,
+                        })).collect::<Vec<_>>(),
+                        "max_receivers": meta_info.value.max_receivers,
+                        // Relay throughput, and which side is holding the transfer back
+                        "metrics": stats::transfer_metrics(&meta_info.value.metrics),
+                        // Server-attested hash of the bytes relayed so far, in file order
+                        "digest": {
+                            "algorithm": "sha256",
+                            "bytes": meta_info.value.digest.offset,
+                            "value": meta_info.value.digest.hex(),
+                        },
                     }
 
                 }))
@@ -421,6 +515,7 @@ pub async fn get_status(Path(id): Path<String>) -> impl IntoResponse {
                 Json(json!({
                     "code": 404,
                     "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
                     "message": "Not Found"
                 }))
             )
@@ -428,189 +523,814 @@ pub async fn get_status(Path(id): Path<String>) -> impl IntoResponse {
     }
 }
 
-/// Handler for downloading file chunks
-/// Supports range requests for chunked file transfer
-/// Includes retry logic and atomic operations for concurrent access
-#[instrument(skip_all)]
-pub async fn get_file(
+/// Handler for previewing a transfer before accepting it
+/// Returns file name, size, block size and expiry without claiming the code
+#[instrument]
+pub async fn get_meta(Path(id): Path<String>) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    match MetaInfo::get_db().get(&id).await {
+        Some(meta_info) => {
+            let expires_in = meta_info.exp.saturating_duration_since(std::time::Instant::now()).as_secs();
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "code": 200,
+                    "success": true,
+                    "data": {
+                        "file_name": meta_info.value.file_name,
+                        "file_size": meta_info.value.file_size,
+                        "block_size": meta_info.value.block_size,
+                        "expires_at": unix_now() + expires_in,
+                        "expires_in": expires_in,
+                        "claimed": meta_info.value.is_using,
+                        "receiver_slots": meta_info.value.max_receivers as usize - meta_info.value.receivers.len().min(meta_info.value.max_receivers as usize),
+                        "done": meta_info.value.done,
+                    }
+                }))
+            )
+        },
+        None => {
+            event!(Level::WARN, "Meta preview failed - ID not found: {}", id);
+            (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+        },
+    }
+}
+
+/// Handler for registering or overwriting the file metadata of a transfer
+/// Lets the sender fix up the file name and size before any block is uploaded
+#[instrument(skip(headers, payload))]
+pub async fn update_meta(
     Path(id): Path<String>,
-    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    Json(payload): Json<UpdateMetaSchema>,
 ) -> impl IntoResponse {
-    let receive_id = match query.get("rid") {
-        Some(receive_id) => receive_id.to_string(),
+    let id = normalize_id(&id);
+
+    let meta_db = MetaInfo::get_db();
+    let meta_info = match meta_db.get(&id).await {
+        Some(meta_info) => meta_info,
         None => {
-            event!(Level::WARN, "Missing Parameter: rid");
+            event!(Level::WARN, "Meta update failed - ID not found: {}", id);
             return (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "code": 400,
-                "success": false,
-                "message": "Missing Parameter: rid"
-            })))
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
             .into_response();
         }
     };
 
-    let start = match parse_u64_param(query.get("start"), "start") {
-        Ok(s) => s,
-        Err(err) => return err.into_response(),
-    };
-
-    if start == 0 {
-        // Changed from INFO to DEBUG to reduce log verbosity
-        event!(Level::DEBUG, "Starting file download for ID: {} by receiver: {}", id, receive_id);
-        // Try to get the metadata and atomically update it in a single operation
-        let mut retries = 0;
-        
-        loop {
-            let meta_info = MetaInfo::get_db().get(&id).await;
-            
-            match meta_info {
-                Some(mut current_meta) => {
-                    if current_meta.value.is_using
-                        && !current_meta.value.used_by.is_empty()
-                        && current_meta.value.used_by != receive_id
-                    {
-                        event!(Level::WARN, "File already in use for ID: {}", id);
-                        return (
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({
-                            "code": 400,
-                            "success": false,
-                            "message": "Bad Request"
-                        })))
-                        .into_response();
-                    }
+    if let Err(err) = auth::authorize_sender(&id, &headers, &meta_info.value) {
+        return err.into_response();
+    }
 
-                    let should_update = !current_meta.value.is_using
-                        || current_meta.value.used_by.is_empty()
-                        || current_meta.value.used_by != receive_id;
-
-                    if should_update {
-                        current_meta.value.is_using = true;
-                        current_meta.value.used_by = receive_id.clone();
-
-                        match MetaInfo::get_db().update(&id, current_meta.value, current_meta.exp).await {
-                            Ok(_) => {
-                                event!(Level::DEBUG, "Successfully updated metadata for ID: {}", id);
-                                break;
-                            }
-                            Err(_) => {
-                                retries += 1;
-                                if retries >= MAX_RETRIES {
-                                    event!(Level::ERROR, "Failed to update metadata after {} retries for ID: {}", MAX_RETRIES, id);
-                                    return (
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    Json(json!({
-                                        "code": 500,
-                                        "success": false,
-                                        "message": "Internal Server Error"
-                                    })))
-                                    .into_response();
-                                }
-                                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                            }
-                        }
-                    } else {
-                        break;
-                    }
-                }
-                None => {
-                    event!(Level::WARN, "Access ID Not Found: {}", id);
-                    return (StatusCode::NOT_FOUND, "Access ID Not Found").into_response();
-                }
-            }
-        }
-        
-        // Small delay to ensure atomicity
-        tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_INTERVAL)).await;
+    if payload.file_size > max_total_size() {
+        event!(Level::WARN, "File too large during meta update: {} bytes > {}", payload.file_size, max_total_size());
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::FILE_TOO_LARGE,
+                "message": "File exceeds maximum allowed size",
+                "details": { "max_file_size": max_total_size() },
+            }))
+        )
+        .into_response();
     }
 
-    match MetaInfo::get_db().get(&id).await {
-        Some(meta_info) => {
-            if meta_info.value.used_by != receive_id {
-                event!(Level::WARN, "Wrong Receive ID for ID: {}", id);
-                return (
-                StatusCode::BAD_REQUEST,
+    let (payload_name, payload_size) = (payload.file_name.clone(), payload.file_size);
+    let updated = meta_db
+        .modify(&id, |meta| {
+            // Once a block was accepted the receiver may already hold part of
+            // the old file, even after the relay dropped it
+            if meta.done || meta.metrics.upload.blocks > 0 || meta.digest.offset > 0 {
+                return false;
+            }
+            meta.file_name = payload.file_name;
+            meta.file_size = payload.file_size;
+            true
+        })
+        .await;
+
+    match updated {
+        Some(true) => {
+            event!(Level::DEBUG, "Meta updated for ID: {}", id);
+            events::publish(&id, events::METADATA_UPDATED, json!({
+                "file_name": payload_name,
+                "file_size": payload_size,
+            }));
+            (
+                StatusCode::OK,
                 Json(json!({
-                    "code": 400,
+                    "code": 200,
+                    "success": true,
+                }))
+            )
+            .into_response()
+        },
+        Some(false) => {
+            event!(Level::WARN, "Meta update rejected - transfer already started: {}", id);
+            (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "code": 409,
                     "success": false,
-                    "message": "Wrong Receive ID"
-                }))).into_response();
-            }
+                    "error": errors::TRANSFER_STARTED,
+                    "message": "Transfer already started"
+                }))
+            )
+            .into_response()
         },
-        None => {
-            event!(Level::WARN, "Access ID Not Found during verification: {}", id);
-            return (StatusCode::NOT_FOUND, "Access ID Not Found").into_response();
-        }
-    };
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        )
+        .into_response(),
+    }
+}
 
-    // Retry logic for getting file block with extended retries while staying under client timeout
-    let mut retries = 0;
-    
-    let (block_name, block_data, block_start, block_end, block_total) = loop {
-        match FileBlock::get_db().get(&format!("{}:{:012}", &id, start)).await {
-            Some(file_block) => {
-                if file_block.value.start > start {
-                    event!(Level::WARN, "Wrong start position for ID: {} and start: {}", id.clone(), start);
-                    return (
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({
-                        "code": 400,
-                        "success": false,
-                        "message": "Wrong start position"
-                    })))
-                    .into_response();
-                }
-                // Changed from DEBUG to TRACE to reduce log verbosity
-                event!(Level::TRACE, "Retrieved block for ID: {} and start: {}", id.clone(), start);
-                break (file_block.value.filename, file_block.value.data, file_block.value.start, file_block.value.end, file_block.value.total);
-            },
-            None => {
-                if retries >= BLOCK_FETCH_MAX_RETRIES {
-                    event!(Level::WARN, "Block {}:{:012} not ready after {} retries", &id, start, BLOCK_FETCH_MAX_RETRIES);
-                    return (
-                        StatusCode::TOO_EARLY,
-                        Json(json!({
-                            "code": 425,
-                            "success": false,
-                            "message": "Block not ready, retry shortly"
-                        }))
-                    )
-                    .into_response();
-                }
-                retries += 1;
-                tokio::time::sleep(tokio::time::Duration::from_millis(BLOCK_FETCH_RETRY_INTERVAL)).await;
+/// Handler for claiming an access code as one of its receivers
+/// Mints a signed receiver token that must accompany every download call;
+/// presenting a current token again returns it unchanged. Codes created with
+/// `receivers=N` accept up to N concurrent claims; with `approval=true` new
+/// claims stay pending until the sender approves them
+#[instrument(skip_all)]
+pub async fn claim(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    Query(query): Query<ClaimQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    let presented = query
+        .token
+        .as_deref()
+        .and_then(|t| token::open(RECEIVER_TOKEN_SCOPE, &id, t))
+        .map(str::to_string);
+    let (rid, receiver_token) = token::mint(RECEIVER_TOKEN_SCOPE, &id);
+
+    // Ok((true, _)): freshly claimed, Ok((false, _)): already held by the presented token;
+    // the second field tells whether the claim is approved
+    let outcome = MetaInfo::get_db().modify(&id, |meta| {
+        if let Some(claim) = presented.as_deref().and_then(|presented| meta.receiver(presented)) {
+            return Ok((false, claim.approved));
+        }
+        if !meta.has_free_slot() {
+            return Err(());
+        }
+        let approved = !meta.require_approval;
+        meta.is_using = true;
+        meta.receivers.push(ReceiverClaim { id: rid.clone(), cursor: 0, approved, missing: None });
+        Ok((true, approved))
+    }).await;
+
+    let (receiver_token, approved) = match outcome {
+        Some(Ok((true, approved))) => {
+            event!(Level::DEBUG, "Access code claimed for ID: {}", id);
+            audit::record(audit::TRANSFER_CLAIMED, Some(&id), client, json!({
+                "receiver": rid,
+                "approved": approved,
+            }));
+            if !approved {
+                signaling::notify_sender(&id, signaling::APPROVAL_REQUEST, json!({ "receiver": rid })).await;
+            }
+            (receiver_token, approved)
+        }
+        Some(Ok((false, approved))) => (query.token.unwrap_or_default(), approved),
+        Some(Err(())) => {
+            event!(Level::WARN, "Access code already claimed for ID: {}", id);
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "code": 409,
+                    "success": false,
+                    "error": errors::ROOM_TAKEN,
+                    "message": "Access code already claimed"
+                }))
+            )
+            .into_response();
+        }
+        None => {
+            event!(Level::WARN, "Claim failed - ID not found: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+            .into_response();
+        }
+    };
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "webdav": webdav::mount_path(&id, &receiver_token),
+            "token": receiver_token,
+            "approved": approved,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for listing the receivers holding a claim on a code
+/// Requires the sender token; pending claims await review
+#[instrument(skip_all)]
+pub async fn list_receivers(Path(id): Path<String>, headers: HeaderMap) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    let meta_info = match MetaInfo::get_db().get(&id).await {
+        Some(meta_info) => meta_info,
+        None => {
+            event!(Level::WARN, "Receiver listing failed - ID not found: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+            .into_response();
+        }
+    };
+
+    if let Err(err) = auth::authorize_sender(&id, &headers, &meta_info.value) {
+        return err.into_response();
+    }
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "require_approval": meta_info.value.require_approval,
+            "receivers": meta_info.value.receivers.iter().map(|r| json!({
+                "id": r.id,
+                "approved": r.approved,
+                "cursor": r.cursor,
+            })).collect::<Vec<_>>(),
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for approving or rejecting a receiver claim
+/// Requires the sender token; a rejected claim frees its slot
+#[instrument(skip(headers, payload))]
+pub async fn review_receiver(
+    Path((id, rid)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(payload): Json<ReviewReceiverPayload>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    let meta_db = MetaInfo::get_db();
+    let meta_info = match meta_db.get(&id).await {
+        Some(meta_info) => meta_info,
+        None => {
+            event!(Level::WARN, "Receiver review failed - ID not found: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+            .into_response();
+        }
+    };
+
+    if let Err(err) = auth::authorize_sender(&id, &headers, &meta_info.value) {
+        return err.into_response();
+    }
+
+    let reviewed = meta_db.modify(&id, |meta| {
+        let index = meta.receivers.iter().position(|r| r.id == rid)?;
+        if payload.approve {
+            meta.receivers[index].approved = true;
+        } else {
+            meta.receivers.remove(index);
+            meta.is_using = !meta.receivers.is_empty();
+        }
+        Some(())
+    }).await.flatten();
+
+    if reviewed.is_none() {
+        event!(Level::WARN, "Receiver review failed - unknown receiver for ID: {}", id);
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::RECEIVER_NOT_FOUND,
+                "message": "Unknown receiver"
+            }))
+        )
+        .into_response();
+    }
+
+    event!(Level::DEBUG, "Receiver {} for ID {}: {}", if payload.approve { "approved" } else { "rejected" }, id, rid);
+    Json(json!({
+        "code": 200,
+        "success": true,
+    }))
+    .into_response()
+}
+
+/// Handler for generating a pre-authorized download link
+/// The link carries an HMAC over id, expiry and max uses so the receiver
+/// can redeem it for a receiver token without claiming the code first
+#[instrument(skip_all)]
+pub async fn create_link(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateLinkPayload>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    let meta_info = match MetaInfo::get_db().get(&id).await {
+        Some(meta_info) => meta_info,
+        None => {
+            event!(Level::WARN, "Link creation failed - ID not found: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+            .into_response();
+        }
+    };
+
+    if let Err(err) = auth::authorize_sender(&id, &headers, &meta_info.value) {
+        return err.into_response();
+    }
+
+    // Links never outlive the transfer metadata they point at
+    let remaining = meta_info.exp.saturating_duration_since(std::time::Instant::now()).as_secs();
+    let ttl_secs = payload.ttl_secs.unwrap_or(DEFAULT_LINK_TTL_SECS).min(remaining);
+    let max_uses = payload.max_uses.unwrap_or(1);
+    if ttl_secs == 0 || max_uses == 0 || max_uses > MAX_LINK_USES {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::INVALID_PARAMETER,
+                "message": format!("max_uses must be between 1 and {} and ttl_secs positive", MAX_LINK_USES),
+                "details": { "field": "max_uses", "min": 1, "max": MAX_LINK_USES },
+            }))
+        )
+        .into_response();
+    }
+
+    let exp = unix_now() + ttl_secs;
+    let sig = token::sign(LINK_SCOPE, &[&id, &exp.to_string(), &max_uses.to_string()]);
+
+    event!(Level::DEBUG, "Created download link for ID: {} valid {}s for {} uses", id, ttl_secs, max_uses);
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "url": format!("{}/{}/file?exp={}&uses={}&sig={}", tenant::path_prefix(), tenant::bare(&id), exp, max_uses, sig),
+            "expires_at": exp,
+            "max_uses": max_uses,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for redeeming a pre-authorized download link
+/// Binds a free receiver slot of the transfer to a fresh receiver token
+#[instrument(skip_all)]
+pub async fn redeem_link(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    Query(query): Query<RedeemLinkQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    let valid = token::verify(LINK_SCOPE, &[&id, &query.exp.to_string(), &query.uses.to_string()], &query.sig);
+    if !valid || query.exp <= unix_now() {
+        event!(Level::WARN, "Invalid or expired download link for ID: {}", id);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_LINK,
+                "message": "Invalid or expired download link"
+            }))
+        )
+        .into_response();
+    }
+
+    let usage_db = LinkUsage::get_db();
+    let ttl_secs = query.exp - unix_now();
+    let _ = usage_db.insert_if_absent(&query.sig, LinkUsage { uses: 0 }, ttl_secs).await;
+    let within_limit = usage_db.modify(&query.sig, |usage| {
+        if usage.uses >= query.uses {
+            return false;
+        }
+        usage.uses += 1;
+        true
+    }).await;
+
+    if within_limit != Some(true) {
+        event!(Level::WARN, "Download link exhausted for ID: {}", id);
+        return (
+            StatusCode::GONE,
+            Json(json!({
+                "code": 410,
+                "success": false,
+                "error": errors::LINK_USED,
+                "message": "Download link already used"
+            }))
+        )
+        .into_response();
+    }
+
+    let (rid, receiver_token) = token::mint(RECEIVER_TOKEN_SCOPE, &id);
+    // Receivers already downloading keep their slots; a link never evicts one
+    let claimed = MetaInfo::get_db().modify(&id, |meta| {
+        if !meta.has_free_slot() {
+            return false;
+        }
+        meta.is_using = true;
+        // The link itself is the sender's approval
+        meta.receivers.push(ReceiverClaim { id: rid.clone(), cursor: 0, approved: true, missing: None });
+        true
+    }).await;
+
+    if claimed == Some(false) {
+        event!(Level::WARN, "Link redemption failed - no free receiver slot for ID: {}", id);
+        // The use was not spent on a download, so give it back
+        usage_db.modify(&query.sig, |usage| usage.uses = usage.uses.saturating_sub(1)).await;
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "code": 409,
+                "success": false,
+                "error": errors::ROOM_TAKEN,
+                "message": "All receiver slots are taken"
+            }))
+        )
+        .into_response();
+    }
+    if claimed.is_none() {
+        event!(Level::WARN, "Link redemption failed - ID not found: {}", id);
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        )
+        .into_response();
+    }
+
+    event!(Level::DEBUG, "Download link redeemed for ID: {}", id);
+    audit::record(audit::LINK_REDEEMED, Some(&id), client, json!({ "receiver": rid }));
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "webdav": webdav::mount_path(&id, &receiver_token),
+            "token": receiver_token
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for a receiver handing its claim to another device (phone → laptop)
+/// Returns a short-lived token that moves the claim, progress included, to
+/// whichever device redeems it first
+#[instrument(skip_all)]
+pub async fn create_handoff(
+    Path(id): Path<String>,
+    ValidQuery(query): ValidQuery<ReceiverQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let Some(receive_id) = token::open(RECEIVER_TOKEN_SCOPE, &id, &query.token).map(str::to_string) else {
+        event!(Level::WARN, "Invalid receiver token for handoff of ID: {}", id);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_RECEIVER_TOKEN,
+                "message": "Invalid receiver token"
+            }))
+        )
+        .into_response();
+    };
+
+    match MetaInfo::get_db().get(&id).await {
+        Some(meta_info) if meta_info.value.receiver(&receive_id).is_some() => {}
+        Some(_) => {
+            event!(Level::WARN, "Wrong Receive ID for handoff of ID: {}", id);
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "code": 400,
+                    "success": false,
+                    "error": errors::WRONG_RECEIVER,
+                    "message": "Wrong Receive ID"
+                }))
+            )
+            .into_response();
+        }
+        None => {
+            event!(Level::WARN, "Handoff failed - ID not found: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+            .into_response();
+        }
+    }
+
+    let expires = (unix_now() + HANDOFF_TTL_SECS).to_string();
+    let signature = token::sign(HANDOFF_SCOPE, &[&id, &receive_id, &expires]);
+    let handoff = format!("{}.{}.{}", receive_id, expires, signature);
+    event!(Level::DEBUG, "Receiver {} of ID {} created a handoff", receive_id, id);
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "handoff": handoff,
+            "url": format!("{}/{}/file?handoff={}", tenant::path_prefix(), tenant::bare(&id), handoff),
+            "expires_in": HANDOFF_TTL_SECS,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for redeeming a handoff token on the new device
+/// The claim keeps its progress and approval but gets a fresh receiver token;
+/// the old device's token stops working in the same step
+#[instrument(skip_all)]
+pub async fn redeem_handoff(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    ValidQuery(query): ValidQuery<HandoffQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
+    let mut parts = query.handoff.splitn(3, '.');
+    let previous = match (parts.next(), parts.next(), parts.next()) {
+        (Some(rid), Some(expires), Some(signature))
+            if expires.parse::<u64>().is_ok_and(|expires| expires > unix_now())
+                && token::verify(HANDOFF_SCOPE, &[&id, rid, expires], signature) =>
+        {
+            rid.to_string()
+        }
+        _ => {
+            event!(Level::WARN, "Invalid or expired handoff token for ID: {}", id);
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "code": 403,
+                    "success": false,
+                    "error": errors::INVALID_HANDOFF,
+                    "message": "Invalid or expired handoff token"
+                }))
+            )
+            .into_response();
+        }
+    };
+
+    let (rid, receiver_token) = token::mint(RECEIVER_TOKEN_SCOPE, &id);
+    // Renaming the claim is what makes the token single-use
+    let handed = MetaInfo::get_db().modify(&id, |meta| {
+        let claim = meta.receivers.iter_mut().find(|r| r.id == previous)?;
+        claim.id = rid.clone();
+        Some((claim.approved, claim.cursor))
+    }).await;
+
+    let (approved, cursor) = match handed {
+        Some(Some(handed)) => handed,
+        Some(None) => {
+            event!(Level::WARN, "Handoff token already used for ID: {}", id);
+            return (
+                StatusCode::GONE,
+                Json(json!({
+                    "code": 410,
+                    "success": false,
+                    "error": errors::HANDOFF_USED,
+                    "message": "Handoff token already used"
+                }))
+            )
+            .into_response();
+        }
+        None => {
+            event!(Level::WARN, "Handoff redemption failed - ID not found: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+            .into_response();
+        }
+    };
+
+    event!(Level::DEBUG, "Claim of ID {} handed off from {} to {}", id, previous, rid);
+    audit::record(audit::CLAIM_HANDED_OFF, Some(&id), client, json!({ "from": previous, "to": rid }));
+    signaling::notify_sender(&id, signaling::RECEIVER_HANDOFF, json!({ "from": previous, "to": rid })).await;
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "webdav": webdav::mount_path(&id, &receiver_token),
+            "token": receiver_token,
+            "approved": approved,
+            "cursor": cursor,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for downloading file chunks
+/// Supports range requests for chunked file transfer
+/// Includes retry logic and atomic operations for concurrent access
+#[instrument(skip_all)]
+pub async fn get_file(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    ValidQuery(query): ValidQuery<DownloadQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let start = query.start;
+
+    let receive_id = match token::open(RECEIVER_TOKEN_SCOPE, &id, &query.token) {
+        Some(receive_id) => receive_id.to_string(),
+        None => {
+            event!(Level::WARN, "Invalid receiver token for ID: {}", id);
+            return (
+            StatusCode::FORBIDDEN,
+            Extension(errors::ErrorCode(errors::INVALID_RECEIVER_TOKEN)),
+            Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_RECEIVER_TOKEN,
+                "message": "Invalid receiver token"
+            })))
+            .into_response();
+        }
+    };
+
+    let max_receivers = match MetaInfo::get_db().get(&id).await {
+        Some(meta_info) => {
+            let claim = meta_info.value.receiver(&receive_id);
+            if claim.is_some_and(|claim| !claim.approved) {
+                event!(Level::WARN, "Receiver awaiting approval for ID: {}", id);
+                return (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "code": 403,
+                    "success": false,
+                    "error": errors::AWAITING_APPROVAL,
+                    "message": "Awaiting sender approval"
+                }))).into_response();
+            }
+            if claim.is_none() {
+                event!(Level::WARN, "Wrong Receive ID for ID: {}", id);
+                return (
+                StatusCode::BAD_REQUEST,
+                Extension(errors::ErrorCode(errors::WRONG_RECEIVER)),
+                Json(json!({
+                    "code": 400,
+                    "success": false,
+                    "error": errors::WRONG_RECEIVER,
+                    "message": "Wrong Receive ID"
+                }))).into_response();
+            }
+            if meta_info.value.paused {
+                event!(Level::TRACE, "Block request while paused for ID: {}", id);
+                return (
+                StatusCode::LOCKED,
+                Json(json!({
+                    "code": 423,
+                    "success": false,
+                    "error": errors::TRANSFER_PAUSED,
+                    "message": "Transfer paused by sender"
+                }))).into_response();
             }
+            meta_info.value.max_receivers as usize
+        },
+        None => {
+            event!(Level::WARN, "Access ID Not Found during verification: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Access ID Not Found"
+                }))
+            )
+            .into_response();
         }
     };
 
-    // Delete the block in a separate async task to prevent blocking the response
-    // This ensures that even if the deletion fails, the response is still sent
-    let delete_task = async |id, start|{
-        match FileBlock::get_db().remove(&format!("{}:{:012}", &id, start)).await {
-            Some(_) => {
-                // Changed from DEBUG to TRACE to reduce log verbosity
-                event!(Level::TRACE, "Successfully removed block {}:{:012}", &id, start);
-            }
-            None => {
-                // Changed from WARN to TRACE to reduce log verbosity
-                event!(Level::TRACE, "Attempted to remove non-existent block {}:{:012}", &id, start);
-            }
-        }
+    let wait_started = Instant::now();
+    let Some(file_block) = wait_for_block(&id, start).await else {
+        event!(Level::WARN, "Block {}:{:012} not ready after {} retries", &id, start, BLOCK_FETCH_MAX_RETRIES);
+        return (
+            StatusCode::TOO_EARLY,
+            Json(json!({
+                "code": 425,
+                "success": false,
+                "error": errors::BLOCK_NOT_READY,
+                "message": "Block not ready, retry shortly"
+            }))
+        )
+        .into_response();
     };
-    
-    // Spawn the deletion task but don't wait for it to complete
-    tokio::spawn(delete_task(id.clone(), start));
+    if file_block.start > start {
+        event!(Level::WARN, "Wrong start position for ID: {} and start: {}", id.clone(), start);
+        return (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "code": 400,
+            "success": false,
+            "error": errors::WRONG_START,
+            "message": "Wrong start position"
+        })))
+        .into_response();
+    }
+    // Changed from DEBUG to TRACE to reduce log verbosity
+    event!(Level::TRACE, "Retrieved block for ID: {} and start: {}", id.clone(), start);
+    let FileBlock { filename: block_name, data: block_data, start: block_start, end: block_end, total: block_total, .. } = file_block;
+
+    // Record consumption without blocking the response
+    record_consumption(&id, &receive_id, start, block_end, max_receivers, block_data.len() as u64, wait_started.elapsed());
 
-    let headers: [(&str, &str); 3] = [
-        ("Content-Name", &block_name),
-        ("Content-Type", "application/octet-stream"),
-        ("Content-Range", &format!("bytes {}-{}/{}", block_start, block_end, block_total)),
+    let mut headers = vec![
+        ("Content-Name", block_name),
+        ("Content-Type", "application/octet-stream".to_string()),
+        ("Content-Range", format!("bytes {}-{}/{}", block_start, block_end, block_total)),
     ];
+    // The final block carries the digest of the whole file once every block went through the relay
+    if block_end + 1 == block_total
+        && let Some(meta_info) = MetaInfo::get_db().get(&id).await
+        && meta_info.value.digest.offset == block_total
+    {
+        headers.push((CONTENT_SHA256_HEADER, meta_info.value.digest.hex()));
+    }
     
     // Changed from DEBUG to TRACE to reduce log verbosity
     event!(Level::TRACE, "Sending file block for ID: {} range: {}-{}", id, block_start, block_end);
+    stats::record_relayed(&id, block_data.len() as u64);
+    audit::record(audit::BLOCK_RELAYED, Some(&id), client, json!({
+        "receiver": receive_id,
+        "start": block_start,
+        "end": block_end,
+    }));
     
     (
         StatusCode::PARTIAL_CONTENT,
@@ -620,29 +1340,199 @@ pub async fn get_file(
 }
 
 
+/// Wait for the block starting at `start`, retrying while it is still being
+/// uploaded; None when it did not arrive within the retry budget
+pub(crate) async fn wait_for_block(id: &str, start: u64) -> Option<FileBlock> {
+    // Extended retries while staying under the client timeout
+    let mut retries = 0;
+    loop {
+        if let Some(file_block) = FileBlock::get_db().get(&format!("{}:{:012}", id, start)).await {
+            return Some(file_block.value);
+        }
+        if retries >= BLOCK_FETCH_MAX_RETRIES {
+            return None;
+        }
+        retries += 1;
+        tokio::time::sleep(tokio::time::Duration::from_millis(BLOCK_FETCH_RETRY_INTERVAL)).await;
+    }
+}
+
+/// Hold until the sender resumes a paused transfer; returns at once when it is
+/// not paused or no longer exists
+pub(crate) async fn wait_while_paused(id: &str) {
+    while MetaInfo::get_db().get(id).await.is_some_and(|meta| meta.value.paused) {
+        tokio::time::sleep(tokio::time::Duration::from_millis(PAUSE_POLL_INTERVAL)).await;
+    }
+}
+
+/// Record that a receiver consumed the block at `start` in a separate task;
+/// the block is dropped once every receiver slot has consumed it (or its TTL lapses).
+/// `waited` is how long the receiver was kept waiting for the block to arrive
+pub(crate) fn record_consumption(
+    id: &str,
+    receive_id: &str,
+    start: u64,
+    block_end: u64,
+    max_receivers: usize,
+    bytes: u64,
+    waited: Duration,
+) {
+    let id = id.to_string();
+    let receive_id = receive_id.to_string();
+    tokio::spawn(async move {
+        let key = format!("{}:{:012}", &id, start);
+        let block_db = FileBlock::get_db();
+        let consumed = block_db.modify(&key, |block| {
+            // Time the block sat at the relay before anyone picked it up
+            let dwell = block.served_to.is_empty().then(|| block.stored_at.elapsed());
+            if !block.served_to.contains(&receive_id) {
+                block.served_to.push(receive_id.clone());
+            }
+            let newly_consumed = block.consumed_at.is_none() && block.served_to.len() >= max_receivers;
+            if newly_consumed {
+                block.consumed_at = Some(Instant::now());
+            }
+            (newly_consumed, dwell)
+        }).await;
+        if consumed.is_some_and(|(newly_consumed, _)| newly_consumed) {
+            // Keep it briefly so a response that failed mid-body can be fetched again
+            match served_block_grace_secs() {
+                0 => {
+                    block_db.remove(&key).await;
+                    event!(Level::TRACE, "Successfully removed block {}", key);
+                }
+                grace => {
+                    block_db.shorten_ttl(&key, grace).await;
+                    event!(Level::TRACE, "Block {} consumed, retained for {}s", key, grace);
+                }
+            }
+        }
+
+        MetaInfo::get_db().modify(&id, |meta| {
+            if let Some(receiver) = meta.receivers.iter_mut().find(|r| r.id == receive_id) {
+                receiver.cursor = receiver.cursor.max(block_end);
+            }
+            meta.metrics.download.record(bytes, Duration::ZERO);
+            meta.metrics.receiver_wait += waited;
+            if let Some((_, Some(dwell))) = consumed {
+                meta.metrics.buffer_dwell += dwell;
+            }
+        }).await;
+    });
+}
+
+/// Seconds until the receivers of a full window are expected to have consumed
+/// a block of `block_bytes`, from their recent download rate
+fn window_retry_after(meta: Option<&MetaInfo>, block_bytes: u64) -> u64 {
+    // A buffered block only leaves the window once every receiver slot has read it
+    let drain_bps = meta
+        .map(|meta| stats::recent_bps(&meta.metrics.download, Instant::now()) / u64::from(meta.max_receivers.max(1)))
+        .unwrap_or(0);
+    match drain_bps {
+        0 => WINDOW_RETRY_IDLE_SECS,
+        bps => block_bytes.div_ceil(bps),
+    }
+    .clamp(1, WINDOW_RETRY_MAX_SECS)
+}
+
+/// Feed buffered blocks into the transfer's running digest for as long as
+/// they continue where it left off; out-of-order blocks wait for their
+/// predecessor, whose upload picks them up
+async fn advance_digest(id: &str) {
+    let meta_db = MetaInfo::get_db();
+    let block_db = FileBlock::get_db();
+    while let Some(offset) = meta_db.get(id).await.map(|meta| meta.value.digest.offset) {
+        let Some(block) = block_db.get(&format!("{}:{:012}", id, offset)).await else {
+            break;
+        };
+        // Only apply when no concurrent upload advanced the digest meanwhile
+        let applied = meta_db.modify(id, |meta| {
+            let current = meta.digest.offset == offset;
+            if current {
+                meta.digest.update(&block.value.data);
+            }
+            current
+        }).await;
+        if applied.is_none() {
+            break;
+        }
+    }
+}
+
+fn total_mismatch(total: u64, expected: u64) -> (StatusCode, Json<serde_json::Value>) {
+    (StatusCode::CONFLICT, Json(json!({
+        "code": 409,
+        "success": false,
+        "error": errors::BLOCK_TOTAL_MISMATCH,
+        "message": "Block total disagrees with the file size",
+        "details": { "total": total, "expected": expected },
+    })))
+}
+
+/// Check that a block's inclusive range lies inside its file and that the
+/// file size matches what the transfer already knows; these values end up in
+/// the `Content-Range` served to receivers
+fn validate_block_info(info: &FileInfo, known_total: Option<u64>) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if info.total == 0 || info.end < info.start || info.end >= info.total {
+        return Err((StatusCode::BAD_REQUEST, Json(json!({
+            "code": 400,
+            "success": false,
+            "error": errors::BLOCK_RANGE_INVALID,
+            "message": "Invalid file range",
+            "details": { "start": info.start, "end": info.end, "total": info.total },
+        }))));
+    }
+    if let Some(expected) = known_total.filter(|expected| *expected != info.total) {
+        return Err(total_mismatch(info.total, expected));
+    }
+    Ok(())
+}
+
+/// Record the total of the first accepted block as the file size of a
+/// transfer created without one, so later blocks are checked against it
+async fn pin_total(id: &str, total: u64) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let pinned = MetaInfo::get_db().modify(id, |meta| {
+        if meta.file_size == 0 {
+            meta.file_size = total;
+        }
+        meta.file_size
+    }).await;
+    match pinned {
+        Some(expected) if expected != total => Err(total_mismatch(total, expected)),
+        _ => Ok(()),
+    }
+}
+
 /// Handler for uploading file chunks
 /// Processes multipart form data with file info and chunk data
 /// Includes validation for block size and file limits
-#[instrument]
-pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl IntoResponse {
+/// Requires the sender token issued with the id
+#[instrument(skip(headers, multipart))]
+pub async fn upload_file(Path(id): Path<String>, headers: HeaderMap, multipart: Multipart) -> impl IntoResponse {
+    let id = normalize_id(&id);
     // Changed from INFO to DEBUG to reduce log verbosity for large files
     event!(Level::DEBUG, "Starting file upload for ID: {}", id);
     
     // Allow upload even if receiver hasn't connected yet; only require a valid ID.
-    match MetaInfo::get_db().get(&id).await {
+    let expected_total = match MetaInfo::get_db().get(&id).await {
         Some(meta_info) => {
+            if let Err(err) = auth::authorize_sender(&id, &headers, &meta_info.value) {
+                return err.into_response();
+            }
             if !meta_info.value.is_using {
                 event!(Level::DEBUG, "Receiver not connected yet for ID: {}", id);
             }
+            (meta_info.value.file_size > 0).then_some(meta_info.value.file_size)
         },
         None => {
-            event!(Level::WARN, "Missing Access ID: {}", id);
+            event!(Level::WARN, "Access ID Not Found during upload: {}", id);
             return (
                 StatusCode::NOT_FOUND,
                 Json(json!({
                     "code": 404,
                     "success": false,
-                    "message": "Missing Access ID"
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Access ID Not Found"
                 }))
             )
             .into_response();
@@ -656,6 +1546,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
     let mut start: u64 = 0;
     let mut end: u64 = 0;
     let mut total: u64 = 0;
+    let mut first_upload = false;
 
     // Process info part
     if let Some(field) = match multipart.next_field().await {
@@ -667,6 +1558,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 400,
                     "success": false,
+                    "error": errors::MALFORMED_UPLOAD,
                     "message": "Bad Request: Missing info part"
                 }))
             )
@@ -679,6 +1571,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 500,
                     "success": false,
+                    "error": errors::INTERNAL,
                     "message": "Internal Server Error"
                 }))
             )
@@ -694,6 +1587,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                     Json(json!({
                         "code": 400,
                         "success": false,
+                        "error": errors::MALFORMED_UPLOAD,
                         "message": "Field name is missing"
                     }))
                 )
@@ -708,6 +1602,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 400,
                     "success": false,
+                    "error": errors::MALFORMED_UPLOAD,
                     "message": "First part must be info"
                 }))
             )
@@ -723,6 +1618,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                     Json(json!({
                         "code": 500,
                         "success": false,
+                        "error": errors::MALFORMED_UPLOAD,
                         "message": "Failed to read file data"
                     }))
                 )
@@ -739,6 +1635,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                     Json(json!({
                         "code": 400,
                         "success": false,
+                        "error": errors::MALFORMED_UPLOAD,
                         "message": "Failed to parse info json"
                     }))
                 )
@@ -746,24 +1643,16 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
             }
         };
 
+        if let Err(err) = validate_block_info(&info, expected_total) {
+            event!(Level::WARN, "Inconsistent info part for ID {}: start={}, end={}, total={}, expected total {:?}", id, info.start, info.end, info.total, expected_total);
+            return err.into_response();
+        }
+
         filename = info.filename;
         start = info.start;
         end = info.end;
         total = info.total;
 
-        if end < start || total == 0 || start >= total {
-            event!(Level::WARN, "Invalid range in info part: start={}, end={}, total={}", start, end, total);
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "code": 400,
-                    "success": false,
-                    "message": "Invalid file range"
-                }))
-            )
-            .into_response();
-        }
-
         let max_total = max_total_size();
         if total > max_total {
             event!(Level::WARN, "File too large: {} > {}", total, max_total);
@@ -772,7 +1661,9 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 400,
                     "success": false,
-                    "message": "File exceeds maximum allowed size"
+                    "error": errors::FILE_TOO_LARGE,
+                    "message": "File exceeds maximum allowed size",
+                    "details": { "max_file_size": max_total_size() },
                 }))
             )
             .into_response();
@@ -792,6 +1683,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 400,
                     "success": false,
+                    "error": errors::MALFORMED_UPLOAD,
                     "message": "Missing file part"
                 }))
             )
@@ -804,6 +1696,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 500,
                     "success": false,
+                    "error": errors::INTERNAL,
                     "message": "Internal Server Error"
                 }))
             )
@@ -819,6 +1712,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                     Json(json!({
                         "code": 400,
                         "success": false,
+                        "error": errors::MALFORMED_UPLOAD,
                         "message": "Field name is missing"
                     }))
                 )
@@ -833,12 +1727,14 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 400,
                     "success": false,
+                    "error": errors::MALFORMED_UPLOAD,
                     "message": "Second part must be file"
                 }))
             )
             .into_response();
         }
 
+        let receive_started = Instant::now();
         let data = match field.bytes().await {
             Ok(data) => data,
             Err(err) => {
@@ -848,12 +1744,15 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                     Json(json!({
                         "code": 500,
                         "success": false,
+                        "error": errors::INTERNAL,
                         "message": "Internal Server Error: Failed to read file data"
                     }))
                 )
                 .into_response();
             }
         };
+        let receive_time = receive_started.elapsed();
+        let relay_started = Instant::now();
 
         // Check block size limit
         if data.len() as u64 > max_block_size() {
@@ -862,7 +1761,9 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 400,
                     "success": false,
-                    "message": "Block size exceeds maximum limitation"
+                    "error": errors::BLOCK_TOO_LARGE,
+                    "message": "Block size exceeds maximum limitation",
+                    "details": { "max_block_size": max_block_size() },
                 }))
             )
             .into_response();
@@ -876,7 +1777,9 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                 Json(json!({
                     "code": 400,
                     "success": false,
-                    "message": "Block size mismatch"
+                    "error": errors::BLOCK_SIZE_MISMATCH,
+                    "message": "Block size mismatch",
+                    "details": { "expected": expected_len, "actual": data.len() },
                 }))
             )
             .into_response();
@@ -887,8 +1790,9 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
         let prefix = format!("{}:", id);
         let store = file_block_db.store.read().await;
         
-        for key in store.keys() {
-            if key.starts_with(&prefix) {
+        // Consumed blocks held for re-fetches no longer occupy the window
+        for (key, entry) in store.iter() {
+            if key.starts_with(&prefix) && entry.value.consumed_at.is_none() {
                 block_count += 1;
             }
 
@@ -899,24 +1803,53 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
         drop(store);
 
         if block_count >= max_blocks_per_file() {
+            // Tell the sender when the receivers should have drained a block instead of a hard failure
+            let meta = MetaInfo::get_db().get(&id).await.map(|meta| meta.value);
+            let retry_after = window_retry_after(meta.as_ref(), data.len() as u64);
+            event!(Level::DEBUG, "Block window full for ID: {}, retry after {}s", id, retry_after);
             return (
-                StatusCode::BAD_REQUEST,
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
                 Json(json!({
-                      "code": 400,
+                      "code": 429,
                       "success": false,
-                      "message": format!("Maximum number of blocks per file reached ({})", max_blocks_per_file())
+                      "error": errors::BLOCK_WINDOW_FULL,
+                      "message": format!("Maximum number of blocks per file reached ({})", max_blocks_per_file()),
+                      "details": {
+                          "window": {
+                              "occupied": block_count,
+                              "capacity": max_blocks_per_file(),
+                          },
+                          "retry_after_secs": retry_after,
+                      }
                 }))
             )
             .into_response();
         }
 
-        let file_block = FileBlock::new(
+        let block = inspect::BlockContext { id: &id, file_name: &filename, file_size: total, start, end, data: &data };
+        if let Err(rejection) = inspect::inspect_block(&block).await {
+            event!(Level::WARN, "Block {}-{} for ID {} rejected by {}: {}", start, end, id, rejection.inspector, rejection.reason);
+            return rejection.into_error().into_response();
+        }
+
+        if expected_total.is_none()
+            && let Err(err) = pin_total(&id, total).await
+        {
+            event!(Level::WARN, "Block total {} for ID {} disagrees with a concurrently pinned size", total, id);
+            return err.into_response();
+        }
+
+        let mut file_block = FileBlock::new(
             &data,
             filename.clone(),
             start,
             end,
             total,
         );
+        if block_dedup() && file_block.deduplicate() {
+            event!(Level::DEBUG, "Reusing buffered content for block {}-{} of ID: {}", start, end, id);
+        }
 
         match FileBlock::get_db()
             .insert(&format!("{}:{:012}", &id, start), file_block, BLOCK_TTL_SECS)
@@ -925,6 +1858,15 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
             Ok(_) => {
                 // Changed from INFO to DEBUG to reduce log verbosity for large files
                 event!(Level::DEBUG, "Successfully uploaded block for '{}' range {}-{} of total {} for ID: {}", filename, start, end, total, id);
+                advance_digest(&id).await;
+                let relay_time = relay_started.elapsed();
+                first_upload = MetaInfo::get_db().modify(&id, |meta| {
+                    let first = meta.metrics.upload.blocks == 0;
+                    meta.metrics.upload.record(data.len() as u64, receive_time);
+                    meta.metrics.relay_time += relay_time;
+                    first
+                }).await == Some(true);
+                events::publish(&id, events::BLOCK_AVAILABLE, json!({ "start": start, "end": end, "total": total }));
             },
             Err(e) => {
                 event!(Level::ERROR, "Failed to insert file block into DB: {} for ID: {}", e, id);
@@ -933,6 +1875,7 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
                     Json(json!({
                         "code": 500,
                         "success": false,
+                        "error": errors::INTERNAL,
                         "message": "Internal Server Error"
                     }))
                 )
@@ -941,77 +1884,379 @@ pub async fn upload_file(Path(id): Path<String>, multipart: Multipart) -> impl I
         }
     }
 
-    Json(json!({
+    let mut response = Json(json!({
         "code": 200,
         "success": true,
         "message": "Upload Success"
     }))
-    .into_response()
+    .into_response();
+    if first_upload {
+        response.extensions_mut().insert(abuse::FirstUpload);
+    }
+    response
 }
 
 /// Handler for marking file download as complete
-/// Updates the metadata to indicate successful download
-#[instrument]
-pub async fn done(Path(id): Path<String>, Json(_payload): Json<serde_json::Value>) -> impl IntoResponse {
-    // Mark download as complete for the given ID
+/// Requires the token of an approved receiver; a rejection by the completion inspectors is
+/// reported to the sender and leaves the transfer incomplete
+#[instrument(skip_all)]
+pub async fn done(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    ValidQuery(query): ValidQuery<ReceiverQuery>,
+    Json(_payload): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let Some(receive_id) = token::open(RECEIVER_TOKEN_SCOPE, &id, &query.token).map(str::to_string) else {
+        event!(Level::WARN, "Invalid receiver token for download completion of ID: {}", id);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::INVALID_RECEIVER_TOKEN,
+                "message": "Invalid receiver token"
+            }))
+        );
+    };
+
+    let meta_info = match MetaInfo::get_db().get(&id).await {
+        Some(meta_info) if meta_info.value.receiver(&receive_id).is_some_and(|claim| claim.approved) => meta_info,
+        Some(meta_info) if meta_info.value.receiver(&receive_id).is_some() => {
+            event!(Level::WARN, "Pending receiver tried to complete ID: {}", id);
+            return (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "code": 403,
+                    "success": false,
+                    "error": errors::AWAITING_APPROVAL,
+                    "message": "Awaiting sender approval"
+                }))
+            );
+        }
+        Some(_) => {
+            event!(Level::WARN, "Wrong Receive ID for download completion of ID: {}", id);
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "code": 400,
+                    "success": false,
+                    "error": errors::WRONG_RECEIVER,
+                    "message": "Wrong Receive ID"
+                }))
+            );
+        }
+        None => {
+            event!(Level::WARN, "ID not found for download completion: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            );
+        }
+    };
+
+    // Inspection may take a scanner round trip, so it runs on a snapshot and
+    // the flag is set afterwards without overwriting concurrent changes
+    if !meta_info.value.done {
+        let file = inspect::FileContext {
+            id: &id,
+            file_name: &meta_info.value.file_name,
+            file_size: meta_info.value.file_size,
+        };
+        if let Err(rejection) = inspect::inspect_file(&file).await {
+            event!(Level::WARN, "Transfer {} rejected by {}: {}", id, rejection.inspector, rejection.reason);
+            signaling::notify_sender(&id, signaling::CONTENT_REJECTED, json!({
+                "inspector": rejection.inspector,
+                "reason": rejection.reason,
+            })).await;
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({
+                    "code": 422,
+                    "success": false,
+                    "error": errors::CONTENT_REJECTED,
+                    "message": "Transfer rejected by content inspection"
+                }))
+            );
+        }
+    }
+
+    let completed = MetaInfo::get_db().modify(&id, |meta| {
+        let newly_done = !meta.done;
+        meta.done = true;
+        let file_size = meta.file_size;
+        // Transfers that never had a block served over HTTP went peer-to-peer
+        let via_relay = meta.receivers.iter().any(|r| r.cursor > 0);
+        let sha256 = (file_size > 0 && meta.digest.offset == file_size).then(|| meta.digest.hex());
+        (newly_done, file_size, via_relay, sha256)
+    }).await;
+
+    let Some((newly_done, file_size, via_relay, sha256)) = completed else {
+        event!(Level::WARN, "Transfer expired before download completion: {}", id);
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            }))
+        );
+    };
+
+    if newly_done {
+        stats::record_completed(&id, file_size, via_relay);
+        audit::record(audit::TRANSFER_COMPLETED, Some(&id), client, json!({
+            "file_size": file_size,
+            "via_relay": via_relay,
+        }));
+    }
+    event!(Level::DEBUG, "Download marked as complete for ID: {}", id);
+    (
+        StatusCode::OK,
+        Json(json!({
+            "code": 200,
+            "success": true,
+            "message": "Download completion marked successfully",
+            // Relay digest of the complete file; null when blocks went peer-to-peer
+            "data": { "sha256": sha256 },
+        }))
+    )
+}
+
+/// Handler for cancelling a transfer
+/// Drops the metadata, buffered blocks and signaling state for the id
+#[instrument(skip(headers))]
+pub async fn cancel(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+
     match MetaInfo::get_db().get(&id).await {
-        Some(mut meta_info) => {
-            meta_info.value.done = true;
-            match MetaInfo::get_db().update(&id, meta_info.value, meta_info.exp).await {
-                Ok(_) => {
-                    event!(Level::DEBUG, "Download marked as complete for ID: {}", id);
-                    (
-                        StatusCode::OK,
-                        Json(json!({
-                            "code": 200,
-                            "success": true,
-                            "message": "Download completion marked successfully"
-                        }))
-                    )
-                },
-                Err(e) => {
-                    event!(Level::ERROR, "Failed to update download completion status: {}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({
-                            "code": 500,
-                            "success": false,
-                            "message": "Internal Server Error"
-                        }))
-                    )
-                }
+        Some(meta_info) => {
+            if let Err(err) = auth::authorize_sender(&id, &headers, &meta_info.value) {
+                return err.into_response();
             }
-        },
+        }
         None => {
-            event!(Level::WARN, "ID not found for download completion: {}", id);
-            (
+            event!(Level::WARN, "ID not found for cancellation: {}", id);
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
+                    "message": "Not Found"
+                }))
+            )
+            .into_response();
+        }
+    }
+
+    if MetaInfo::get_db().remove(&id).await.is_some_and(|meta| !meta.value.done) {
+        stats::record_cancelled(&id);
+    }
+    if SignalState::get_db().remove(&id).await.is_some() {
+        feed::publish(feed::ROOM_CLOSED, Some(&id), json!({ "reason": "cancelled" }));
+    }
+    let removed = FileBlock::get_db().remove_prefix(&format!("{}:", id)).await;
+    event!(Level::DEBUG, "Transfer cancelled for ID: {} ({} buffered blocks dropped)", id, removed);
+    audit::record(audit::TRANSFER_CANCELLED, Some(&id), client, json!({ "dropped_blocks": removed }));
+    events::publish(&id, events::TRANSFER_CANCELLED, json!({}));
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "message": "Transfer cancelled"
+    }))
+    .into_response()
+}
+
+/// Handler for the sender pausing the relay, e.g. to free up its uplink
+/// Receivers get 423 for blocks until the transfer is resumed
+#[instrument(skip(headers))]
+pub async fn pause(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    set_paused(client, &normalize_id(&id), &headers, true).await
+}
+
+/// Handler for the sender resuming a paused relay
+#[instrument(skip(headers))]
+pub async fn resume(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    set_paused(client, &normalize_id(&id), &headers, false).await
+}
+
+async fn set_paused(client: SocketAddr, id: &str, headers: &HeaderMap, paused: bool) -> axum::response::Response {
+    let meta_db = MetaInfo::get_db();
+    match meta_db.get(id).await {
+        Some(meta_info) => {
+            if let Err(err) = auth::authorize_sender(id, headers, &meta_info.value) {
+                return err.into_response();
+            }
+            if meta_info.value.done {
+                return (
+                    StatusCode::CONFLICT,
+                    Json(json!({
+                        "code": 409,
+                        "success": false,
+                        "error": errors::TRANSFER_DONE,
+                        "message": "Transfer already completed"
+                    }))
+                )
+                .into_response();
+            }
+        }
+        None => {
+            event!(Level::WARN, "ID not found for pause/resume: {}", id);
+            return (
                 StatusCode::NOT_FOUND,
                 Json(json!({
                     "code": 404,
                     "success": false,
+                    "error": errors::TRANSFER_NOT_FOUND,
                     "message": "Not Found"
                 }))
             )
+            .into_response();
         }
     }
+
+    // Only an actual change is announced, so repeated clicks stay quiet
+    let changed = meta_db.modify(id, |meta| std::mem::replace(&mut meta.paused, paused) != paused).await;
+    if changed == Some(true) {
+        let (audit_kind, event_kind, signal_type) = if paused {
+            (audit::TRANSFER_PAUSED, events::TRANSFER_PAUSED, signaling::TRANSFER_PAUSED)
+        } else {
+            (audit::TRANSFER_RESUMED, events::TRANSFER_RESUMED, signaling::TRANSFER_RESUMED)
+        };
+        event!(Level::DEBUG, "Transfer {} for ID: {}", if paused { "paused" } else { "resumed" }, id);
+        audit::record(audit_kind, Some(id), client, json!({}));
+        events::publish(id, event_kind, json!({}));
+        signaling::notify_receivers(id, signal_type, json!({})).await;
+    }
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": { "paused": paused }
+    }))
+    .into_response()
 }
 
 /// Handler for serving static assets
-/// Returns CSS, JS, and other static files with appropriate MIME types
+/// Returns CSS, JS, and other static files with appropriate MIME types,
+/// cache headers and precompressed variants; rejected or missing paths get the 404 page
 #[instrument(skip_all)]
-pub async fn get_assets(Path(file): Path<String>) -> impl IntoResponse {
-    match StaticFiles::get(format!("assets/{}", file).as_str()) {
-        Some(f) => {
-            let mime = mime_guess::from_path(&file).first_or_octet_stream();
-            let headers = AppendHeaders([(
-                header::CONTENT_TYPE,
-                mime.as_ref().to_string().parse::<axum::http::HeaderValue>().unwrap(),
-            )]);
-            (headers, Body::from(f.data)).into_response()
+pub async fn get_assets(Path(file): Path<String>, headers: HeaderMap) -> impl IntoResponse {
+    let path = match safe_join("assets", &file) {
+        Some(path) => path,
+        None => {
+            event!(Level::WARN, "Rejected asset path: {}", file);
+            return error_page(StatusCode::NOT_FOUND);
         },
+    };
+    match serve_embedded(&path, &headers) {
+        Some(response) => response,
         None => {
             event!(Level::WARN, "Asset file not found: {}", file);
-            (StatusCode::NOT_FOUND, "File not found").into_response()
+            error_page(StatusCode::NOT_FOUND)
         },
     }
 }
+
+/// Fallback for unknown API paths, so clients get JSON rather than the SPA shell
+#[instrument(skip_all)]
+pub async fn api_not_found(uri: Uri) -> impl IntoResponse {
+    event!(Level::DEBUG, "Unknown API path: {}", uri.path());
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+            "code": 404,
+            "success": false,
+            "error": errors::NOT_FOUND,
+            "message": "Not Found"
+        }))
+    )
+}
+
+/// Panic handler for the API, so clients get a JSON 500 instead of a reset connection
+pub fn api_panic(err: Box<dyn std::any::Any + Send + 'static>) -> axum::response::Response {
+    event!(Level::ERROR, "API handler panicked: {}", panic_message(err.as_ref()));
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+            "code": 500,
+            "success": false,
+            "error": errors::INTERNAL,
+            "message": "Internal Server Error"
+        }))
+    )
+    .into_response()
+}
+
+/// Fallback handler serving the SPA from the embedded `StaticFiles`.
+/// Directory paths resolve to their `index.html`, and extensionless paths
+/// that match nothing fall back to the root `index.html` for client-side routing
+#[instrument(skip_all)]
+pub async fn spa_fallback(uri: Uri, headers: HeaderMap) -> impl IntoResponse {
+    let path = uri.path().trim_matches('/');
+    if path.is_empty() {
+        return serve_embedded("index.html", &headers)
+            .unwrap_or_else(|| error_page(StatusCode::NOT_FOUND));
+    }
+    let path = match safe_join("", path) {
+        Some(path) => path,
+        None => return error_page(StatusCode::NOT_FOUND),
+    };
+
+    if let Some(response) = serve_embedded(&path, &headers) {
+        return response;
+    }
+    if let Some(response) = serve_embedded(&format!("{}/index.html", path), &headers) {
+        return response;
+    }
+    let is_file = path.rsplit('/').next().is_some_and(|name| name.contains('.'));
+    if !is_file && let Some(response) = serve_embedded("index.html", &headers) {
+        return response;
+    }
+
+    event!(Level::DEBUG, "Static file not found: {}", path);
+    error_page(StatusCode::NOT_FOUND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(start: u64, end: u64, total: u64) -> FileInfo {
+        FileInfo { filename: "a.bin".to_string(), start, end, total }
+    }
+
+    fn rejection(info: &FileInfo, known_total: Option<u64>) -> Option<serde_json::Value> {
+        validate_block_info(info, known_total).err().map(|(_, Json(body))| body["error"].clone())
+    }
+
+    #[test]
+    fn test_validate_block_info() {
+        assert_eq!(rejection(&info(0, 0, 1), None), None);
+        assert_eq!(rejection(&info(0, 99, 100), Some(100)), None);
+        for bad in [info(10, 9, 100), info(0, 100, 100), info(0, 0, 0)] {
+            assert_eq!(rejection(&bad, None), Some(json!(errors::BLOCK_RANGE_INVALID)));
+        }
+        assert_eq!(rejection(&info(0, 99, 200), Some(100)), Some(json!(errors::BLOCK_TOTAL_MISMATCH)));
+    }
+}
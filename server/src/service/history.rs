@@ -0,0 +1,67 @@
+use std::time::Instant;
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tracing::{event, instrument, Level};
+
+use crate::{dao::db::MetaInfo, service::{auth, errors, tenant}};
+
+/// Handler listing the caller's transfers that are still alive
+/// The caller is identified by the `X-Sender-Key` it created them with (or its
+/// API key); each entry carries a fresh sender token so a reloaded upload page
+/// or CLI can take control of the transfer again
+#[instrument(skip_all)]
+pub async fn list_my_transfers(headers: HeaderMap) -> impl IntoResponse {
+    let tags = auth::owner_tags(&headers);
+    if tags.is_empty() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+                "code": 401,
+                "success": false,
+                "error": errors::SENDER_KEY_REQUIRED,
+                "message": "Sender key or API key required"
+            }))
+        )
+        .into_response();
+    }
+
+    let now = Instant::now();
+    let mut transfers = MetaInfo::get_db()
+        .store
+        .read()
+        .await
+        .iter()
+        .filter(|(id, entry)| entry.exp > now && tenant::owns(id) && entry.value.owners.iter().any(|owner| tags.contains(owner)))
+        .map(|(id, entry)| {
+            let meta = &entry.value;
+            let expires_in = entry.exp.saturating_duration_since(now).as_secs();
+            (expires_in, json!({
+                "id": tenant::bare(id),
+                "file_name": meta.file_name,
+                "file_size": meta.file_size,
+                "uploaded_bytes": meta.metrics.upload.bytes,
+                "downloaded_bytes": meta.metrics.download.bytes,
+                "receivers": meta.receivers.len(),
+                "done": meta.done,
+                "paused": meta.paused,
+                "expires_in": expires_in,
+                "sender_token": auth::issue_sender_token(id, meta),
+            }))
+        })
+        .collect::<Vec<_>>();
+    // Newest first: every transfer starts with the same TTL
+    transfers.sort_by_key(|(expires_in, _)| std::cmp::Reverse(*expires_in));
+    event!(Level::TRACE, "Listed {} transfers for owner", transfers.len());
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": { "transfers": transfers.into_iter().map(|(_, transfer)| transfer).collect::<Vec<_>>() }
+    }))
+    .into_response()
+}
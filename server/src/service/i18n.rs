@@ -0,0 +1,97 @@
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+
+use crate::service::static_files::StaticFiles;
+
+/// Locales with embedded page variants; the first one is the default and
+/// lives at the unsuffixed `index.html`
+pub const SUPPORTED_LOCALES: &[&str] = &["zh-CN", "en"];
+
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    /// Explicit locale override, e.g. `?lang=en`
+    pub lang: Option<String>,
+}
+
+/// Match a language tag against the supported locales, first exactly and
+/// then by primary subtag (`en-US` -> `en`, `zh` -> `zh-CN`)
+fn match_locale(tag: &str) -> Option<&'static str> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return None;
+    }
+    if let Some(locale) = SUPPORTED_LOCALES.iter().find(|l| l.eq_ignore_ascii_case(tag)) {
+        return Some(locale);
+    }
+    let primary = tag.split(['-', '_']).next().unwrap_or(tag);
+    SUPPORTED_LOCALES
+        .iter()
+        .find(|l| l.split('-').next().is_some_and(|p| p.eq_ignore_ascii_case(primary)))
+        .copied()
+}
+
+/// Pick the page locale from `?lang=` first, then from `Accept-Language`
+/// ordered by quality, falling back to the default locale
+pub fn negotiate_locale(lang: Option<&str>, headers: &HeaderMap) -> &'static str {
+    if let Some(locale) = lang.and_then(match_locale) {
+        return locale;
+    }
+
+    let mut ranges: Vec<(&str, f32)> = headers
+        .get_all(header::ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    // Stable sort keeps header order for equal weights
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .into_iter()
+        .find_map(|(tag, _)| match_locale(tag))
+        .unwrap_or(SUPPORTED_LOCALES[0])
+}
+
+/// Embedded path of the page under `dir` (e.g. `"upload/"`, `""` for the
+/// landing page) for `locale`, falling back to the default variant when
+/// the localized one is not bundled
+pub fn localized_page(dir: &str, locale: &str) -> String {
+    if locale != SUPPORTED_LOCALES[0] {
+        let path = format!("{}index.{}.html", dir, locale);
+        if StaticFiles::get(&path).is_some() {
+            return path;
+        }
+    }
+    format!("{}index.html", dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_negotiate_locale() {
+        assert_eq!(negotiate_locale(None, &HeaderMap::new()), "zh-CN");
+        assert_eq!(negotiate_locale(None, &accept("en-US,en;q=0.9")), "en");
+        assert_eq!(negotiate_locale(None, &accept("fr;q=1, zh;q=0.5, en;q=0.8")), "en");
+        assert_eq!(negotiate_locale(None, &accept("en;q=0, zh-TW")), "zh-CN");
+        assert_eq!(negotiate_locale(Some("EN"), &accept("zh-CN")), "en");
+        assert_eq!(negotiate_locale(Some("xx"), &accept("en")), "en");
+    }
+}
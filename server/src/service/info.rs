@@ -0,0 +1,80 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use serde_json::json;
+use tracing::{event, instrument, Level};
+
+use crate::service::{auth, discovery, handler, portmap, tenant, webrtc};
+
+/// Revision of the block/signaling protocol; bump on incompatible changes
+pub const PROTOCOL_REVISION: u32 = 1;
+
+/// Handler describing this server's version, limits and capabilities
+/// Lets CLI and third-party clients negotiate instead of hard-coding assumptions
+#[instrument]
+pub async fn get_info() -> impl IntoResponse {
+    event!(Level::TRACE, "Server info requested");
+    let (id_min_length, id_max_length) = handler::id_length_bounds();
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "code": 200,
+            "success": true,
+            "data": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+                "protocol": PROTOCOL_REVISION,
+                "max_block_size": handler::max_block_size(),
+                // Blocks the server buffers per transfer before uploads are rejected
+                "window_size": handler::max_blocks_per_file(),
+                "max_file_size": handler::max_total_size(),
+                // Tenant this request was served for, from the path or the API key
+                "tenant": tenant::current(),
+                "id_length": {
+                    "default": handler::default_id_length(),
+                    "min": id_min_length,
+                    "max": id_max_length,
+                },
+                // Router-mapped address reachable from outside the LAN, null until mapped
+                "external": portmap::external_address(),
+                "features": {
+                    "webrtc": true,
+                    "webrtc_relay_fallback": webrtc::turn_relay_enabled(),
+                    "signaling_binary_frames": true,
+                    "receiver_events": true,
+                    "download_links": true,
+                    "delta_upload": true,
+                    "receiver_seek": true,
+                    "pause_resume": true,
+                    "claim_handoff": true,
+                    "transfer_history": true,
+                    "tenants": tenant::enabled(),
+                    "webdav": true,
+                    "lan_discovery": discovery::mdns_enabled(),
+                    "port_mapping": portmap::port_mapping_enabled(),
+                    "api_keys": auth::api_keys_enabled(),
+                    "compression": true,
+                    "e2ee": false,
+                },
+            }
+        })),
+    )
+}
+
+/// Handler exposing deployment feature flags to the bundled SPA
+/// Derived from server config so UI affordances follow the deployment without rebuilding assets
+#[instrument]
+pub async fn get_flags() -> impl IntoResponse {
+    event!(Level::TRACE, "Feature flags requested");
+    (
+        StatusCode::OK,
+        Json(json!({
+            "code": 200,
+            "success": true,
+            "data": {
+                "relay_fallback": webrtc::turn_relay_enabled(),
+                "api_key_required": auth::api_keys_enabled(),
+                "max_file_size": handler::max_total_size(),
+            }
+        })),
+    )
+}
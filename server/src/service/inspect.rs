@@ -0,0 +1,217 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use axum::{body::Bytes, http::StatusCode, Json};
+use lazy_static::lazy_static;
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tracing::{event, Level};
+
+use crate::service::errors;
+use crate::utils::env::read_env_string;
+
+lazy_static! {
+    static ref INSPECTORS: Vec<Box<dyn BlockInspector>> = configured_inspectors();
+}
+
+/// Chunk size of the clamd INSTREAM protocol
+const CLAMD_CHUNK_BYTES: usize = 64 * 1024;
+/// Upper bound for one clamd round trip before the block is failed closed
+const CLAMD_TIMEOUT_SECS: u64 = 10;
+
+/// Why an inspector refused content; surfaced to the sender as-is
+#[derive(Debug)]
+pub struct Rejection {
+    pub inspector: &'static str,
+    pub reason: String,
+}
+
+pub type InspectFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Rejection>> + Send + 'a>>;
+
+/// A block about to be buffered for relay
+pub struct BlockContext<'a> {
+    pub id: &'a str,
+    pub file_name: &'a str,
+    pub file_size: u64,
+    pub start: u64,
+    pub end: u64,
+    pub data: &'a Bytes,
+}
+
+/// A transfer the receiver just marked complete
+pub struct FileContext<'a> {
+    pub id: &'a str,
+    pub file_name: &'a str,
+    pub file_size: u64,
+}
+
+/// Deployment hook run on relayed content. Blocks are inspected one at a
+/// time before they are buffered, so signatures spanning block boundaries are
+/// only caught by `inspect_file` implementations that keep their own state
+pub trait BlockInspector: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn inspect_block<'a>(&'a self, block: &'a BlockContext<'a>) -> InspectFuture<'a>;
+
+    fn inspect_file<'a>(&'a self, _file: &'a FileContext<'a>) -> InspectFuture<'a> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Rejects files by extension (INSPECT_BLOCKED_EXTENSIONS, comma-separated)
+struct ExtensionPolicy {
+    blocked: Vec<String>,
+}
+
+impl ExtensionPolicy {
+    fn check(&self, file_name: &str) -> Result<(), Rejection> {
+        let extension = file_name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+        match extension {
+            Some(ext) if self.blocked.contains(&ext) => Err(Rejection {
+                inspector: self.name(),
+                reason: format!("Files of type .{} are not allowed", ext),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl BlockInspector for ExtensionPolicy {
+    fn name(&self) -> &'static str {
+        "extension-policy"
+    }
+
+    fn inspect_block<'a>(&'a self, block: &'a BlockContext<'a>) -> InspectFuture<'a> {
+        Box::pin(async move { self.check(block.file_name) })
+    }
+
+    fn inspect_file<'a>(&'a self, file: &'a FileContext<'a>) -> InspectFuture<'a> {
+        Box::pin(async move { self.check(file.file_name) })
+    }
+}
+
+/// Scans each block with a clamd daemon (CLAMD_ADDR, e.g. `127.0.0.1:3310`)
+struct ClamdScanner {
+    addr: String,
+}
+
+impl ClamdScanner {
+    async fn scan(&self, data: &[u8]) -> std::io::Result<String> {
+        let mut stream = TcpStream::connect(&self.addr).await?;
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in data.chunks(CLAMD_CHUNK_BYTES) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await?;
+
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+        Ok(String::from_utf8_lossy(&reply).trim_end_matches('\0').trim().to_string())
+    }
+}
+
+impl BlockInspector for ClamdScanner {
+    fn name(&self) -> &'static str {
+        "clamd"
+    }
+
+    fn inspect_block<'a>(&'a self, block: &'a BlockContext<'a>) -> InspectFuture<'a> {
+        Box::pin(async move {
+            let scanned = tokio::time::timeout(Duration::from_secs(CLAMD_TIMEOUT_SECS), self.scan(block.data)).await;
+            // Fail closed: a block that could not be scanned is not relayed
+            let reply = match scanned {
+                Ok(Ok(reply)) => reply,
+                Ok(Err(err)) => {
+                    event!(Level::ERROR, "clamd scan failed for ID {}: {}", block.id, err);
+                    return Err(Rejection { inspector: self.name(), reason: "Content scanner unavailable".to_string() });
+                }
+                Err(_) => {
+                    event!(Level::ERROR, "clamd scan timed out for ID {}", block.id);
+                    return Err(Rejection { inspector: self.name(), reason: "Content scanner unavailable".to_string() });
+                }
+            };
+            match reply.strip_suffix("FOUND") {
+                None if reply.ends_with("OK") => Ok(()),
+                Some(found) => Err(Rejection {
+                    inspector: self.name(),
+                    reason: format!("Malware detected: {}", found.trim_start_matches("stream:").trim()),
+                }),
+                None => {
+                    event!(Level::ERROR, "Unexpected clamd reply for ID {}: {}", block.id, reply);
+                    Err(Rejection { inspector: self.name(), reason: "Content scanner error".to_string() })
+                }
+            }
+        })
+    }
+}
+
+fn configured_inspectors() -> Vec<Box<dyn BlockInspector>> {
+    let mut inspectors: Vec<Box<dyn BlockInspector>> = Vec::new();
+    if let Some(raw) = read_env_string("INSPECT_BLOCKED_EXTENSIONS") {
+        let blocked = raw
+            .split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect::<Vec<_>>();
+        if !blocked.is_empty() {
+            inspectors.push(Box::new(ExtensionPolicy { blocked }));
+        }
+    }
+    if let Some(addr) = read_env_string("CLAMD_ADDR") {
+        inspectors.push(Box::new(ClamdScanner { addr }));
+    }
+    for inspector in &inspectors {
+        event!(Level::INFO, "Content inspector enabled: {}", inspector.name());
+    }
+    inspectors
+}
+
+/// Run every configured inspector over a block, stopping at the first rejection
+pub async fn inspect_block(block: &BlockContext<'_>) -> Result<(), Rejection> {
+    event!(Level::TRACE, "Inspecting block {}-{} of {} for ID {}", block.start, block.end, block.file_size, block.id);
+    for inspector in INSPECTORS.iter() {
+        inspector.inspect_block(block).await?;
+    }
+    Ok(())
+}
+
+/// Run every configured inspector's completion hook
+pub async fn inspect_file(file: &FileContext<'_>) -> Result<(), Rejection> {
+    event!(Level::TRACE, "Inspecting completed file '{}' ({} bytes) for ID {}", file.file_name, file.file_size, file.id);
+    for inspector in INSPECTORS.iter() {
+        inspector.inspect_file(file).await?;
+    }
+    Ok(())
+}
+
+impl Rejection {
+    /// Structured 422 response naming the inspector that refused the content
+    pub fn into_error(self) -> (StatusCode, Json<serde_json::Value>) {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({
+            "code": 422,
+            "success": false,
+            "error": errors::CONTENT_REJECTED,
+            "message": self.reason,
+            "inspector": self.inspector,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_extension_policy() {
+        let policy = ExtensionPolicy { blocked: vec!["exe".to_string()] };
+        let data = Bytes::from_static(b"MZ");
+        let block = |file_name| BlockContext { id: "abcde", file_name, file_size: 2, start: 0, end: 1, data: &data };
+
+        assert!(policy.inspect_block(&block("setup.EXE")).await.is_err());
+        assert!(policy.inspect_block(&block("notes.txt")).await.is_ok());
+        assert!(policy.inspect_block(&block("README")).await.is_ok());
+    }
+}
@@ -1,2 +1,25 @@
+pub mod abuse;
+pub mod access_log;
+pub mod admin;
+pub mod audit;
+pub mod auth;
+pub mod delta;
+pub mod discovery;
+pub mod entity;
+pub mod errors;
+pub mod events;
+pub mod extract;
+pub mod feed;
 pub mod handler;
+pub mod history;
+pub mod i18n;
+pub mod info;
+pub mod inspect;
+pub mod portmap;
+pub mod proxy;
+pub mod signaling;
 pub mod static_files;
+pub mod stats;
+pub mod tenant;
+pub mod webdav;
+pub mod webrtc;
\ No newline at end of file
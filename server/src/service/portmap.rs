@@ -0,0 +1,228 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use igd_next::{aio::tokio::search_gateway, PortMappingProtocol, SearchOptions};
+use lazy_static::lazy_static;
+use serde_json::json;
+use tokio::net::UdpSocket;
+use tracing::{event, Level};
+
+use crate::utils::env::{read_env_string, read_env_u64};
+
+/// NAT-PMP server port on the gateway (RFC 6886)
+const NAT_PMP_PORT: u16 = 5351;
+/// Attempts per NAT-PMP request; the wait doubles each time starting at 250ms
+const NAT_PMP_ATTEMPTS: u32 = 4;
+/// Pause before retrying after the router refused or did not answer
+const RETRY_SECS: u64 = 60;
+
+lazy_static! {
+    static ref PORT_MAPPING: Option<Method> = read_method();
+    static ref PORT_MAPPING_LEASE_SECS: u64 = read_env_u64("PORT_MAPPING_LEASE_SECS", 60 * 60).max(120);
+    static ref PORT_MAPPING_EXTERNAL_PORT: u64 = read_env_u64("PORT_MAPPING_EXTERNAL_PORT", 0);
+    static ref MAPPING: Mutex<Option<Mapping>> = Mutex::new(None);
+}
+
+/// Which protocol to ask the router with
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Method {
+    NatPmp,
+    Upnp,
+    /// NAT-PMP first, UPnP when the router does not speak it
+    Auto,
+}
+
+/// Port mapping protocol (`natpmp`, `upnp` or `auto`, configurable via PORT_MAPPING; unset disables it)
+fn read_method() -> Option<Method> {
+    match read_env_string("PORT_MAPPING")?.to_ascii_lowercase().as_str() {
+        "natpmp" | "nat-pmp" => Some(Method::NatPmp),
+        "upnp" => Some(Method::Upnp),
+        "auto" | "true" | "1" => Some(Method::Auto),
+        other => {
+            event!(Level::WARN, "PORT_MAPPING is invalid (value: '{}'), port mapping disabled", other);
+            None
+        }
+    }
+}
+
+/// Requested lease; renewed at half-time (default 1h, configurable via PORT_MAPPING_LEASE_SECS)
+fn lease_secs() -> u32 {
+    (*PORT_MAPPING_LEASE_SECS).min(u32::MAX as u64) as u32
+}
+/// External port to ask for (default the local port, configurable via PORT_MAPPING_EXTERNAL_PORT)
+fn external_port(local_port: u16) -> u16 {
+    u16::try_from(*PORT_MAPPING_EXTERNAL_PORT).ok().filter(|port| *port > 0).unwrap_or(local_port)
+}
+
+/// A mapping the router granted
+#[derive(Clone, Debug)]
+struct Mapping {
+    via: &'static str,
+    external_ip: IpAddr,
+    external_port: u16,
+    lease_secs: u32,
+}
+
+/// Whether port mapping was requested at all
+pub fn port_mapping_enabled() -> bool {
+    PORT_MAPPING.is_some()
+}
+
+/// Address reachable from outside the LAN, for `/api/info`; null until mapped
+pub fn external_address() -> serde_json::Value {
+    match MAPPING.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        Some(mapping) => {
+            let addr = SocketAddr::new(mapping.external_ip, mapping.external_port);
+            json!({
+                "address": addr.to_string(),
+                "url": format!("http://{}", addr),
+                "via": mapping.via,
+                "lease_secs": mapping.lease_secs,
+            })
+        }
+        None => serde_json::Value::Null,
+    }
+}
+
+/// Keep a mapping of `local_port` alive for as long as the server runs
+pub fn start(local_port: u16) {
+    let Some(method) = *PORT_MAPPING else {
+        return;
+    };
+    tokio::spawn(async move {
+        loop {
+            let wait = match map_port(method, local_port).await {
+                Ok(mapping) => {
+                    event!(Level::INFO, "Mapped external {}:{} via {}", mapping.external_ip, mapping.external_port, mapping.via);
+                    let renew = Duration::from_secs((mapping.lease_secs / 2).max(60) as u64);
+                    *MAPPING.lock().unwrap_or_else(PoisonError::into_inner) = Some(mapping);
+                    renew
+                }
+                Err(err) => {
+                    event!(Level::WARN, "Port mapping failed: {}", err);
+                    *MAPPING.lock().unwrap_or_else(PoisonError::into_inner) = None;
+                    Duration::from_secs(RETRY_SECS)
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    });
+}
+
+async fn map_port(method: Method, local_port: u16) -> Result<Mapping, String> {
+    match method {
+        Method::NatPmp => nat_pmp_map(local_port).await,
+        Method::Upnp => upnp_map(local_port).await,
+        Method::Auto => match nat_pmp_map(local_port).await {
+            Ok(mapping) => Ok(mapping),
+            Err(err) => {
+                event!(Level::DEBUG, "NAT-PMP unavailable ({}), trying UPnP", err);
+                upnp_map(local_port).await
+            }
+        },
+    }
+}
+
+/// Default IPv4 gateway from the kernel routing table (NAT_PMP_GATEWAY overrides it)
+fn default_gateway() -> Option<Ipv4Addr> {
+    if let Some(raw) = read_env_string("NAT_PMP_GATEWAY") {
+        return raw.parse().ok();
+    }
+    parse_route_table(&std::fs::read_to_string("/proc/net/route").ok()?)
+}
+
+/// Gateway of the `00000000` destination in `/proc/net/route`, stored little-endian hex
+fn parse_route_table(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        match fields.as_slice() {
+            [_, "00000000", gateway, ..] => u32::from_str_radix(gateway, 16).ok().map(|raw| Ipv4Addr::from(raw.to_le_bytes())),
+            _ => None,
+        }
+    })
+}
+
+/// Send a NAT-PMP request and wait for the response with the matching opcode
+async fn nat_pmp_request(socket: &UdpSocket, request: &[u8]) -> Result<Vec<u8>, String> {
+    let mut wait = Duration::from_millis(250);
+    let mut buf = [0u8; 16];
+    for _ in 0..NAT_PMP_ATTEMPTS {
+        socket.send(request).await.map_err(|err| err.to_string())?;
+        if let Ok(received) = tokio::time::timeout(wait, socket.recv(&mut buf)).await {
+            let len = received.map_err(|err| err.to_string())?;
+            let response = &buf[..len];
+            if len >= 4 && response[1] == request[1] | 0x80 {
+                return match u16::from_be_bytes([response[2], response[3]]) {
+                    0 => Ok(response.to_vec()),
+                    code => Err(format!("NAT-PMP result code {}", code)),
+                };
+            }
+        }
+        wait *= 2;
+    }
+    Err("no NAT-PMP response from gateway".to_string())
+}
+
+async fn nat_pmp_map(local_port: u16) -> Result<Mapping, String> {
+    let gateway = default_gateway().ok_or("no default gateway")?;
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.map_err(|err| err.to_string())?;
+    socket.connect(SocketAddrV4::new(gateway, NAT_PMP_PORT)).await.map_err(|err| err.to_string())?;
+
+    // Opcode 0: external address
+    let response = nat_pmp_request(&socket, &[0, 0]).await?;
+    let external_ip = match response.get(8..12) {
+        Some(ip) => Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]),
+        None => return Err("short NAT-PMP address response".to_string()),
+    };
+
+    // Opcode 2: map TCP
+    let mut request = vec![0, 2, 0, 0];
+    request.extend_from_slice(&local_port.to_be_bytes());
+    request.extend_from_slice(&external_port(local_port).to_be_bytes());
+    request.extend_from_slice(&lease_secs().to_be_bytes());
+    let response = nat_pmp_request(&socket, &request).await?;
+    let (Some(port), Some(lease)) = (response.get(10..12), response.get(12..16)) else {
+        return Err("short NAT-PMP mapping response".to_string());
+    };
+
+    Ok(Mapping {
+        via: "nat-pmp",
+        external_ip: IpAddr::V4(external_ip),
+        external_port: u16::from_be_bytes([port[0], port[1]]),
+        lease_secs: u32::from_be_bytes([lease[0], lease[1], lease[2], lease[3]]),
+    })
+}
+
+async fn upnp_map(local_port: u16) -> Result<Mapping, String> {
+    let gateway = search_gateway(SearchOptions::default()).await.map_err(|err| err.to_string())?;
+    // The router forwards to whichever local address routes towards it
+    let probe = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.map_err(|err| err.to_string())?;
+    probe.connect(gateway.addr).await.map_err(|err| err.to_string())?;
+    let local_ip = probe.local_addr().map_err(|err| err.to_string())?.ip();
+
+    let external_ip = gateway.get_external_ip().await.map_err(|err| err.to_string())?;
+    let external_port = external_port(local_port);
+    gateway
+        .add_port(PortMappingProtocol::TCP, external_port, SocketAddr::new(local_ip, local_port), lease_secs(), "FileFlow")
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(Mapping { via: "upnp", external_ip, external_port, lease_secs: lease_secs() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_route_table() {
+        let table = "Iface\tDestination\tGateway \tFlags\n\
+                     eth0\t000200C0\t00000000\t0001\n\
+                     eth0\t00000000\t010200C0\t0003\n";
+        assert_eq!(parse_route_table(table), Some(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(parse_route_table("Iface\tDestination\tGateway\n"), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use lazy_static::lazy_static;
+use tracing::{event, Level};
+
+use crate::utils::env::read_env_string;
+
+lazy_static! {
+    static ref TRUSTED_PROXIES: Vec<IpNet> = read_trusted_proxies();
+}
+
+/// An address block such as `10.0.0.0/8`; a bare address is a single host
+#[derive(Debug, PartialEq)]
+struct IpNet {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl IpNet {
+    fn parse(raw: &str) -> Option<Self> {
+        let (addr, prefix) = match raw.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u32>().ok()?)),
+            None => (raw.parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(IpNet { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Peers allowed to report the client address (comma-separated addresses or
+/// CIDR blocks, configurable via TRUSTED_PROXIES; unset trusts nobody)
+fn read_trusted_proxies() -> Vec<IpNet> {
+    let Some(raw) = read_env_string("TRUSTED_PROXIES") else {
+        return Vec::new();
+    };
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let net = IpNet::parse(entry);
+            if net.is_none() {
+                event!(Level::WARN, "Ignoring invalid TRUSTED_PROXIES entry '{}'", entry);
+            }
+            net
+        })
+        .collect()
+}
+
+fn is_trusted(proxies: &[IpNet], ip: IpAddr) -> bool {
+    proxies.iter().any(|net| net.contains(ip))
+}
+
+/// Address of one hop: `203.0.113.7`, `"[2001:db8::1]:4711"` or `198.51.100.2:80`
+fn parse_node(raw: &str) -> Option<IpAddr> {
+    let node = raw.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Hops listed by the proxies, client first. `Forwarded` (RFC 7239) wins over
+/// `X-Forwarded-For`; an unparseable hop (e.g. `for=unknown`) cuts the chain there
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| headers.get_all(name).iter().filter_map(|v| v.to_str().ok()).flat_map(|v| v.split(','));
+
+    let forwarded = values(header::FORWARDED.as_str())
+        .map(|element| {
+            element
+                .split(';')
+                .find_map(|pair| pair.trim().split_once('=').filter(|(key, _)| key.eq_ignore_ascii_case("for")))
+                .and_then(|(_, node)| parse_node(node))
+        })
+        .collect::<Vec<_>>();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+    values("x-forwarded-for").map(parse_node).collect()
+}
+
+/// Walk the chain from the nearest hop outwards, skipping trusted proxies;
+/// the first untrusted hop is the client
+fn resolve_ip(proxies: &[IpNet], peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+    if !is_trusted(proxies, peer) {
+        return peer;
+    }
+    let mut client = peer;
+    for hop in forwarded_chain(headers).into_iter().rev() {
+        match hop {
+            Some(ip) => {
+                client = ip;
+                if !is_trusted(proxies, ip) {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+    client
+}
+
+/// Middleware replacing the peer address with the client address reported
+/// by trusted proxies, so abuse limits and audit logs see real clients
+pub async fn resolve_client(mut request: Request, next: Next) -> Response {
+    let proxies = TRUSTED_PROXIES.as_slice();
+    if !proxies.is_empty()
+        && let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied()
+    {
+        let ip = resolve_ip(proxies, peer.ip(), request.headers());
+        if ip != peer.ip() {
+            event!(Level::TRACE, "Client {} forwarded by {}", ip, peer);
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::new(ip, 0)));
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn ip(raw: &str) -> IpAddr {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_ip_skips_trusted_hops() {
+        let proxies = vec![IpNet::parse("127.0.0.1").unwrap(), IpNet::parse("10.0.0.0/8").unwrap()];
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("1.1.1.1, 203.0.113.9, 10.1.2.3"));
+
+        assert_eq!(resolve_ip(&proxies, ip("127.0.0.1"), &headers), ip("203.0.113.9"));
+        // Untrusted peers cannot spoof their address
+        assert_eq!(resolve_ip(&proxies, ip("198.51.100.1"), &headers), ip("198.51.100.1"));
+
+        headers.insert(header::FORWARDED, HeaderValue::from_static("for=\"[2001:db8::1]:4711\";proto=https"));
+        assert_eq!(resolve_ip(&proxies, ip("::ffff:127.0.0.1"), &headers), ip("2001:db8::1"));
+    }
+}
@@ -0,0 +1,928 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, Bytes}, extract::{Path, Query}, http::{header, HeaderMap, StatusCode}, response::{AppendHeaders, IntoResponse, Response}, Json
+};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{event, instrument, Level};
+
+use crate::{
+    dao::db::{MetaInfo, SignalFrame, SignalMessage, SignalPeer, SignalState},
+    service::{auth, errors, events, feed, handler::{normalize_id, META_TTL_SECS, RECEIVER_TOKEN_SCOPE}},
+    utils::{env::{read_env_u64, read_env_usize}, time::unix_now, token},
+};
+
+lazy_static! {
+    static ref SIGNAL_MAX_RECEIVERS: usize = read_env_usize("SIGNAL_MAX_RECEIVERS", 4);
+    static ref SIGNAL_RESUME_GRACE_SECS: u64 = read_env_u64("SIGNAL_RESUME_GRACE_SECS", 60);
+    static ref SIGNAL_IDLE_TIMEOUT_SECS: u64 = read_env_u64("SIGNAL_IDLE_TIMEOUT_SECS", 15);
+    static ref SIGNAL_MAX_PAYLOAD_BYTES: usize = read_env_usize("SIGNAL_MAX_PAYLOAD_BYTES", 64 * 1024);
+    static ref SIGNAL_MAX_FRAME_BYTES: usize = read_env_usize("SIGNAL_MAX_FRAME_BYTES", 64 * 1024);
+    static ref SIGNAL_CHAT_BUFFER_SECS: u64 = read_env_u64("SIGNAL_CHAT_BUFFER_SECS", 5 * 60);
+}
+
+/// Token scope for signaling sessions
+const SESSION_TOKEN_SCOPE: &str = "signal-session";
+/// Origin of messages generated by the server itself
+const SERVER_ROLE: &str = "server";
+/// Peer id used to address and describe the (single) sender
+const SENDER_PEER_ID: &str = "sender";
+/// Presence event types
+const PEER_JOINED: &str = "peer-joined";
+const PEER_LEFT: &str = "peer-left";
+/// Prompt asking the sender to approve a pending receiver claim
+pub const APPROVAL_REQUEST: &str = "approval-request";
+/// Instruction for the sender to push only the ranges a receiver is missing
+pub const RESEND_RANGES: &str = "resend-ranges";
+/// Server-originated notice that a receiver resumes at a block offset
+pub const RECEIVER_SEEK: &str = "receiver-seek";
+/// Notice that a receiver moved its claim to another device
+pub const RECEIVER_HANDOFF: &str = "receiver-handoff";
+/// Notices to receivers that the sender paused or resumed the relay
+pub const TRANSFER_PAUSED: &str = "transfer-paused";
+pub const TRANSFER_RESUMED: &str = "transfer-resumed";
+/// Notice to the sender that an inspector refused the completed file
+pub const CONTENT_REJECTED: &str = "content-rejected";
+
+/// TTL for signaling data (seconds)
+const SIGNAL_TTL_SECS: u64 = 60 * 60;
+/// Number of messages retained per room
+const SIGNAL_MAX_MESSAGES: usize = 200;
+/// Longest accepted chat note (characters)
+const CHAT_MAX_CHARS: usize = 500;
+/// Number of binary frames retained per room
+const SIGNAL_MAX_FRAMES: usize = 32;
+
+/// Maximum number of receivers per signaling room (default 4, configurable via SIGNAL_MAX_RECEIVERS)
+fn max_receivers() -> usize {
+    *SIGNAL_MAX_RECEIVERS
+}
+/// How long a silent receiver keeps its slot and can resume (default 60s, configurable via SIGNAL_RESUME_GRACE_SECS)
+fn resume_grace() -> Duration {
+    Duration::from_secs(*SIGNAL_RESUME_GRACE_SECS)
+}
+
+/// How long a peer may stay silent before it is considered disconnected (default 15s, configurable via SIGNAL_IDLE_TIMEOUT_SECS)
+fn idle_timeout() -> Duration {
+    Duration::from_secs(*SIGNAL_IDLE_TIMEOUT_SECS)
+}
+
+pub fn is_connected(last_seen: Instant, now: Instant) -> bool {
+    now.duration_since(last_seen) < idle_timeout()
+}
+
+/// Connection state of both sides of a room as reported to clients
+fn presence(state: &SignalState, now: Instant) -> serde_json::Value {
+    json!({
+        "sender_connected": state.sender_last_seen.is_some_and(|seen| is_connected(seen, now)),
+        "receivers": state.receivers.iter().map(|peer| json!({
+            "id": peer.id,
+            "connected": is_connected(peer.last_seen, now),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Append a message to the room, trimming the oldest beyond the retention limit
+fn push_message(
+    state: &mut SignalState,
+    from: &str,
+    msg_type: &str,
+    data: serde_json::Value,
+    rid: Option<String>,
+    to: Option<String>,
+) -> u64 {
+    state.seq = state.seq.saturating_add(1);
+    let seq = state.seq;
+    let sent_at = unix_now();
+    state.messages.push(SignalMessage {
+        seq,
+        from: from.to_string(),
+        msg_type: msg_type.to_string(),
+        data,
+        rid,
+        to,
+        sent_at,
+    });
+
+    // Chat notes are only buffered briefly for an absent peer
+    let chat_cutoff = sent_at.saturating_sub(chat_buffer_secs());
+    state.messages.retain(|msg| msg.msg_type != "chat" || msg.sent_at >= chat_cutoff);
+
+    if state.messages.len() > SIGNAL_MAX_MESSAGES {
+        let drain_len = state.messages.len() - SIGNAL_MAX_MESSAGES;
+        state.messages.drain(0..drain_len);
+    }
+    seq
+}
+
+/// Push a server-originated message to the sender of a room, opening the room if needed
+pub async fn notify_sender(id: &str, msg_type: &str, data: serde_json::Value) {
+    let signal_db = SignalState::get_db();
+    let _ = signal_db.insert_if_absent(id, SignalState::new(), SIGNAL_TTL_SECS).await;
+    let notified = signal_db.modify(id, |state| {
+        push_message(state, SERVER_ROLE, msg_type, data, None, Some(SENDER_PEER_ID.to_string()));
+    }).await;
+    if notified.is_none() {
+        event!(Level::WARN, "Failed to notify sender for ID {}: room expired", id);
+        return;
+    }
+    signal_db.touch(id, SIGNAL_TTL_SECS).await;
+}
+
+/// Broadcast a server-originated message to the receivers of an open room
+pub async fn notify_receivers(id: &str, msg_type: &str, data: serde_json::Value) {
+    // Tagged with the sender's peer id so the sender does not see it echoed
+    SignalState::get_db().modify(id, |state| {
+        push_message(state, SERVER_ROLE, msg_type, data, Some(SENDER_PEER_ID.to_string()), None);
+    }).await;
+}
+
+/// Notify the other side of the room that a peer joined or left. Events about a
+/// receiver go to the sender; events about the sender are broadcast to receivers
+fn push_presence(id: &str, state: &mut SignalState, msg_type: &str, role: &str, peer: &str) {
+    let room_event = if msg_type == PEER_JOINED { feed::ROOM_JOINED } else { feed::ROOM_LEFT };
+    feed::publish(room_event, Some(id), json!({ "role": role, "peer": peer }));
+    let to = if role == "receiver" { Some(SENDER_PEER_ID.to_string()) } else { None };
+    push_message(
+        state,
+        SERVER_ROLE,
+        msg_type,
+        json!({ "role": role, "peer": peer }),
+        Some(peer.to_string()),
+        to,
+    );
+}
+
+/// Record that the sender is alive, announcing it when it (re)appears;
+/// returns true when it just (re)appeared
+fn mark_sender_seen(id: &str, state: &mut SignalState, now: Instant) -> bool {
+    let appeared = state.sender_last_seen.is_none();
+    if appeared {
+        state.sender_joined_at = Some(now);
+        push_presence(id, state, PEER_JOINED, "sender", SENDER_PEER_ID);
+    }
+    state.sender_last_seen = Some(now);
+    appeared
+}
+
+/// Drop peers that have been silent for longer than the resume grace window
+fn prune_peers(id: &str, state: &mut SignalState, now: Instant) {
+    let (kept, evicted): (Vec<_>, Vec<_>) = std::mem::take(&mut state.receivers)
+        .into_iter()
+        .partition(|peer| now.duration_since(peer.last_seen) < resume_grace());
+    state.receivers = kept;
+    for peer in evicted {
+        push_presence(id, state, PEER_LEFT, "receiver", &peer.id);
+    }
+
+    if state.sender_last_seen.is_some_and(|seen| now.duration_since(seen) >= resume_grace()) {
+        state.sender_last_seen = None;
+        push_presence(id, state, PEER_LEFT, "sender", SENDER_PEER_ID);
+    }
+}
+
+/// Maximum serialized size of a message's `data` (default 64KB, configurable via SIGNAL_MAX_PAYLOAD_BYTES)
+fn max_payload_bytes() -> usize {
+    *SIGNAL_MAX_PAYLOAD_BYTES
+}
+
+/// Maximum size of a relayed binary frame (default 64KB, configurable via SIGNAL_MAX_FRAME_BYTES)
+fn max_frame_bytes() -> usize {
+    *SIGNAL_MAX_FRAME_BYTES
+}
+
+/// How long chat notes are kept for a peer that has not picked them up (default 5min, configurable via SIGNAL_CHAT_BUFFER_SECS)
+fn chat_buffer_secs() -> u64 {
+    *SIGNAL_CHAT_BUFFER_SECS
+}
+
+/// Message types relayed between peers; application-defined types use an `x-` prefix
+#[derive(Debug, PartialEq)]
+enum SignalKind {
+    Offer,
+    Answer,
+    Candidate,
+    Ready,
+    Fallback,
+    Chat,
+    Custom(String),
+}
+
+fn signal_error(error: &str, message: &str, details: serde_json::Value) -> (StatusCode, Json<serde_json::Value>) {
+    (StatusCode::BAD_REQUEST, Json(json!({
+        "code": 400,
+        "success": false,
+        "error": error,
+        "message": message,
+        "details": details,
+    })))
+}
+
+impl SignalKind {
+    /// Validate the type and the shape and size of its payload before relaying
+    fn parse(msg_type: &str, data: &serde_json::Value) -> Result<Self, (StatusCode, Json<serde_json::Value>)> {
+        let size = serde_json::to_vec(data).map(|raw| raw.len()).unwrap_or(usize::MAX);
+        if size > max_payload_bytes() {
+            return Err(signal_error(errors::SIGNAL_TOO_LARGE, "Signal payload too large", json!({
+                "size": size,
+                "limit": max_payload_bytes(),
+            })));
+        }
+
+        let kind = match msg_type {
+            "offer" => SignalKind::Offer,
+            "answer" => SignalKind::Answer,
+            "candidate" => SignalKind::Candidate,
+            "ready" => SignalKind::Ready,
+            "fallback" => SignalKind::Fallback,
+            "chat" => SignalKind::Chat,
+            custom if custom.len() > 2 && custom.len() <= 64 && custom.starts_with("x-") => SignalKind::Custom(custom.to_string()),
+            other => {
+                return Err(signal_error(errors::SIGNAL_UNSUPPORTED, "Unsupported signal type", json!({ "type": other })));
+            }
+        };
+
+        let valid = match kind {
+            // RTCSessionDescriptionInit
+            SignalKind::Offer | SignalKind::Answer => {
+                data.get("sdp").is_some_and(|sdp| sdp.is_string())
+                    && data.get("type").and_then(|t| t.as_str()) == Some(kind.as_str())
+            }
+            // RTCIceCandidateInit
+            SignalKind::Candidate => data.get("candidate").is_some_and(|c| c.is_string()),
+            SignalKind::Chat => data
+                .get("text")
+                .and_then(|text| text.as_str())
+                .is_some_and(|text| !text.trim().is_empty() && text.chars().count() <= CHAT_MAX_CHARS),
+            _ => true,
+        };
+
+        if !valid {
+            return Err(signal_error(errors::SIGNAL_MALFORMED, "Malformed signal payload", json!({ "type": kind.as_str() })));
+        }
+        Ok(kind)
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            SignalKind::Offer => "offer",
+            SignalKind::Answer => "answer",
+            SignalKind::Candidate => "candidate",
+            SignalKind::Ready => "ready",
+            SignalKind::Fallback => "fallback",
+            SignalKind::Chat => "chat",
+            SignalKind::Custom(custom) => custom,
+        }
+    }
+}
+
+/// Whether the session token was issued to this receiver in this room
+fn session_matches(id: &str, rid: &str, session: Option<&str>) -> bool {
+    session.and_then(|session| token::open(SESSION_TOKEN_SCOPE, id, session)) == Some(rid)
+}
+
+fn invalid_role() -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+            "code": 400,
+            "success": false,
+            "error": errors::INVALID_ROLE,
+            "message": "Invalid role"
+        })),
+    )
+        .into_response()
+}
+
+/// Require the transfer's sender token from anyone acting as the sender, so a
+/// third party who learns the id cannot pre-register and intercept the exchange
+async fn authorize_sender_role(id: &str, headers: &HeaderMap) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match MetaInfo::get_db().get(id).await {
+        Some(meta_info) => auth::authorize_sender(id, headers, &meta_info.value),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::TRANSFER_NOT_FOUND,
+                "message": "Not Found"
+            })),
+        )),
+    }
+}
+
+/// Reject a sender without its token, or a receiver whose session does not
+/// belong to the claimed peer id; a receiver without a peer id must hold a
+/// claim on the transfer instead
+async fn authorize_poll(
+    id: &str,
+    role: &str,
+    peer: Option<&str>,
+    query: &SignalQuery,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if role == "sender" {
+        return authorize_sender_role(id, headers).await;
+    }
+    let authorized = match peer {
+        Some(peer) => session_matches(id, peer, query.session.as_deref()),
+        None => holds_claim(id, query.token.as_deref()).await,
+    };
+    if authorized {
+        return Ok(());
+    }
+    Err((
+        StatusCode::FORBIDDEN,
+        Json(json!({
+            "code": 403,
+            "success": false,
+            "error": errors::INVALID_SESSION,
+            "message": "Invalid signaling session"
+        })),
+    ))
+}
+
+/// Whether a receiver token belongs to a current, sender-approved claim on the transfer
+async fn holds_claim(id: &str, claim: Option<&str>) -> bool {
+    let Some(rid) = claim.and_then(|claim| token::open(RECEIVER_TOKEN_SCOPE, id, claim)) else {
+        return false;
+    };
+    MetaInfo::get_db()
+        .get(id)
+        .await
+        .is_some_and(|meta_info| meta_info.value.receiver(rid).is_some_and(|claim| claim.approved))
+}
+
+/// Refresh the last-seen time of the polling peer, keeping its slot alive
+async fn touch_peer(id: &str, role: &str, peer: Option<&str>, now: Instant) {
+    let signal_db = SignalState::get_db();
+    let touched = signal_db.modify(id, |state| {
+        prune_peers(id, state, now);
+        let appeared = if role == "sender" {
+            mark_sender_seen(id, state, now)
+        } else {
+            if let Some(entry) = state.receivers.iter_mut().find(|entry| Some(entry.id.as_str()) == peer) {
+                entry.last_seen = now;
+            }
+            false
+        };
+        let both_connected = state.sender_last_seen.is_some_and(|seen| is_connected(seen, now))
+            && state.receivers.iter().any(|entry| is_connected(entry.last_seen, now));
+        (appeared, both_connected)
+    }).await;
+    let Some((appeared, both_connected)) = touched else {
+        return;
+    };
+    if appeared {
+        events::publish(id, events::SENDER_CONNECTED, json!({}));
+    }
+    // A peer-to-peer transfer never touches the relay, so an active room keeps
+    // its own state and the transfer metadata from expiring mid-transfer
+    if both_connected {
+        signal_db.touch(id, SIGNAL_TTL_SECS).await;
+        MetaInfo::get_db().touch(id, META_TTL_SECS).await;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignalPostPayload {
+    pub role: String,
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub data: serde_json::Value,
+    /// Peer id of the posting receiver
+    pub rid: Option<String>,
+    /// Receiver peer id a sender message is addressed to; broadcast when absent
+    pub to: Option<String>,
+    /// Session token returned when the receiver first joined, used to resume
+    pub session: Option<String>,
+    /// Receiver token of an approved claim, required on a receiver's first join
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignalQuery {
+    pub role: String,
+    pub since: Option<u64>,
+    /// Peer id of the polling receiver
+    pub peer: Option<String>,
+    /// Session token of the polling receiver
+    pub session: Option<String>,
+    /// Receiver peer id a sender frame is addressed to; broadcast when absent
+    pub to: Option<String>,
+    /// Receiver token of the claim, for receivers polling without a peer id
+    pub token: Option<String>,
+}
+
+/// Whether a message should be delivered to the polling peer
+fn is_visible(msg: &SignalMessage, role: &str, peer: Option<&str>) -> bool {
+    is_addressed_to(&msg.from, msg.rid.as_deref(), msg.to.as_deref(), role, peer)
+}
+
+fn is_addressed_to(from: &str, rid: Option<&str>, to: Option<&str>, role: &str, peer: Option<&str>) -> bool {
+    if from == role {
+        return false;
+    }
+    let own_id = if role == "sender" { Some(SENDER_PEER_ID) } else { peer };
+    // Peers are not told about their own presence changes
+    if from == SERVER_ROLE && rid == own_id {
+        return false;
+    }
+    match to {
+        Some(to) => own_id == Some(to),
+        None => true,
+    }
+}
+
+/// Handler for posting WebRTC signaling messages
+/// Receivers must identify themselves with `rid`; a room admits up to
+/// SIGNAL_MAX_RECEIVERS receivers. Sender messages are broadcast unless `to` is set
+/// Senders must present their `X-Sender-Token`.
+/// A receiver joins with the `token` of an approved claim; its first message
+/// returns a session token, and presenting that lets the receiver resume its
+/// slot after a drop within SIGNAL_RESUME_GRACE_SECS
+#[instrument(skip_all)]
+pub async fn post_signal(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(payload): Json<SignalPostPayload>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let role = payload.role.trim();
+    if role != "sender" && role != "receiver" {
+        return invalid_role();
+    }
+
+    if role == "sender"
+        && let Err(err) = authorize_sender_role(&id, &headers).await
+    {
+        return err.into_response();
+    }
+
+    let kind = match SignalKind::parse(payload.msg_type.trim(), &payload.data) {
+        Ok(kind) => kind,
+        Err(err) => {
+            event!(Level::WARN, "Rejected signaling message for ID: {}", id);
+            return err.into_response();
+        }
+    };
+    let msg_type = kind.as_str();
+
+    let rid = payload.rid.as_deref().map(str::trim).filter(|rid| !rid.is_empty());
+    if role == "receiver" && rid.is_none() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::MISSING_PARAMETER,
+                "message": "Missing Parameter: rid",
+                "details": { "field": "rid" },
+            })),
+        )
+            .into_response();
+    }
+    if role == "receiver" && rid == Some(SENDER_PEER_ID) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::INVALID_PARAMETER,
+                "message": "Invalid Parameter: rid",
+                "details": { "field": "rid" },
+            })),
+        )
+            .into_response();
+    }
+    // Only a receiver past the claim and approval gates may join the room
+    let claimed = role == "receiver" && holds_claim(&id, payload.token.as_deref()).await;
+
+    // The whole join and append happens under one write lock so concurrent
+    // receivers cannot overwrite each other's updates
+    let signal_db = SignalState::get_db();
+    let _ = signal_db.insert_if_absent(&id, SignalState::new(), SIGNAL_TTL_SECS).await;
+    let now = Instant::now();
+    let posted = signal_db.modify(&id, |state| {
+        prune_peers(&id, state, now);
+        let appeared = role == "sender" && mark_sender_seen(&id, state, now);
+
+        let mut session = None;
+        if let Some(rid) = rid.filter(|_| role == "receiver") {
+            match state.receivers.iter_mut().find(|peer| peer.id == rid) {
+                Some(peer) => {
+                    // A known peer id may only be reused by the holder of its session
+                    if !session_matches(&id, rid, payload.session.as_deref()) {
+                        event!(Level::WARN, "Receiver id already registered for ID: {}", id);
+                        return Err((
+                            StatusCode::CONFLICT,
+                            Json(json!({
+                                "code": 409,
+                                "success": false,
+                                "error": errors::RECEIVER_ID_TAKEN,
+                                "message": "Receiver id already registered"
+                            })),
+                        ));
+                    }
+                    peer.last_seen = now;
+                }
+                None => {
+                    if !claimed {
+                        event!(Level::WARN, "Receiver without an approved claim tried to join signaling room for ID: {}", id);
+                        return Err((
+                            StatusCode::FORBIDDEN,
+                            Json(json!({
+                                "code": 403,
+                                "success": false,
+                                "error": errors::INVALID_RECEIVER_TOKEN,
+                                "message": "An approved receiver claim is required to join"
+                            })),
+                        ));
+                    }
+                    if state.receivers.len() >= max_receivers() {
+                        event!(Level::WARN, "Signaling room full for ID: {}", id);
+                        return Err((
+                            StatusCode::CONFLICT,
+                            Json(json!({
+                                "code": 409,
+                                "success": false,
+                                "error": errors::ROOM_FULL,
+                                "message": format!("Signaling room is full ({} receivers)", max_receivers()),
+                                "details": { "max_receivers": max_receivers() },
+                            })),
+                        ));
+                    }
+                    state.receivers.push(SignalPeer { id: rid.to_string(), joined_at: now, last_seen: now });
+                    push_presence(&id, state, PEER_JOINED, "receiver", rid);
+                    session = Some(token::issue(SESSION_TOKEN_SCOPE, &id, rid));
+                    event!(Level::DEBUG, "Receiver joined signaling room for ID: {}", id);
+                }
+            }
+        }
+
+        // Receivers always talk to the single sender
+        let to = if role == "sender" { payload.to } else { Some(SENDER_PEER_ID.to_string()) };
+        let seq = push_message(state, role, msg_type, payload.data, rid.map(str::to_string), to);
+        Ok((seq, session, appeared))
+    }).await;
+
+    match posted {
+        Some(Ok((seq, session, appeared))) => {
+            if appeared {
+                events::publish(&id, events::SENDER_CONNECTED, json!({}));
+            }
+            signal_db.touch(&id, SIGNAL_TTL_SECS).await;
+            Json(json!({
+                "code": 200,
+                "success": true,
+                "data": {
+                    "seq": seq,
+                    "session": session,
+                }
+            }))
+            .into_response()
+        }
+        Some(Err(err)) => err.into_response(),
+        None => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "code": 500,
+                "success": false,
+                "error": errors::INTERNAL,
+                "message": "Signaling room expired"
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Handler for fetching WebRTC signaling messages
+/// Receivers only see broadcasts and messages addressed to their `peer` id
+#[instrument(skip_all)]
+pub async fn get_signal(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<SignalQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let role = query.role.trim();
+    if role != "sender" && role != "receiver" {
+        return invalid_role();
+    }
+
+    let since = query.since.unwrap_or(0);
+    let peer = query.peer.as_deref().map(str::trim).filter(|peer| !peer.is_empty());
+
+    if let Err(err) = authorize_poll(&id, role, peer, &query, &headers).await {
+        return err.into_response();
+    }
+    let now = Instant::now();
+    touch_peer(&id, role, peer, now).await;
+
+    let state = SignalState::get_db().get(&id).await;
+
+    let (messages, latest, presence) = match state {
+        Some(entry) => {
+            let latest = entry.value.seq;
+            let messages = entry
+                .value
+                .messages
+                .iter()
+                .filter(|msg| msg.seq > since && is_visible(msg, role, peer))
+                .cloned()
+                .collect::<Vec<_>>();
+            (messages, latest, presence(&entry.value, now))
+        }
+        None => (Vec::new(), since, presence(&SignalState::new(), now)),
+    };
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "latest": latest,
+            "messages": messages,
+            "presence": presence,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for relaying a binary frame through the signaling room
+/// The raw request body is queued as-is so clients can use the room as a
+/// low-rate sideband without base64-encoding into JSON messages
+#[instrument(skip_all)]
+pub async fn post_signal_frame(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<SignalQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let role = query.role.trim();
+    if role != "sender" && role != "receiver" {
+        return invalid_role();
+    }
+
+    let peer = query.peer.as_deref().map(str::trim).filter(|peer| !peer.is_empty());
+    if let Err(err) = authorize_poll(&id, role, peer, &query, &headers).await {
+        return err.into_response();
+    }
+    if role == "receiver" && peer.is_none() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "code": 400,
+                "success": false,
+                "error": errors::MISSING_PARAMETER,
+                "message": "Missing Parameter: peer",
+                "details": { "field": "peer" },
+            })),
+        )
+            .into_response();
+    }
+
+    if body.len() > max_frame_bytes() {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(json!({
+                "code": 413,
+                "success": false,
+                "error": errors::SIGNAL_TOO_LARGE,
+                "message": "Signal frame too large",
+                "details": { "size": body.len(), "limit": max_frame_bytes() }
+            })),
+        )
+            .into_response();
+    }
+
+    let to = if role == "sender" { query.to.clone() } else { Some(SENDER_PEER_ID.to_string()) };
+    let seq = SignalState::get_db().modify(&id, |state| {
+        state.frame_seq = state.frame_seq.saturating_add(1);
+        state.frames.push(SignalFrame {
+            seq: state.frame_seq,
+            from: role.to_string(),
+            rid: peer.map(str::to_string),
+            to,
+            data: body,
+        });
+        if state.frames.len() > SIGNAL_MAX_FRAMES {
+            let drain_len = state.frames.len() - SIGNAL_MAX_FRAMES;
+            state.frames.drain(0..drain_len);
+        }
+        state.frame_seq
+    }).await;
+
+    match seq {
+        Some(seq) => Json(json!({
+            "code": 200,
+            "success": true,
+            "data": {
+                "seq": seq
+            }
+        }))
+        .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "code": 404,
+                "success": false,
+                "error": errors::ROOM_NOT_FOUND,
+                "message": "Signaling room not found"
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Handler for fetching the next binary frame after `since`
+/// Returns the raw frame with its sequence in `X-Signal-Seq`, or 204 when none is pending
+#[instrument(skip_all)]
+pub async fn get_signal_frame(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<SignalQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let role = query.role.trim();
+    if role != "sender" && role != "receiver" {
+        return invalid_role();
+    }
+
+    let peer = query.peer.as_deref().map(str::trim).filter(|peer| !peer.is_empty());
+    if let Err(err) = authorize_poll(&id, role, peer, &query, &headers).await {
+        return err.into_response();
+    }
+
+    let since = query.since.unwrap_or(0);
+    let frame = SignalState::get_db().get(&id).await.and_then(|entry| {
+        entry.value.frames.into_iter().find(|frame| {
+            frame.seq > since && is_addressed_to(&frame.from, frame.rid.as_deref(), frame.to.as_deref(), role, peer)
+        })
+    });
+
+    match frame {
+        Some(frame) => (
+            StatusCode::OK,
+            AppendHeaders([
+                (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                (header::HeaderName::from_static("x-signal-seq"), frame.seq.to_string()),
+                (header::HeaderName::from_static("x-signal-from"), frame.rid.unwrap_or(frame.from)),
+            ]),
+            Body::from(frame.data),
+        )
+            .into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// Handler for leaving a signaling room explicitly (e.g. on page unload)
+/// Frees the receiver slot immediately and notifies the other side
+#[instrument(skip_all)]
+pub async fn leave_signal(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<SignalQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let role = query.role.trim();
+    if role != "sender" && role != "receiver" {
+        return invalid_role();
+    }
+
+    let peer = query.peer.as_deref().map(str::trim).filter(|peer| !peer.is_empty());
+    if let Err(err) = authorize_poll(&id, role, peer, &query, &headers).await {
+        return err.into_response();
+    }
+
+    SignalState::get_db().modify(&id, |state| {
+        if role == "sender" {
+            if state.sender_last_seen.take().is_some() {
+                push_presence(&id, state, PEER_LEFT, "sender", SENDER_PEER_ID);
+            }
+        } else if let Some(peer) = peer {
+            let count_before = state.receivers.len();
+            state.receivers.retain(|entry| entry.id != peer);
+            if state.receivers.len() != count_before {
+                push_presence(&id, state, PEER_LEFT, "receiver", peer);
+            }
+        }
+    }).await;
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "message": "Left signaling room"
+    }))
+    .into_response()
+}
+
+/// Handler for signaling keepalives
+/// Clients that stop polling once their data channel is up ping periodically so
+/// the server can tell a live peer from a half-open one; silent peers are reported
+/// as disconnected after SIGNAL_IDLE_TIMEOUT_SECS and evicted after the resume grace
+#[instrument(skip_all)]
+pub async fn ping_signal(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<SignalQuery>,
+) -> impl IntoResponse {
+    let id = normalize_id(&id);
+    let role = query.role.trim();
+    if role != "sender" && role != "receiver" {
+        return invalid_role();
+    }
+
+    let peer = query.peer.as_deref().map(str::trim).filter(|peer| !peer.is_empty());
+    if let Err(err) = authorize_poll(&id, role, peer, &query, &headers).await {
+        return err.into_response();
+    }
+
+    let now = Instant::now();
+    touch_peer(&id, role, peer, now).await;
+    let presence = match SignalState::get_db().get(&id).await {
+        Some(entry) => presence(&entry.value, now),
+        None => presence(&SignalState::new(), now),
+    };
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "type": "pong",
+            "idle_timeout_secs": idle_timeout().as_secs(),
+            "presence": presence,
+        }
+    }))
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(from: &str, to: Option<&str>) -> SignalMessage {
+        SignalMessage {
+            seq: 1,
+            from: from.to_string(),
+            msg_type: "offer".to_string(),
+            data: json!({}),
+            rid: None,
+            to: to.map(str::to_string),
+            sent_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_presence_events_skip_their_subject() {
+        let mut state = SignalState::new();
+        push_presence("presence-test", &mut state, PEER_JOINED, "receiver", "a");
+        let joined = &state.messages[0];
+        assert!(is_visible(joined, "sender", None));
+        assert!(!is_visible(joined, "receiver", Some("a")));
+        assert!(!is_visible(joined, "receiver", Some("b")));
+
+        mark_sender_seen("presence-test", &mut state, Instant::now());
+        let sender_joined = &state.messages[1];
+        assert!(!is_visible(sender_joined, "sender", None));
+        assert!(is_visible(sender_joined, "receiver", Some("a")));
+    }
+
+    #[test]
+    fn test_parse_known_and_custom_types() {
+        let offer = json!({ "type": "offer", "sdp": "v=0" });
+        assert_eq!(SignalKind::parse("offer", &offer).unwrap(), SignalKind::Offer);
+        assert_eq!(SignalKind::parse("candidate", &json!({ "candidate": "" })).unwrap(), SignalKind::Candidate);
+        assert_eq!(SignalKind::parse("x-note", &json!({})).unwrap(), SignalKind::Custom("x-note".to_string()));
+        assert_eq!(SignalKind::parse("chat", &json!({ "text": "wrong file, resending" })).unwrap(), SignalKind::Chat);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_malformed_and_oversized() {
+        assert!(SignalKind::parse("bogus", &json!({})).is_err());
+        assert!(SignalKind::parse("answer", &json!({ "type": "offer", "sdp": "v=0" })).is_err());
+        assert!(SignalKind::parse("candidate", &json!({})).is_err());
+        assert!(SignalKind::parse("chat", &json!({ "text": " " })).is_err());
+        assert!(SignalKind::parse("chat", &json!({ "text": "a".repeat(CHAT_MAX_CHARS + 1) })).is_err());
+        let huge = json!({ "blob": "a".repeat(max_payload_bytes()) });
+        assert!(SignalKind::parse("ready", &huge).is_err());
+    }
+
+    #[test]
+    fn test_broadcast_reaches_all_receivers() {
+        let msg = message("sender", None);
+        assert!(is_visible(&msg, "receiver", Some("a")));
+        assert!(is_visible(&msg, "receiver", Some("b")));
+        assert!(!is_visible(&msg, "sender", None));
+    }
+
+    #[test]
+    fn test_addressed_message_reaches_only_target() {
+        let msg = message("sender", Some("a"));
+        assert!(is_visible(&msg, "receiver", Some("a")));
+        assert!(!is_visible(&msg, "receiver", Some("b")));
+        assert!(!is_visible(&msg, "receiver", None));
+    }
+}
@@ -1,5 +1,190 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
 use rust_embed::Embed;
 
 #[derive(Embed)]
 #[folder = "../web/dist"]
-pub struct StaticFiles;
\ No newline at end of file
+pub struct StaticFiles;
+
+/// Cache policy for content-hashed bundler output
+const IMMUTABLE_CACHE: &str = "public, max-age=31536000, immutable";
+/// Cache policy for everything else: always revalidate via ETag
+const REVALIDATE_CACHE: &str = "no-cache";
+
+/// Branded error pages shipped from `web/public`, keyed by status code
+const ERROR_PAGES: &[(StatusCode, &str)] = &[
+    (StatusCode::NOT_FOUND, "404.html"),
+    (StatusCode::INTERNAL_SERVER_ERROR, "500.html"),
+];
+
+/// Precompressed variants probed in order of preference
+const ENCODINGS: &[(&str, &str)] = &[("br", ".br"), ("gzip", ".gz")];
+
+/// Whether the file name carries a bundler content hash (e.g. `index-B1x9_kQz.js`)
+fn is_hashed_name(path: &str) -> bool {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let stem = match file.rsplit_once('.') {
+        Some((stem, _)) => stem,
+        None => return false,
+    };
+    match stem.rsplit_once(['-', '.']) {
+        Some((name, hash)) => {
+            !name.is_empty()
+                && hash.len() >= 8
+                && hash.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// Join a request path onto an embedded directory, rejecting traversal,
+/// absolute paths, empty segments and backslashes
+pub fn safe_join(base: &str, path: &str) -> Option<String> {
+    if path.is_empty() || path.contains('\\') || path.contains('\0') {
+        return None;
+    }
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        if segment.is_empty() || segment == "." || segment == ".." {
+            return None;
+        }
+        segments.push(segment);
+    }
+    if base.is_empty() {
+        return Some(segments.join("/"));
+    }
+    Some(format!("{}/{}", base, segments.join("/")))
+}
+
+fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            let mut parts = v.trim().split(';');
+            let name = parts.next().unwrap_or("").trim();
+            let rejected = parts.any(|p| p.trim().replace(' ', "") == "q=0");
+            name.eq_ignore_ascii_case(encoding) && !rejected
+        })
+}
+
+/// Serve an embedded file with ETag revalidation, cache headers and
+/// precompressed `.br`/`.gz` variants when the client accepts them.
+/// Returns `None` when the file is not embedded
+pub fn serve_embedded(path: &str, headers: &HeaderMap) -> Option<Response> {
+    let file = StaticFiles::get(path)?;
+
+    let (file, encoding) = ENCODINGS
+        .iter()
+        .filter(|(encoding, _)| accepts_encoding(headers, encoding))
+        .find_map(|(encoding, suffix)| {
+            StaticFiles::get(&format!("{}{}", path, suffix)).map(|f| (f, Some(*encoding)))
+        })
+        .unwrap_or((file, None));
+
+    let hash = file.metadata.sha256_hash();
+    let etag = format!(
+        "\"{}{}\"",
+        hash[..16].iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+        encoding.map(|e| format!("-{}", e)).unwrap_or_default()
+    );
+    let cache_control = if is_hashed_name(path) { IMMUTABLE_CACHE } else { REVALIDATE_CACHE };
+
+    let mut response_headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    response_headers.insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+    if not_modified {
+        return Some((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    if let Ok(value) = HeaderValue::from_str(mime.as_ref()) {
+        response_headers.insert(header::CONTENT_TYPE, value);
+    }
+    if let Some(encoding) = encoding {
+        response_headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    }
+
+    Some((response_headers, Body::from(file.data)).into_response())
+}
+
+/// Render the embedded error page for `status`, falling back to the
+/// plain canonical reason when no page is bundled for it
+pub fn error_page(status: StatusCode) -> Response {
+    let page = ERROR_PAGES
+        .iter()
+        .find(|(code, _)| *code == status)
+        .and_then(|(_, file)| StaticFiles::get(file));
+    match page {
+        Some(page) => (
+            status,
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8")),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+            ],
+            Body::from(page.data),
+        )
+            .into_response(),
+        None => (status, status.canonical_reason().unwrap_or("Error")).into_response(),
+    }
+}
+
+/// `CatchPanicLayer` handler that answers with the embedded 500 page
+pub fn panic_page(err: Box<dyn std::any::Any + Send + 'static>) -> Response {
+    tracing::event!(tracing::Level::ERROR, "Handler panicked: {}", panic_message(err.as_ref()));
+    error_page(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Text of a caught panic payload
+pub fn panic_message(err: &(dyn std::any::Any + Send)) -> &str {
+    err.downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| err.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_hashed_name() {
+        assert!(is_hashed_name("assets/index-B1x9_kQz.js"));
+        assert!(is_hashed_name("assets/img/logo.3f2a9c1d.svg"));
+        assert!(!is_hashed_name("index.html"));
+        assert!(!is_hashed_name("assets/vendor-lib.js"));
+        assert!(!is_hashed_name("favicon.ico"));
+    }
+
+    #[test]
+    fn test_safe_join() {
+        assert_eq!(safe_join("assets", "img/icons/foo.svg").as_deref(), Some("assets/img/icons/foo.svg"));
+        assert_eq!(safe_join("assets", "../index.html"), None);
+        assert_eq!(safe_join("assets", "img/../../secret"), None);
+        assert_eq!(safe_join("assets", "/etc/passwd"), None);
+        assert_eq!(safe_join("assets", "img//foo.svg"), None);
+        assert_eq!(safe_join("assets", "img\\foo.svg"), None);
+        assert_eq!(safe_join("", "upload/index.html").as_deref(), Some("upload/index.html"));
+    }
+
+    #[test]
+    fn test_accepts_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip, br;q=0"));
+        assert!(accepts_encoding(&headers, "gzip"));
+        assert!(!accepts_encoding(&headers, "br"));
+    }
+}
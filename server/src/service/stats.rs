@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use serde_json::json;
+
+use crate::{
+    dao::db::{FlowMetrics, TransferMetrics},
+    service::tenant,
+    utils::time::unix_now,
+};
+
+/// Length of the rolling window reported next to the lifetime totals (minutes)
+const WINDOW_MINUTES: usize = 60;
+/// Span of the "current" per-transfer rate
+const RECENT_RATE_SECS: u64 = 10;
+
+lazy_static! {
+    static ref STATS: Stats = Stats::new();
+    /// Counters of each configured tenant, reported to admin requests scoped to it
+    static ref TENANT_STATS: HashMap<String, Stats> = tenant::names().map(|name| (name.clone(), Stats::new())).collect();
+}
+
+/// Counters an event for transfer `id` lands in: the instance's and its tenant's
+fn scopes(id: &str) -> impl Iterator<Item = &'static Stats> {
+    std::iter::once(&*STATS).chain(tenant::of(id).and_then(|name| TENANT_STATS.get(name)))
+}
+
+/// Counters for one minute of the rolling window
+#[derive(Clone, Copy, Default)]
+struct Bucket {
+    minute: u64,
+    created: u64,
+    completed: u64,
+    bytes_relayed: u64,
+}
+
+/// Lifetime totals of this instance plus a per-minute rolling window
+struct Stats {
+    started_at: Instant,
+    created: AtomicU64,
+    completed: AtomicU64,
+    cancelled: AtomicU64,
+    expired: AtomicU64,
+    completed_p2p: AtomicU64,
+    completed_relay: AtomicU64,
+    completed_bytes: AtomicU64,
+    bytes_relayed: AtomicU64,
+    window: Mutex<[Bucket; WINDOW_MINUTES]>,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Stats {
+            started_at: Instant::now(),
+            created: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+            completed_p2p: AtomicU64::new(0),
+            completed_relay: AtomicU64::new(0),
+            completed_bytes: AtomicU64::new(0),
+            bytes_relayed: AtomicU64::new(0),
+            window: Mutex::new([Bucket::default(); WINDOW_MINUTES]),
+        }
+    }
+
+    fn bump(&self, f: impl FnOnce(&mut Bucket)) {
+        if let Ok(mut window) = self.window.lock() {
+            f(bucket_for(&mut window, unix_now() / 60));
+        }
+    }
+}
+
+/// Bucket slot for `minute`, recycling it when it still holds an older minute
+fn bucket_for(window: &mut [Bucket; WINDOW_MINUTES], minute: u64) -> &mut Bucket {
+    let bucket = &mut window[minute as usize % WINDOW_MINUTES];
+    if bucket.minute != minute {
+        *bucket = Bucket { minute, ..Bucket::default() };
+    }
+    bucket
+}
+
+/// Sum the buckets that fall inside the window ending at `minute`
+fn window_totals(window: &[Bucket; WINDOW_MINUTES], minute: u64) -> Bucket {
+    let oldest = minute.saturating_sub(WINDOW_MINUTES as u64 - 1);
+    window
+        .iter()
+        .filter(|bucket| bucket.minute >= oldest && bucket.minute <= minute)
+        .fold(Bucket { minute, ..Bucket::default() }, |mut acc, bucket| {
+            acc.created += bucket.created;
+            acc.completed += bucket.completed;
+            acc.bytes_relayed += bucket.bytes_relayed;
+            acc
+        })
+}
+
+pub fn record_created(id: &str) {
+    for stats in scopes(id) {
+        stats.created.fetch_add(1, Ordering::Relaxed);
+        stats.bump(|bucket| bucket.created += 1);
+    }
+}
+
+/// A transfer finished; `via_relay` tells whether any block went through the server
+pub fn record_completed(id: &str, file_size: u64, via_relay: bool) {
+    for stats in scopes(id) {
+        stats.completed.fetch_add(1, Ordering::Relaxed);
+        stats.completed_bytes.fetch_add(file_size, Ordering::Relaxed);
+        if via_relay {
+            stats.completed_relay.fetch_add(1, Ordering::Relaxed);
+        } else {
+            stats.completed_p2p.fetch_add(1, Ordering::Relaxed);
+        }
+        stats.bump(|bucket| bucket.completed += 1);
+    }
+}
+
+pub fn record_cancelled(id: &str) {
+    for stats in scopes(id) {
+        stats.cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A transfer's metadata expired before it was marked done
+pub fn record_expired(id: &str) {
+    for stats in scopes(id) {
+        stats.expired.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn record_relayed(id: &str, bytes: u64) {
+    for stats in scopes(id) {
+        stats.bytes_relayed.fetch_add(bytes, Ordering::Relaxed);
+        stats.bump(|bucket| bucket.bytes_relayed += bytes);
+    }
+}
+
+/// Point-in-time view of the counters for the admin API; a request scoped to
+/// a tenant sees only that tenant's
+pub fn snapshot() -> serde_json::Value {
+    let stats = tenant::current().and_then(|name| TENANT_STATS.get(&name)).unwrap_or(&STATS);
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let completed = load(&stats.completed);
+    let p2p = load(&stats.completed_p2p);
+    let relay = load(&stats.completed_relay);
+    let last_window = stats
+        .window
+        .lock()
+        .map(|window| window_totals(&window, unix_now() / 60))
+        .unwrap_or_default();
+
+    json!({
+        "uptime_secs": stats.started_at.elapsed().as_secs(),
+        "transfers": {
+            "created": load(&stats.created),
+            "completed": completed,
+            "cancelled": load(&stats.cancelled),
+            "expired": load(&stats.expired),
+            "p2p": p2p,
+            "relay": relay,
+            "p2p_ratio": if p2p + relay > 0 { p2p as f64 / (p2p + relay) as f64 } else { 0.0 },
+            "average_size": load(&stats.completed_bytes).checked_div(completed).unwrap_or(0),
+        },
+        "bytes_relayed": load(&stats.bytes_relayed),
+        "last_hour": {
+            "created": last_window.created,
+            "completed": last_window.completed,
+            "bytes_relayed": last_window.bytes_relayed,
+        },
+    })
+}
+
+fn bytes_per_sec(bytes: u64, span: Duration) -> Option<u64> {
+    (!span.is_zero()).then(|| (bytes as f64 / span.as_secs_f64()) as u64)
+}
+
+/// Rate over the last few seconds, or since the first block when more recent
+pub fn recent_bps(flow: &FlowMetrics, now: Instant) -> u64 {
+    let Some(first_at) = flow.first_at else {
+        return 0;
+    };
+    let window = Duration::from_secs(RECENT_RATE_SECS);
+    let span = now.duration_since(first_at).min(window);
+    let bytes = flow.samples.iter().filter(|(at, _)| now.duration_since(*at) <= span).map(|(_, bytes)| bytes).sum();
+    bytes_per_sec(bytes, span).unwrap_or(0)
+}
+
+fn flow_summary(flow: &FlowMetrics, now: Instant) -> serde_json::Value {
+    let span = match (flow.first_at, flow.last_at) {
+        (Some(first), Some(last)) => last.duration_since(first).max(flow.busy),
+        _ => Duration::ZERO,
+    };
+    json!({
+        "bytes": flow.bytes,
+        "blocks": flow.blocks,
+        // From the first to the latest block, so idle time between blocks counts
+        "average_bps": bytes_per_sec(flow.bytes, span),
+        "current_bps": recent_bps(flow, now),
+        // Only while the body was on the wire: the endpoint's own link speed
+        "link_bps": bytes_per_sec(flow.bytes, flow.busy),
+    })
+}
+
+/// Which side held the transfer back: the relay's own handling, a sender the
+/// receivers kept waiting on, or receivers leaving blocks buffered
+fn bottleneck(metrics: &TransferMetrics) -> Option<&'static str> {
+    if metrics.upload.blocks == 0 || metrics.download.blocks == 0 {
+        return None;
+    }
+    Some(if metrics.relay_time > metrics.upload.busy {
+        "relay"
+    } else if metrics.receiver_wait >= metrics.buffer_dwell {
+        "sender"
+    } else {
+        "receiver"
+    })
+}
+
+/// Throughput and timing of one transfer for the status endpoint
+pub fn transfer_metrics(metrics: &TransferMetrics) -> serde_json::Value {
+    let now = Instant::now();
+    json!({
+        "upload": flow_summary(&metrics.upload, now),
+        "download": flow_summary(&metrics.download, now),
+        "relay_ms": metrics.relay_time.as_millis() as u64,
+        "receiver_wait_ms": metrics.receiver_wait.as_millis() as u64,
+        "buffer_dwell_ms": metrics.buffer_dwell.as_millis() as u64,
+        "bottleneck": bottleneck(metrics),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_rates() {
+        let mut flow = FlowMetrics::default();
+        assert_eq!(recent_bps(&flow, Instant::now()), 0);
+        flow.record(1000, Duration::from_millis(500));
+        let summary = flow_summary(&flow, Instant::now());
+        // A single block has no span besides its own transfer time
+        assert_eq!(summary["average_bps"], 2000);
+        assert_eq!(summary["link_bps"], 2000);
+
+        let mut metrics = TransferMetrics { upload: flow.clone(), download: flow, ..Default::default() };
+        metrics.buffer_dwell = Duration::from_secs(1);
+        assert_eq!(bottleneck(&metrics), Some("receiver"));
+        metrics.relay_time = Duration::from_secs(1);
+        assert_eq!(bottleneck(&metrics), Some("relay"));
+    }
+
+    #[test]
+    fn test_window_rolls_over_stale_buckets() {
+        let mut window = [Bucket::default(); WINDOW_MINUTES];
+        bucket_for(&mut window, 100).created += 2;
+        bucket_for(&mut window, 101).created += 1;
+        assert_eq!(window_totals(&window, 101).created, 3);
+
+        // Minute 101's slot is recycled a window later and minute 100 has aged out
+        let later = 101 + WINDOW_MINUTES as u64;
+        bucket_for(&mut window, later).created += 5;
+        assert_eq!(window_totals(&window, later).created, 5);
+    }
+}
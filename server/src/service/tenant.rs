@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::Request,
+    http::{StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use lazy_static::lazy_static;
+use serde_json::json;
+use tracing::{event, Level};
+
+use crate::{
+    dao::db::QuotaUsage,
+    service::{auth, errors},
+    utils::env::read_env_string,
+};
+
+/// Joins a tenant and a transfer id into a store key; never part of an id alphabet
+pub const SEPARATOR: char = '~';
+/// Path prefix selecting a tenant, as in `/t/{tenant}/api/...`
+const PATH_PREFIX: &str = "/t/";
+/// Length of the per-tenant quota window (seconds)
+const QUOTA_WINDOW_SECS: u64 = 60 * 60 * 24;
+/// Longest accepted tenant name
+const MAX_NAME_LEN: usize = 32;
+
+lazy_static! {
+    static ref TENANTS: HashMap<String, Option<u64>> = read_tenants();
+}
+
+tokio::task_local! {
+    /// Tenant the current request was resolved to
+    static CURRENT: String;
+}
+
+/// Lowercase letters, digits and dashes, so a name is a safe path segment
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// Parse TENANTS as a comma separated list of `name` or `name:daily_quota`
+fn read_tenants() -> HashMap<String, Option<u64>> {
+    let Some(raw) = read_env_string("TENANTS") else {
+        return HashMap::new();
+    };
+
+    let mut tenants = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (name, quota) = match entry.split_once(':') {
+            Some((name, quota)) => (name.trim(), quota.trim().parse::<u64>().ok()),
+            None => (entry, None),
+        };
+        if !valid_name(name) {
+            event!(Level::WARN, "Invalid tenant name '{}', ignoring", name);
+            continue;
+        }
+        tenants.insert(name.to_string(), quota);
+    }
+
+    event!(Level::INFO, "Multi-tenant mode enabled with {} tenants", tenants.len());
+    tenants
+}
+
+/// Whether any tenants are configured
+pub fn enabled() -> bool {
+    !TENANTS.is_empty()
+}
+
+/// Configured tenant names
+pub fn names() -> impl Iterator<Item = &'static String> {
+    TENANTS.keys()
+}
+
+/// Tenant of the request being handled, if any
+pub fn current() -> Option<String> {
+    CURRENT.try_with(String::clone).ok()
+}
+
+/// The transfer id part of a store key
+pub fn bare(key: &str) -> &str {
+    key.rsplit_once(SEPARATOR).map_or(key, |(_, id)| id)
+}
+
+/// Tenant a store key belongs to
+pub fn of(key: &str) -> Option<&str> {
+    key.rsplit_once(SEPARATOR).map(|(tenant, _)| tenant)
+}
+
+/// Store key of an id in the current tenant; a tenant given with the id is
+/// dropped so one tenant's codes never resolve from another or from outside
+pub fn scoped(id: &str) -> String {
+    let id = bare(id);
+    match current() {
+        Some(tenant) => format!("{}{}{}", tenant, SEPARATOR, id),
+        None => id.to_string(),
+    }
+}
+
+/// Whether a store key is visible from the current scope; outside any tenant
+/// every key is
+pub fn owns(key: &str) -> bool {
+    match current() {
+        Some(tenant) => of(key) == Some(tenant.as_str()),
+        None => true,
+    }
+}
+
+/// Prefix for URLs handed out while serving a tenant's request
+pub fn path_prefix() -> String {
+    current().map(|tenant| format!("{}{}", PATH_PREFIX, tenant)).unwrap_or_default()
+}
+
+/// Split `/t/{tenant}/rest` into the tenant and `/rest`
+pub(crate) fn split_path(path: &str) -> Option<(&str, &str)> {
+    let rest = path.strip_prefix(PATH_PREFIX)?;
+    Some(match rest.find('/') {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, "/"),
+    })
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({
+        "code": 404,
+        "success": false,
+        "error": errors::UNKNOWN_TENANT,
+        "message": "Unknown tenant"
+    }))).into_response()
+}
+
+/// Charge a transfer creation against the current tenant's daily quota
+pub async fn charge_creation() -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let Some(tenant) = current() else {
+        return Ok(());
+    };
+    let Some(Some(limit)) = TENANTS.get(&tenant).copied() else {
+        return Ok(());
+    };
+
+    let usage_db = QuotaUsage::get_db();
+    let key = format!("{}{}", tenant, SEPARATOR);
+    let _ = usage_db.insert_if_absent(&key, QuotaUsage { count: 0 }, QUOTA_WINDOW_SECS).await;
+    let within_quota = usage_db.modify(&key, |usage| {
+        if usage.count >= limit {
+            return false;
+        }
+        usage.count += 1;
+        true
+    }).await;
+
+    if within_quota != Some(true) {
+        event!(Level::WARN, "Tenant {} quota of {} transfers exhausted", tenant, limit);
+        return Err((StatusCode::TOO_MANY_REQUESTS, Json(json!({
+            "code": 429,
+            "success": false,
+            "error": errors::QUOTA_EXHAUSTED,
+            "message": format!("Tenant quota exhausted ({} transfers per day)", limit),
+            "details": { "scope": "tenant", "limit": limit },
+        }))));
+    }
+    Ok(())
+}
+
+/// Resolve the tenant from a `/t/{tenant}` path prefix, which is stripped
+/// before routing, or from the tenant an API key is bound to; the rest of the
+/// request runs scoped to it
+pub async fn resolve(mut request: Request, next: Next) -> Response {
+    if !enabled() {
+        return next.run(request).await;
+    }
+
+    let mut tenant = None;
+    if let Some((name, rest)) = split_path(request.uri().path()) {
+        if !TENANTS.contains_key(name) {
+            event!(Level::DEBUG, "Request for unknown tenant '{}'", name);
+            return not_found();
+        }
+        let path_and_query = match request.uri().query() {
+            Some(query) => format!("{}?{}", rest, query),
+            None => rest.to_string(),
+        };
+        let mut parts = request.uri().clone().into_parts();
+        parts.path_and_query = path_and_query.parse().ok();
+        let Ok(uri) = Uri::from_parts(parts) else {
+            return not_found();
+        };
+        tenant = Some(name.to_string());
+        *request.uri_mut() = uri;
+    }
+
+    match (tenant.as_deref(), auth::api_key_tenant(request.headers())) {
+        (Some(tenant), Some(bound)) if tenant != bound => {
+            event!(Level::WARN, "API key of tenant {} used for tenant {}", bound, tenant);
+            return (StatusCode::FORBIDDEN, Json(json!({
+                "code": 403,
+                "success": false,
+                "error": errors::TENANT_MISMATCH,
+                "message": "API key belongs to another tenant"
+            }))).into_response();
+        }
+        (None, Some(bound)) => tenant = Some(bound.to_string()),
+        _ => {}
+    }
+
+    match tenant {
+        Some(tenant) => CURRENT.scope(tenant, next.run(request)).await,
+        None => next.run(request).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scoped_keys() {
+        assert_eq!(split_path("/t/team-a/api/info"), Some(("team-a", "/api/info")));
+        assert_eq!(split_path("/t/team-a"), Some(("team-a", "/")));
+        assert_eq!(split_path("/api/info"), None);
+
+        assert_eq!(scoped("team-a~abc"), "abc");
+        let key = CURRENT.scope("team-a".to_string(), async {
+            // Another tenant's key resolves inside this tenant instead
+            assert_eq!(scoped("team-b~abc"), "team-a~abc");
+            assert!(!owns("team-b~abc") && !owns("abc"));
+            assert_eq!(path_prefix(), "/t/team-a");
+            scoped("abc")
+        }).await;
+        assert_eq!((of(&key), bare(&key)), (Some("team-a"), "abc"));
+        assert!(owns(&key));
+    }
+}
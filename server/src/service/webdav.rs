@@ -0,0 +1,258 @@
+use std::{io, net::SocketAddr, time::Instant};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::stream;
+use serde_json::json;
+use tracing::{event, instrument, Level};
+
+use crate::{
+    dao::db::MetaInfo,
+    service::{audit, errors, handler::{normalize_id, record_consumption, wait_for_block, wait_while_paused, RECEIVER_TOKEN_SCOPE}, stats, tenant},
+    utils::token,
+};
+
+/// Mount point of the WebDAV view
+const DAV_PREFIX: &str = "/dav";
+/// Methods a read-only collection answers
+const DAV_ALLOW: &str = "OPTIONS, PROPFIND, GET, HEAD";
+
+/// Path a receiver mounts to browse the transfer with the OS file manager
+pub fn mount_path(id: &str, receiver_token: &str) -> String {
+    format!("{}{}/{}/{}/", tenant::path_prefix(), DAV_PREFIX, encode_segment(tenant::bare(id)), encode_segment(receiver_token))
+}
+
+/// Check the receiver token embedded in the mount URL; returns the receiver id
+/// and the transfer it may read
+async fn authorize(id: &str, receiver_token: &str) -> Result<(String, MetaInfo), (StatusCode, Json<serde_json::Value>)> {
+    let Some(receive_id) = token::open(RECEIVER_TOKEN_SCOPE, id, receiver_token).map(str::to_string) else {
+        event!(Level::WARN, "Invalid receiver token for WebDAV view of ID: {}", id);
+        return Err((StatusCode::FORBIDDEN, Json(json!({
+            "code": 403,
+            "success": false,
+            "error": errors::INVALID_RECEIVER_TOKEN,
+            "message": "Invalid receiver token"
+        }))));
+    };
+
+    let Some(meta_info) = MetaInfo::get_db().get(id).await else {
+        return Err((StatusCode::NOT_FOUND, Json(json!({
+            "code": 404,
+            "success": false,
+            "error": errors::TRANSFER_NOT_FOUND,
+            "message": "Not Found"
+        }))));
+    };
+
+    match meta_info.value.receiver(&receive_id) {
+        Some(claim) if claim.approved => Ok((receive_id, meta_info.value)),
+        Some(_) => Err((StatusCode::FORBIDDEN, Json(json!({
+            "code": 403,
+            "success": false,
+            "error": errors::AWAITING_APPROVAL,
+            "message": "Awaiting sender approval"
+        })))),
+        None => Err((StatusCode::BAD_REQUEST, Json(json!({
+            "code": 400,
+            "success": false,
+            "error": errors::WRONG_RECEIVER,
+            "message": "Wrong Receive ID"
+        })))),
+    }
+}
+
+fn xml_escape(raw: &str) -> String {
+    raw.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Percent-encode one path segment for an `href`
+fn encode_segment(raw: &str) -> String {
+    raw.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// One `<D:response>` entry; `size` is None for the collection
+fn prop_entry(href: &str, name: &str, size: Option<u64>) -> String {
+    let props = match size {
+        None => "<D:resourcetype><D:collection/></D:resourcetype>".to_string(),
+        Some(size) => format!(
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength><D:getcontenttype>{}</D:getcontenttype>",
+            size,
+            mime_guess::from_path(name).first_or_octet_stream()
+        ),
+    };
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop><D:displayname>{}</D:displayname>{}</D:prop>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        xml_escape(href),
+        xml_escape(name),
+        props
+    )
+}
+
+fn multistatus(entries: Vec<String>) -> Response {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">{}</D:multistatus>",
+        entries.concat()
+    );
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+/// Answer for methods the read-only view does not support, including every write
+fn method_not_allowed(method: &Method) -> Response {
+    event!(Level::DEBUG, "Rejected WebDAV {} on read-only view", method);
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        [(header::ALLOW, DAV_ALLOW)],
+        Json(json!({
+            "code": 405,
+            "success": false,
+            "error": errors::READ_ONLY,
+            "message": "Read-only WebDAV view"
+        })),
+    )
+        .into_response()
+}
+
+fn options() -> Response {
+    (
+        StatusCode::OK,
+        [(header::ALLOW, DAV_ALLOW), (header::HeaderName::from_static("dav"), "1")],
+    )
+        .into_response()
+}
+
+/// Handler for the virtual directory of a claimed transfer at
+/// `/dav/{id}/{token}/`, listing the transfer's file
+#[instrument(skip_all)]
+pub async fn dav_collection(
+    method: Method,
+    Path((id, receiver_token)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let id = normalize_id(&id);
+    if method == Method::OPTIONS {
+        return options();
+    }
+    if method.as_str() != "PROPFIND" {
+        return method_not_allowed(&method);
+    }
+    let meta = match authorize(&id, &receiver_token).await {
+        Ok((_, meta)) => meta,
+        Err(err) => return err.into_response(),
+    };
+
+    let base = mount_path(&id, &receiver_token);
+    let mut entries = vec![prop_entry(&base, &id, None)];
+    // Depth 0 asks about the collection alone
+    if headers.get("depth").and_then(|v| v.to_str().ok()) != Some("0") && !meta.file_name.is_empty() {
+        let href = format!("{}{}", base, encode_segment(&meta.file_name));
+        entries.push(prop_entry(&href, &meta.file_name, Some(meta.file_size)));
+    }
+    event!(Level::TRACE, "WebDAV listing for ID: {}", id);
+    multistatus(entries)
+}
+
+/// Handler for the transfer's file inside the WebDAV view; GET streams the
+/// blocks through the relay in order, consuming them like `/file` does
+#[instrument(skip_all)]
+pub async fn dav_file(
+    method: Method,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path((id, receiver_token, name)): Path<(String, String, String)>,
+) -> Response {
+    let id = normalize_id(&id);
+    if method == Method::OPTIONS {
+        return options();
+    }
+    if !matches!(method.as_str(), "PROPFIND" | "GET" | "HEAD") {
+        return method_not_allowed(&method);
+    }
+    let (receive_id, meta) = match authorize(&id, &receiver_token).await {
+        Ok(authorized) => authorized,
+        Err(err) => return err.into_response(),
+    };
+    if name != meta.file_name {
+        return (StatusCode::NOT_FOUND, Json(json!({
+            "code": 404,
+            "success": false,
+            "error": errors::NOT_FOUND,
+            "message": "Not Found"
+        }))).into_response();
+    }
+
+    if method.as_str() == "PROPFIND" {
+        let href = format!("{}{}", mount_path(&id, &receiver_token), encode_segment(&meta.file_name));
+        return multistatus(vec![prop_entry(&href, &meta.file_name, Some(meta.file_size))]);
+    }
+
+    let mime = mime_guess::from_path(&meta.file_name).first_or_octet_stream().to_string();
+    let mut response = Response::builder().status(StatusCode::OK).header(header::CONTENT_TYPE, mime);
+    // A size of 0 means the sender has not announced it yet
+    if meta.file_size > 0 {
+        response = response.header(header::CONTENT_LENGTH, meta.file_size);
+    }
+    if method == Method::HEAD {
+        return response.body(Body::empty()).unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+    // Relayed blocks can only be read once, so there is no random access
+    response = response.header(header::ACCEPT_RANGES, "none");
+
+    event!(Level::DEBUG, "WebDAV download of ID: {} by receiver {}", id, receive_id);
+
+    let max_receivers = meta.max_receivers as usize;
+    let blocks = stream::unfold(Some(0u64), move |next| {
+        let id = id.clone();
+        let receive_id = receive_id.clone();
+        async move {
+            let start = next?;
+            // A paused transfer holds the copy open rather than failing it
+            wait_while_paused(&id).await;
+            let wait_started = Instant::now();
+            let Some(block) = wait_for_block(&id, start).await else {
+                // Aborting the body tells the client the copy failed rather than truncating silently
+                event!(Level::WARN, "WebDAV stream for ID: {} stalled at {}", id, start);
+                return Some((Err(io::Error::new(io::ErrorKind::TimedOut, "block not ready")), None));
+            };
+            record_consumption(&id, &receive_id, start, block.end, max_receivers, block.data.len() as u64, wait_started.elapsed());
+            stats::record_relayed(&id, block.data.len() as u64);
+            audit::record(audit::BLOCK_RELAYED, Some(&id), client, json!({
+                "receiver": receive_id,
+                "start": block.start,
+                "end": block.end,
+                "via": "webdav",
+            }));
+            let next = (block.end + 1 < block.total).then_some(block.end + 1);
+            Some((Ok(block.data), next))
+        }
+    });
+    response.body(Body::from_stream(blocks)).unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prop_entry_escapes_name() {
+        let href = format!("/dav/abc/t/{}", encode_segment("a&b c.txt"));
+        assert_eq!(href, "/dav/abc/t/a%26b%20c.txt");
+        let entry = prop_entry(&href, "a&b c.txt", Some(3));
+        assert!(entry.contains("<D:displayname>a&amp;b c.txt</D:displayname>"));
+        assert!(entry.contains("<D:getcontentlength>3</D:getcontentlength>"));
+    }
+}
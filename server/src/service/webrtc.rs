@@ -0,0 +1,185 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::Sha1;
+use tracing::{event, instrument, Level};
+
+use crate::service::errors;
+use crate::utils::{env::{read_env_string, read_env_u64}, time::unix_now};
+
+/// Public STUN servers used when nothing is configured
+const DEFAULT_STUN_SERVERS: &[&str] = &["stun:stun.l.google.com:19302", "stun:stun.cloudflare.com:3478"];
+
+lazy_static! {
+    static ref ICE_SERVERS: Vec<IceServer> = read_ice_servers();
+    static ref TURN_SECRET: Option<String> = read_env_string("TURN_SECRET");
+    static ref TURN_CREDENTIAL_TTL_SECS: u64 = read_env_u64("TURN_CREDENTIAL_TTL_SECS", 60 * 60 * 24);
+    static ref TURN_USER_PREFIX: String = read_env_string("TURN_USER_PREFIX").unwrap_or_else(|| "fileflow".to_string());
+}
+
+/// A single entry of RTCPeerConnection's `iceServers`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+impl IceServer {
+    fn is_turn(&self) -> bool {
+        self.urls.iter().any(|url| url.starts_with("turn:") || url.starts_with("turns:"))
+    }
+}
+
+fn split_urls(raw: Option<String>) -> Vec<String> {
+    raw.map(|raw| {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Load ICE servers from ICE_SERVERS (JSON array) or the STUN_SERVER / TURN_SERVER
+/// comma separated lists, defaulting to public STUN servers
+fn read_ice_servers() -> Vec<IceServer> {
+    if let Some(raw) = read_env_string("ICE_SERVERS") {
+        match serde_json::from_str::<Vec<IceServer>>(&raw) {
+            Ok(servers) => return servers,
+            Err(err) => event!(Level::WARN, "ICE_SERVERS is invalid ({}), falling back to STUN_SERVER/TURN_SERVER", err),
+        }
+    }
+
+    let mut servers = Vec::new();
+    let stun_urls = split_urls(read_env_string("STUN_SERVER"));
+    let turn_urls = split_urls(read_env_string("TURN_SERVER"));
+
+    if stun_urls.is_empty() && turn_urls.is_empty() {
+        servers.push(IceServer {
+            urls: DEFAULT_STUN_SERVERS.iter().map(|url| url.to_string()).collect(),
+            username: None,
+            credential: None,
+        });
+        return servers;
+    }
+
+    if !stun_urls.is_empty() {
+        servers.push(IceServer { urls: stun_urls, username: None, credential: None });
+    }
+    if !turn_urls.is_empty() {
+        servers.push(IceServer {
+            urls: turn_urls,
+            username: read_env_string("TURN_USERNAME"),
+            credential: read_env_string("TURN_CREDENTIAL"),
+        });
+    }
+    servers
+}
+
+/// Whether any TURN relay is configured for when direct P2P fails
+pub fn turn_relay_enabled() -> bool {
+    ICE_SERVERS.iter().any(IceServer::is_turn)
+}
+
+/// ICE servers to hand to clients, with ephemeral credentials filled into
+/// TURN entries when TURN_SECRET is set
+pub fn ice_servers() -> Vec<IceServer> {
+    let turn = mint_turn_credential();
+    ICE_SERVERS
+        .iter()
+        .cloned()
+        .map(|mut server| {
+            if let Some(turn) = turn.as_ref().filter(|_| server.is_turn()) {
+                server.username = Some(turn.username.clone());
+                server.credential = Some(turn.credential.clone());
+            }
+            server
+        })
+        .collect()
+}
+
+/// Ephemeral TURN credential pair following the coturn REST API scheme
+pub struct TurnCredential {
+    pub username: String,
+    pub credential: String,
+    pub expires_at: u64,
+}
+
+/// Mint a TURN credential: username is `<expiry>:<prefix>` and the credential
+/// is base64(HMAC-SHA1(secret, username)), as coturn's `use-auth-secret` expects
+pub fn mint_turn_credential() -> Option<TurnCredential> {
+    let secret = TURN_SECRET.as_ref()?;
+    let expires_at = unix_now() + *TURN_CREDENTIAL_TTL_SECS;
+    let username = format!("{}:{}", expires_at, TURN_USER_PREFIX.as_str());
+
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(username.as_bytes());
+    let credential = STANDARD.encode(mac.finalize().into_bytes());
+
+    Some(TurnCredential { username, credential, expires_at })
+}
+
+/// Handler for returning P2P configuration (STUN/TURN)
+/// `ice_servers` is the full list; the flat fields mirror its first STUN and TURN entries
+pub async fn get_p2p_config() -> impl IntoResponse {
+    let servers = ice_servers();
+    let stun = servers.iter().find(|server| !server.is_turn());
+    let turn = servers.iter().find(|server| server.is_turn());
+
+    Json(json!({
+        "code": 200,
+        "success": true,
+        "data": {
+            "stun": stun.and_then(|server| server.urls.first()),
+            "turn": turn.and_then(|server| server.urls.first()),
+            "turn_username": turn.and_then(|server| server.username.as_ref()),
+            "turn_credential": turn.and_then(|server| server.credential.as_ref()),
+            "ice_servers": servers,
+        }
+    }))
+    .into_response()
+}
+
+/// Handler for minting time-limited TURN credentials
+/// Returns 404 when TURN_SECRET is not configured
+#[instrument]
+pub async fn get_turn_credentials() -> impl IntoResponse {
+    match mint_turn_credential() {
+        Some(turn) => Json(json!({
+            "code": 200,
+            "success": true,
+            "data": {
+                "urls": ICE_SERVERS
+                    .iter()
+                    .filter(|server| server.is_turn())
+                    .flat_map(|server| server.urls.clone())
+                    .collect::<Vec<_>>(),
+                "username": turn.username,
+                "credential": turn.credential,
+                "ttl": *TURN_CREDENTIAL_TTL_SECS,
+                "expires_at": turn.expires_at,
+            }
+        }))
+        .into_response(),
+        None => {
+            event!(Level::DEBUG, "TURN credentials requested but TURN_SECRET is not set");
+            (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "code": 404,
+                    "success": false,
+                    "error": errors::TURN_UNAVAILABLE,
+                    "message": "TURN credentials are not configured"
+                }))
+            )
+            .into_response()
+        }
+    }
+}
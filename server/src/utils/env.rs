@@ -0,0 +1,38 @@
+use std::env;
+use tracing::{event, Level};
+
+/// Read a positive integer from the environment, falling back to the default
+pub fn read_env_u64(key: &str, default: u64) -> u64 {
+    match env::var(key) {
+        Ok(raw) => match raw.trim().parse::<u64>() {
+            Ok(value) if value > 0 => value,
+            _ => {
+                event!(Level::WARN, "{} is invalid (value: '{}'), using default {}", key, raw, default);
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+/// Read a positive integer from the environment, falling back to the default
+pub fn read_env_usize(key: &str, default: usize) -> usize {
+    match env::var(key) {
+        Ok(raw) => match raw.trim().parse::<u64>() {
+            Ok(value) if value > 0 => value.min(usize::MAX as u64) as usize,
+            _ => {
+                event!(Level::WARN, "{} is invalid (value: '{}'), using default {}", key, raw, default);
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+/// Read a trimmed, non-empty string from the environment
+pub fn read_env_string(key: &str) -> Option<String> {
+    env::var(key).ok().and_then(|raw| {
+        let trimmed = raw.trim().to_string();
+        if trimmed.is_empty() { None } else { Some(trimmed) }
+    })
+}
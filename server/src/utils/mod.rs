@@ -1 +1,4 @@
-pub mod nanoid;
\ No newline at end of file
+pub mod env;
+pub mod nanoid;
+pub mod time;
+pub mod token;
\ No newline at end of file
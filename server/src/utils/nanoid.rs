@@ -1,38 +1,33 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use rand::Rng;
 
 // Restrict IDs to 5 characters using digits + lowercase letters
-const ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
-const DEFAULT_SIZE: usize = 5;
+pub const ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+// Lowercase alphabet without look-alikes (0/o, 1/l/i) for codes read aloud or typed on phones
+pub const UNAMBIGUOUS_ALPHABET: &str = "23456789abcdefghjkmnpqrstuvwxyz";
+pub const DEFAULT_SIZE: usize = 5;
 
 /// using the default alphabet
+#[cfg(test)]
 pub fn generate() -> String {
     generate_with_length(DEFAULT_SIZE)
 }
 
 /// Generate a random nanoid string with specified length
+#[cfg(test)]
 pub fn generate_with_length(length: usize) -> String {
     generate_custom(length, ALPHABET)
 }
 
 /// Generate a random nanoid string with specified length and alphabet
+/// Each character is drawn independently from the thread-local CSPRNG, so ids
+/// carry the full entropy of their length and cannot be predicted from the clock
 pub fn generate_custom(length: usize, alphabet: &str) -> String {
-    let mut output = String::with_capacity(length);
-    
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_nanos();
-    
-    let mut seed = now as u64;
-    let alphabet_len = alphabet.len();
-    
-    for _ in 0..length {
-        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
-        let index = (seed as usize) % alphabet_len;
-        output.push(alphabet.chars().nth(index).unwrap_or('0'));
-    }
-    
-    output
+    let alphabet: Vec<char> = alphabet.chars().collect();
+    let mut rng = rand::rng();
+
+    (0..length)
+        .map(|_| alphabet[rng.random_range(0..alphabet.len())])
+        .collect()
 }
 
 #[cfg(test)]
@@ -61,6 +56,14 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_unambiguous_alphabet() {
+        let id = generate_custom(32, UNAMBIGUOUS_ALPHABET);
+        for c in id.chars() {
+            assert!(!"01oli".contains(c));
+        }
+    }
+
     #[test]
     fn test_default_alphabet() {
         let id = generate();
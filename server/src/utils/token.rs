@@ -0,0 +1,105 @@
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use sha2::Sha256;
+use tracing::{event, Level};
+
+use super::env::read_env_string;
+
+type HmacSha256 = Hmac<Sha256>;
+
+lazy_static! {
+    static ref TOKEN_SECRET: Vec<u8> = read_token_secret();
+}
+
+fn read_token_secret() -> Vec<u8> {
+    match read_env_string("TOKEN_SECRET") {
+        Some(secret) => secret.into_bytes(),
+        None => {
+            // Tokens minted with an ephemeral secret do not survive a restart,
+            // which matches the lifetime of the in-memory stores
+            event!(Level::DEBUG, "TOKEN_SECRET not set, using an ephemeral secret");
+            rand::random::<[u8; 32]>().to_vec()
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(raw: &str) -> Option<Vec<u8>> {
+    if !raw.len().is_multiple_of(2) || !raw.is_ascii() {
+        return None;
+    }
+    (0..raw.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&raw[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn mac(scope: &str, parts: &[&str]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(&TOKEN_SECRET).expect("HMAC accepts keys of any size");
+    mac.update(scope.as_bytes());
+    for part in parts {
+        mac.update(&[0]);
+        mac.update(part.as_bytes());
+    }
+    mac
+}
+
+/// Random hex string with the given number of bytes of entropy
+pub fn random_hex(bytes: usize) -> String {
+    let data = (0..bytes).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+    to_hex(&data)
+}
+
+/// Hex HMAC-SHA256 signature over the scope and parts
+pub fn sign(scope: &str, parts: &[&str]) -> String {
+    to_hex(&mac(scope, parts).finalize().into_bytes())
+}
+
+/// Constant-time check of a hex signature produced by `sign`
+pub fn verify(scope: &str, parts: &[&str], signature: &str) -> bool {
+    match from_hex(signature) {
+        Some(raw) => mac(scope, parts).verify_slice(&raw).is_ok(),
+        None => false,
+    }
+}
+
+/// Build the `<nonce>.<signature>` token binding the nonce to the id
+pub fn issue(scope: &str, id: &str, nonce: &str) -> String {
+    format!("{}.{}", nonce, sign(scope, &[id, nonce]))
+}
+
+/// Mint a token for a fresh random nonce, returning both
+pub fn mint(scope: &str, id: &str) -> (String, String) {
+    let nonce = random_hex(16);
+    let token = issue(scope, id, &nonce);
+    (nonce, token)
+}
+
+/// Return the nonce of a token minted by `mint` for the same scope and id
+pub fn open<'a>(scope: &str, id: &str, token: &'a str) -> Option<&'a str> {
+    let (nonce, signature) = token.split_once('.')?;
+    if verify(scope, &[id, nonce], signature) { Some(nonce) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mint_and_open() {
+        let (nonce, token) = mint("receiver", "abcde");
+        assert_eq!(open("receiver", "abcde", &token), Some(nonce.as_str()));
+    }
+
+    #[test]
+    fn test_open_rejects_other_scope_and_id() {
+        let (_, token) = mint("receiver", "abcde");
+        assert_eq!(open("sender", "abcde", &token), None);
+        assert_eq!(open("receiver", "edcba", &token), None);
+        assert_eq!(open("receiver", "abcde", "deadbeef.00"), None);
+        assert_eq!(open("receiver", "abcde", "garbage"), None);
+    }
+}
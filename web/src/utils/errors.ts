@@ -0,0 +1,68 @@
+import { locale } from './i18n';
+
+// Localized text for the server's stable `error` codes; the English `message` is the fallback
+export type ApiError = {
+  code?: number;
+  error?: string;
+  message?: string;
+  details?: Record<string, any>;
+};
+
+const MESSAGES: Record<string, (details: Record<string, any>) => string> = {
+  ERR_NOT_FOUND: () => '资源不存在',
+  ERR_TRANSFER_NOT_FOUND: () => '传输不存在或已过期',
+  ERR_ROOM_NOT_FOUND: () => '信令房间不存在',
+  ERR_RECEIVER_NOT_FOUND: () => '接收方不存在',
+  ERR_UNKNOWN_TENANT: () => '未知的租户',
+  ERR_MISSING_PARAMETER: d => `缺少参数${d.field ? `：${d.field}` : ''}`,
+  ERR_INVALID_PARAMETER: d => `参数无效${d.field ? `：${d.field}` : ''}`,
+  ERR_MALFORMED_UPLOAD: () => '上传请求格式错误',
+  ERR_FILE_TOO_LARGE: () => '文件超过允许的最大大小',
+  ERR_SIZE_UNKNOWN: () => '文件大小尚未确定',
+  ERR_API_KEY_REQUIRED: () => '需要 API Key',
+  ERR_INVALID_API_KEY: () => 'API Key 无效',
+  ERR_TENANT_MISMATCH: () => 'API Key 不属于当前租户',
+  ERR_SENDER_TOKEN_REQUIRED: () => '缺少发送方凭证',
+  ERR_INVALID_SENDER_TOKEN: () => '发送方凭证无效',
+  ERR_SENDER_KEY_REQUIRED: () => '缺少发送方标识',
+  ERR_INVALID_RECEIVER_TOKEN: () => '接收凭证无效',
+  ERR_WRONG_RECEIVER: () => '接收方不匹配',
+  ERR_INVALID_ADMIN_TOKEN: () => '管理员凭证无效',
+  ERR_INVALID_LINK: () => '下载链接无效或已过期',
+  ERR_LINK_USED: () => '下载链接已被使用',
+  ERR_INVALID_HANDOFF: () => '转移链接无效或已过期',
+  ERR_HANDOFF_USED: () => '转移链接已被使用',
+  ERR_ROOM_TAKEN: () => '该取件码已被领取',
+  ERR_AWAITING_APPROVAL: () => '等待发送方确认',
+  ERR_TRANSFER_STARTED: () => '传输已开始',
+  ERR_TRANSFER_DONE: () => '传输已完成',
+  ERR_TRANSFER_PAUSED: () => '发送方已暂停传输',
+  ERR_CONTENT_REJECTED: () => '文件内容未通过检查',
+  ERR_BLOCK_NOT_READY: () => '数据块尚未就绪',
+  ERR_BLOCK_WINDOW_FULL: () => '服务器缓冲已满，请稍候',
+  ERR_BLOCK_TOO_LARGE: () => '数据块过大',
+  ERR_BLOCK_SIZE_MISMATCH: () => '数据块大小不一致',
+  ERR_WRONG_START: () => '数据块起始位置错误',
+  ERR_BLOCK_RANGE_INVALID: () => '数据块范围无效',
+  ERR_BLOCK_TOTAL_MISMATCH: () => '数据块与文件大小不一致',
+  ERR_ROOM_FULL: d => `信令房间已满${d.max_receivers ? `（最多 ${d.max_receivers} 个接收方）` : ''}`,
+  ERR_RECEIVER_ID_TAKEN: () => '接收方标识已被占用',
+  ERR_INVALID_ROLE: () => '角色无效',
+  ERR_INVALID_SESSION: () => '信令会话无效',
+  ERR_SIGNAL_TOO_LARGE: () => '信令消息过大',
+  ERR_SIGNAL_UNSUPPORTED: () => '不支持的信令类型',
+  ERR_SIGNAL_MALFORMED: () => '信令消息格式错误',
+  ERR_TURN_UNAVAILABLE: () => '未配置 TURN 服务',
+  ERR_QUOTA_EXHAUSTED: d => `今日额度已用完${d.limit ? `（每天 ${d.limit} 次）` : ''}`,
+  ERR_RATE_LIMITED: () => '请求过于频繁，请稍后再试',
+  ERR_POW_REQUIRED: () => '需要完成验证',
+  ERR_READ_ONLY: () => '只读视图',
+  ERR_INTERNAL: () => '服务器内部错误',
+};
+
+// Text for an error response body, or the fallback when it carries neither a known code nor a message;
+// English pages show the server's own message
+export const describeError = (body: ApiError | null | undefined, fallback: string): string => {
+  const localize = locale === 'zh-CN' && body?.error ? MESSAGES[body.error] : undefined;
+  return localize ? localize(body?.details ?? {}) : body?.message || fallback;
+};
@@ -0,0 +1,230 @@
+// UI strings for the localized pages; the server picks the HTML entry, which sets <html lang>
+const zhCN = {
+  'common.unknownError': '未知错误',
+  'common.unknownFile': '未知文件',
+  'common.unknownSize': '未知大小',
+  'common.idMissingSignal': 'ID 为空，无法发送信令',
+  'common.idMissingPoll': 'ID 为空，无法获取信令',
+  'common.p2pTimeout': 'P2P 连接超时',
+  'common.p2pFailed': 'P2P 连接失败',
+  'common.p2pFailedShort': 'P2P 失败',
+  'common.p2pTrying': '正在尝试 P2P 连接...',
+  'common.p2pFellBack': 'P2P 传输失败，已回退到 HTTP',
+
+  'upload.title': '文件上传',
+  'upload.subtitle': '通过 FileFlow 快速安全地分享文件',
+  'upload.serviceStatus': '服务状态',
+  'upload.online': '已连接到服务器，可以正常上传文件',
+  'upload.offline': '无法连接到服务器，请检查网络连接',
+  'upload.inFlight': '进行中的传输',
+  'upload.inFlightItem': '{id}：{name}（已上传 {uploaded}）',
+  'upload.inFlightSeparator': '；',
+  'upload.waitingCountdown': '等待接收方连接中... 剩余等待时间: {seconds} 秒',
+  'upload.dropHint': '点击或拖拽文件/文件夹到此区域上传',
+  'upload.dropNote': '支持文件或文件夹上传；多个文件或文件夹将自动打包。请勿上传敏感数据。',
+  'upload.accessIdLabel': '接收 ID:',
+  'upload.accessIdHint': '请将此 ID 发送给文件接收方',
+  'upload.selectFolder': '选择文件夹',
+  'upload.uploading': '上传中...',
+  'upload.awaiting': '等待对方接收',
+  'upload.start': '获取 ID 并上传',
+  'upload.progress': '{percent}% 已上传',
+  'upload.pausedSuffix': '（已暂停）',
+  'upload.resume': '继续',
+  'upload.pause': '暂停',
+  'upload.howTo': '使用说明:',
+  'upload.step1': '选择或拖拽文件/文件夹；多个文件会自动打包',
+  'upload.step2': '点击“获取 ID 并上传”，将生成的 ID 发给接收方',
+  'upload.step3': '接收方在下载页输入 ID 后开始接收，过程中请保持此页开启',
+  'upload.zipFailed': '创建压缩文件失败',
+  'upload.selectFirst': '请先选择一个文件或文件夹',
+  'upload.nothingToUpload': '没有可上传的文件',
+  'upload.sizeUnknown': '无法获取文件大小',
+  'upload.tooLarge': '文件过大，单次上传上限为 {limit}',
+  'upload.idInvalid': '未能获取有效的 ID',
+  'upload.idReady': 'ID 获取成功，请将 ID 发送给接收方',
+  'upload.idFailed': '获取 ID 失败: {error}',
+  'upload.pauseFailed': '暂停失败: {error}',
+  'upload.resumeFailed': '继续失败: {error}',
+  'upload.receiverFallback': '接收方要求回退 HTTP',
+  'upload.receiverConnected': '接收方已连接',
+  'upload.receiverLeft': '接收方已断开',
+  'upload.contentRejected': '文件未通过内容检查：{reason}',
+  'upload.unknownReason': '未知原因',
+  'upload.approvalTitle': '新的接收请求',
+  'upload.approvalContent': '接收方 {receiver} 请求下载此文件，是否允许？',
+  'upload.allow': '允许',
+  'upload.deny': '拒绝',
+  'upload.p2pDone': '接收方已完成 P2P 下载',
+  'upload.waitingReceiver': '正在等待接收方连接...',
+  'upload.receiverJoined': '接收方已连接，开始上传文件...',
+  'upload.waitTimeout': '等待接收方连接超时',
+  'upload.starting': '开始上传文件: {name} ({size})',
+  'upload.startingArchive': '开始上传压缩文件: {name} ({size})',
+  'upload.idMissingConnect': 'ID 为空，无法建立连接',
+  'upload.uploaded': '文件上传成功！等待接收方下载完成...',
+  'upload.doneUnknown': '未能得知文件是否被下载完成，确认下载完成则可以关闭窗口！',
+  'upload.receiverDone': '接收方已下载完成！',
+  'upload.statusCheckFailed': '检查下载状态时出错，但将继续尝试',
+  'upload.failed': '上传失败: {error}',
+
+  'download.title': '文件下载',
+  'download.subtitle': '准备接收通过 FileFlow 分享的文件',
+  'download.enterId': '输入 5 位 ID',
+  'download.enterIdHint': '输入接收方提供的 5 位代码即可进入下载',
+  'download.enter': '进入下载',
+  'download.finished': '下载完成',
+  'download.downloading': '下载中...',
+  'download.start': '开始下载',
+  'download.otherDevice': '在其他设备下载',
+  'download.progress': '{percent}% 已完成',
+  'download.howTo': '如何使用:',
+  'download.step1': '确保分享 ID 来自可信来源',
+  'download.step2': '点击"开始下载"按钮开始接收文件',
+  'download.step3': '文件将自动保存到您的默认下载目录',
+  'download.step4': '下载过程中请勿关闭此页面',
+  'download.expiresHours': '{n} 小时后过期',
+  'download.expiresMinutes': '{n} 分钟后过期',
+  'download.expiresSoon': '即将过期',
+  'download.p2pConfigEmpty': 'P2P 配置为空',
+  'download.downloaded': '文件下载完成!',
+  'download.doneNotifyFailed': '无法通知服务器下载完成，但文件已成功下载',
+  'download.senderFallback': '发送方要求回退 HTTP',
+  'download.p2pInterrupted': 'P2P 连接中断',
+  'download.p2pClosed': 'P2P 连接关闭',
+  'download.awaitingApproval': '等待发送方确认接收请求...',
+  'download.handoffCopied': '转移链接已复制，请在 2 分钟内在另一台设备打开',
+  'download.handoffFailed': '生成转移链接失败: {error}',
+  'download.invalidId': '请先输入有效的 5 位 ID',
+  'download.claimFailed': '领取文件失败: {error}',
+  'download.senderPaused': '发送方已暂停传输',
+  'download.senderResumed': '发送方已继续传输',
+  'download.senderCancelled': '发送方已取消传输',
+  'download.cancelled': '传输已取消',
+  'download.failed': '下载过程中发生错误: {error}',
+  'download.missingBlocks': '文件 {name} 中有缺失的块，请重新上传',
+  'download.saveFailed': '保存文件时发生错误: {error}',
+  'download.noData': '没有下载到任何文件数据',
+  'download.infoFailed': '获取文件信息失败: {error}',
+};
+
+export type MessageKey = keyof typeof zhCN;
+
+const en: Record<MessageKey, string> = {
+  'common.unknownError': 'Unknown error',
+  'common.unknownFile': 'Unknown file',
+  'common.unknownSize': 'Unknown size',
+  'common.idMissingSignal': 'No ID, cannot send signaling messages',
+  'common.idMissingPoll': 'No ID, cannot fetch signaling messages',
+  'common.p2pTimeout': 'P2P connection timed out',
+  'common.p2pFailed': 'P2P connection failed',
+  'common.p2pFailedShort': 'P2P failed',
+  'common.p2pTrying': 'Trying a P2P connection...',
+  'common.p2pFellBack': 'P2P transfer failed, falling back to HTTP',
+
+  'upload.title': 'File Upload',
+  'upload.subtitle': 'Share files quickly and securely with FileFlow',
+  'upload.serviceStatus': 'Service status',
+  'upload.online': 'Connected to the server, ready to upload',
+  'upload.offline': 'Cannot reach the server, check your network connection',
+  'upload.inFlight': 'Transfers in progress',
+  'upload.inFlightItem': '{id}: {name} ({uploaded} uploaded)',
+  'upload.inFlightSeparator': '; ',
+  'upload.waitingCountdown': 'Waiting for the receiver to connect... {seconds}s left',
+  'upload.dropHint': 'Click or drag files or folders here to upload',
+  'upload.dropNote': 'Files and folders are supported; multiple items are zipped automatically. Do not upload sensitive data.',
+  'upload.accessIdLabel': 'Receive ID:',
+  'upload.accessIdHint': 'Send this ID to the receiver',
+  'upload.selectFolder': 'Select folder',
+  'upload.uploading': 'Uploading...',
+  'upload.awaiting': 'Waiting for the receiver',
+  'upload.start': 'Get ID and upload',
+  'upload.progress': '{percent}% uploaded',
+  'upload.pausedSuffix': ' (paused)',
+  'upload.resume': 'Resume',
+  'upload.pause': 'Pause',
+  'upload.howTo': 'How it works:',
+  'upload.step1': 'Pick or drop files or folders; multiple files are zipped automatically',
+  'upload.step2': 'Click "Get ID and upload" and send the ID to the receiver',
+  'upload.step3': 'The receiver enters the ID on the download page; keep this page open meanwhile',
+  'upload.zipFailed': 'Failed to create the archive',
+  'upload.selectFirst': 'Select a file or folder first',
+  'upload.nothingToUpload': 'There is nothing to upload',
+  'upload.sizeUnknown': 'Could not determine the file size',
+  'upload.tooLarge': 'The file is too large; the upload limit is {limit}',
+  'upload.idInvalid': 'Could not get a valid ID',
+  'upload.idReady': 'ID ready, send it to the receiver',
+  'upload.idFailed': 'Failed to get an ID: {error}',
+  'upload.pauseFailed': 'Failed to pause: {error}',
+  'upload.resumeFailed': 'Failed to resume: {error}',
+  'upload.receiverFallback': 'The receiver asked to fall back to HTTP',
+  'upload.receiverConnected': 'Receiver connected',
+  'upload.receiverLeft': 'Receiver disconnected',
+  'upload.contentRejected': 'The file failed content inspection: {reason}',
+  'upload.unknownReason': 'unknown reason',
+  'upload.approvalTitle': 'New receiver request',
+  'upload.approvalContent': 'Receiver {receiver} wants to download this file. Allow it?',
+  'upload.allow': 'Allow',
+  'upload.deny': 'Deny',
+  'upload.p2pDone': 'The receiver finished the P2P download',
+  'upload.waitingReceiver': 'Waiting for the receiver to connect...',
+  'upload.receiverJoined': 'Receiver connected, uploading the file...',
+  'upload.waitTimeout': 'Timed out waiting for the receiver',
+  'upload.starting': 'Uploading {name} ({size})',
+  'upload.startingArchive': 'Uploading archive {name} ({size})',
+  'upload.idMissingConnect': 'No ID, cannot connect',
+  'upload.uploaded': 'Upload complete! Waiting for the receiver to finish downloading...',
+  'upload.doneUnknown': 'Could not confirm the download finished; close this window once the receiver has the file.',
+  'upload.receiverDone': 'The receiver finished downloading!',
+  'upload.statusCheckFailed': 'Checking the download status failed, retrying',
+  'upload.failed': 'Upload failed: {error}',
+
+  'download.title': 'File Download',
+  'download.subtitle': 'Receive a file shared with FileFlow',
+  'download.enterId': 'Enter the 5-character ID',
+  'download.enterIdHint': 'Enter the 5-character code you were given to start downloading',
+  'download.enter': 'Continue',
+  'download.finished': 'Downloaded',
+  'download.downloading': 'Downloading...',
+  'download.start': 'Start download',
+  'download.otherDevice': 'Download on another device',
+  'download.progress': '{percent}% done',
+  'download.howTo': 'How to use:',
+  'download.step1': 'Make sure the ID comes from a trusted source',
+  'download.step2': 'Click "Start download" to receive the file',
+  'download.step3': 'The file is saved to your default download folder',
+  'download.step4': 'Keep this page open while downloading',
+  'download.expiresHours': 'Expires in {n} h',
+  'download.expiresMinutes': 'Expires in {n} min',
+  'download.expiresSoon': 'Expires soon',
+  'download.p2pConfigEmpty': 'No P2P configuration',
+  'download.downloaded': 'Download complete!',
+  'download.doneNotifyFailed': 'The file downloaded, but the server could not be notified',
+  'download.senderFallback': 'The sender asked to fall back to HTTP',
+  'download.p2pInterrupted': 'P2P connection interrupted',
+  'download.p2pClosed': 'P2P connection closed',
+  'download.awaitingApproval': 'Waiting for the sender to approve the request...',
+  'download.handoffCopied': 'Handoff link copied; open it on the other device within 2 minutes',
+  'download.handoffFailed': 'Failed to create a handoff link: {error}',
+  'download.invalidId': 'Enter a valid 5-character ID first',
+  'download.claimFailed': 'Failed to claim the file: {error}',
+  'download.senderPaused': 'The sender paused the transfer',
+  'download.senderResumed': 'The sender resumed the transfer',
+  'download.senderCancelled': 'The sender cancelled the transfer',
+  'download.cancelled': 'Transfer cancelled',
+  'download.failed': 'Download failed: {error}',
+  'download.missingBlocks': 'File {name} is missing blocks, ask the sender to upload it again',
+  'download.saveFailed': 'Failed to save the file: {error}',
+  'download.noData': 'No file data was downloaded',
+  'download.infoFailed': 'Failed to load the file info: {error}',
+};
+
+export type Locale = 'zh-CN' | 'en';
+
+export const locale: Locale = document.documentElement.lang.toLowerCase().startsWith('en') ? 'en' : 'zh-CN';
+
+const CATALOGS: Record<Locale, Record<MessageKey, string>> = { 'zh-CN': zhCN, en };
+
+// Catalog text for the page locale with `{name}` placeholders filled from params
+export const t = (key: MessageKey, params: Record<string, string | number> = {}): string =>
+  CATALOGS[locale][key].replace(/\{(\w+)\}/g, (match, name) => (name in params ? String(params[name]) : match));
@@ -1,5 +1,6 @@
 import message from "ant-design-vue/es/message";
 import type { Ref } from "vue";
+import { describeError } from "./errors";
 
 const MAX_RETRIES = 4;
 const BASE_DELAY_MS = 400;
@@ -10,7 +11,7 @@ const DEFAULT_RETRIES = 2;
 const sleep = (ms: number) => new Promise(resolve => setTimeout(resolve, ms));
 
 const isRetryableStatus = (status: number) =>
-  status === 408 || status === 425 || status === 429 || (status >= 500 && status <= 599);
+  status === 408 || status === 423 || status === 425 || status === 429 || (status >= 500 && status <= 599);
 
 const fetchWithTimeout = async (input: RequestInfo, init: RequestInit, timeoutMs: number): Promise<Response> => {
   const controller = new AbortController();
@@ -22,6 +23,40 @@ const fetchWithTimeout = async (input: RequestInfo, init: RequestInit, timeoutMs
   }
 };
 
+const leadingZeroBits = (hash: Uint8Array) => {
+  let bits = 0;
+  for (const byte of hash) {
+    if (byte === 0) {
+      bits += 8;
+      continue;
+    }
+    bits += Math.clz32(byte) - 24;
+    break;
+  }
+  return bits;
+};
+
+// Find a solution whose SHA-256 over `<challenge>:<solution>` has `bits` leading zero bits.
+const solveProofOfWork = async (challenge: string, bits: number): Promise<string> => {
+  const encoder = new TextEncoder();
+  for (let n = 0; ; n++) {
+    const hash = new Uint8Array(await crypto.subtle.digest('SHA-256', encoder.encode(`${challenge}:${n}`)));
+    if (leadingZeroBits(hash) >= bits) return String(n);
+  }
+};
+
+// Answer a 428 proof-of-work challenge (sent after repeated lookups of unknown ids) with a solved retry.
+const withProofOfWork = async (response: Response, input: RequestInfo, init: RequestInit, timeoutMs: number) => {
+  if (response.status !== 428) return response;
+  const body = await parseJsonSafely<{ details?: { challenge?: string; bits?: number } }>(response.clone());
+  const challenge = body?.details?.challenge;
+  if (!challenge || typeof crypto === 'undefined' || !crypto.subtle) return response;
+  const headers = new Headers(init.headers);
+  headers.set('X-PoW-Challenge', challenge);
+  headers.set('X-PoW-Solution', await solveProofOfWork(challenge, body?.details?.bits ?? 0));
+  return fetchWithTimeout(input, { ...init, headers }, timeoutMs);
+};
+
 const parseJsonSafely = async <T>(response: Response): Promise<T | null> => {
   try {
     return (await response.json()) as T;
@@ -41,6 +76,11 @@ interface RetryOptions {
   retryOnStatus?: (status?: number) => boolean;
 }
 
+// Pages served under /t/{tenant} keep every request and link inside that tenant
+export const tenantPrefix = window.location.pathname.match(/^\/t\/[a-z0-9-]+(?=\/|$)/)?.[0] ?? '';
+export const withTenant = (path: string) =>
+  tenantPrefix && path.startsWith('/') && !path.startsWith('/t/') ? `${tenantPrefix}${path}` : path;
+
 export const fetchWithRetry = async (
   input: RequestInfo,
   init: RequestInit = {},
@@ -55,10 +95,11 @@ export const fetchWithRetry = async (
 ): Promise<Response> => {
   let attempt = 0;
   let lastError: unknown;
+  const target = typeof input === 'string' ? withTenant(input) : input;
 
   while (attempt <= retries) {
     try {
-      const response = await fetchWithTimeout(input, init, timeoutMs);
+      const response = await withProofOfWork(await fetchWithTimeout(target, init, timeoutMs), target, init, timeoutMs);
       if (response.ok || !retryOnStatus(response.status)) {
         return response;
       }
@@ -92,6 +133,95 @@ export const fetchJsonWithRetry = async <T>(
   return { data, response };
 };
 
+export type ServerFlags = {
+  relay_fallback?: boolean;
+  api_key_required?: boolean;
+  max_file_size?: number;
+};
+
+// Deployment feature flags; an unreachable endpoint yields an empty set so callers keep their defaults.
+export const fetchServerFlags = async (): Promise<ServerFlags> => {
+  try {
+    const { data, response } = await fetchJsonWithRetry<{ success?: boolean; data?: ServerFlags }>(
+      '/api/flags',
+      { method: 'get' },
+      { timeoutMs: 5000, retries: 1 },
+    );
+    return response.ok && data?.success && data.data ? data.data : {};
+  } catch {
+    return {};
+  }
+};
+
+const senderTokenKey = (accessId: string) => `sender_token:${accessId}`;
+
+export const getSenderToken = (accessId: string | null): string =>
+  (accessId && localStorage.getItem(senderTokenKey(accessId))) || '';
+
+export const setSenderToken = (accessId: string, token: string) => {
+  localStorage.setItem(senderTokenKey(accessId), token);
+};
+
+// Random per-browser key grouping this sender's transfers for /api/my/transfers.
+export const getSenderKey = (): string => {
+  let key = localStorage.getItem('sender_key');
+  if (!key) {
+    key = Array.from(crypto.getRandomValues(new Uint8Array(16)), b => b.toString(16).padStart(2, '0')).join('');
+    localStorage.setItem('sender_key', key);
+  }
+  return key;
+};
+
+export type MyTransfer = {
+  id: string;
+  file_name: string;
+  file_size: number;
+  uploaded_bytes: number;
+  downloaded_bytes: number;
+  done: boolean;
+  paused: boolean;
+  expires_in: number;
+  sender_token: string;
+};
+
+// In-flight transfers created from this browser; their sender tokens are restored for later control.
+export const fetchMyTransfers = async (): Promise<MyTransfer[]> => {
+  try {
+    const { data, response } = await fetchJsonWithRetry<{ success?: boolean; data?: { transfers?: MyTransfer[] } }>(
+      '/api/my/transfers',
+      { method: 'get', headers: { 'X-Sender-Key': getSenderKey() } },
+      { timeoutMs: 5000, retries: 1 },
+    );
+    const transfers = response.ok && data?.success ? data.data?.transfers ?? [] : [];
+    transfers.forEach(transfer => setSenderToken(transfer.id, transfer.sender_token));
+    return transfers;
+  } catch {
+    return [];
+  }
+};
+
+// Cancel a transfer server-side so buffered blocks are dropped right away.
+export const cancelTransfer = async (accessId: string) => {
+  await fetchWithTimeout(
+    `/api/fileflow/${accessId}/cancel`,
+    { method: 'post', headers: { 'X-Sender-Token': getSenderToken(accessId) } },
+    6000,
+  );
+  localStorage.removeItem(senderTokenKey(accessId));
+};
+
+// Pause or resume the relay; receivers hold off on blocks while paused.
+export const setTransferPaused = async (accessId: string, paused: boolean) => {
+  const response = await fetchWithTimeout(
+    `/api/fileflow/${accessId}/${paused ? 'pause' : 'resume'}`,
+    { method: 'post', headers: { 'X-Sender-Token': getSenderToken(accessId) } },
+    6000,
+  );
+  if (!response.ok) {
+    throw new Error(`${paused ? 'Pause' : 'Resume'} failed with status ${response.status}`);
+  }
+};
+
 export const uploadFile = async (formData: FormData, accessId: string | null, i: number, seq_len: number): Promise<number> => {
   let attempt = 0;
 
@@ -99,7 +229,7 @@ export const uploadFile = async (formData: FormData, accessId: string | null, i:
     try {
       const response = await fetchWithTimeout(
         `/api/fileflow/${accessId}/upload`,
-        { method: "post", body: formData },
+        { method: "post", body: formData, headers: { "X-Sender-Token": getSenderToken(accessId) } },
         18000
       );
 
@@ -107,13 +237,20 @@ export const uploadFile = async (formData: FormData, accessId: string | null, i:
       let messageFromServer: string | undefined;
       try {
         body = await response.json();
-        messageFromServer = body?.message;
+        messageFromServer = body ? describeError(body, body.message) : undefined;
       } catch {
         if (!response.ok) {
           throw new Error(`Upload failed for chunk ${i + 1} with status ${response.status}`);
         }
       }
 
+      // A full block window is pacing, not failure: wait as told without spending a retry
+      const retryAfter = Number(response.headers.get("Retry-After"));
+      if (response.status === 429 && retryAfter > 0) {
+        await sleep(retryAfter * 1000);
+        continue;
+      }
+
       if (!response.ok || (body && body.code !== 200)) {
         const err = new Error(messageFromServer || `Upload failed for chunk ${i + 1} with status ${response.status}`);
         (err as any).status = response.status;
@@ -141,13 +278,124 @@ export const uploadFile = async (formData: FormData, accessId: string | null, i:
   throw new Error(`Upload failed for chunk ${i + 1} after ${MAX_RETRIES} retries`);
 };
 
+const receiverTokenKey = (fileId: string) => `receiver_token:${fileId}`;
+
+export const getReceiverToken = (fileId: string): string => localStorage.getItem(receiverTokenKey(fileId)) || '';
+
+const APPROVAL_POLL_MS = 2000;
+
+const claimOnce = async (fileId: string): Promise<{ token: string; approved: boolean }> => {
+  const stored = getReceiverToken(fileId);
+  const query = stored ? `?token=${encodeURIComponent(stored)}` : '';
+  const { data, response } = await fetchJsonWithRetry<{ success?: boolean; message?: string; data?: { token?: string; approved?: boolean } }>(
+    `/api/fileflow/${fileId}/claim${query}`,
+    { method: 'post' },
+    { timeoutMs: 6000, retries: 2 },
+  );
+  if (!response.ok || !data?.success || !data.data?.token) {
+    throw new Error(describeError(data, `Claim failed with status ${response.status}`));
+  }
+  localStorage.setItem(receiverTokenKey(fileId), data.data.token);
+  return { token: data.data.token, approved: data.data.approved !== false };
+};
+
+// Claim the access code as receiver; re-presenting a stored token keeps the claim.
+// When the sender reviews receivers first, `onPending` fires once and the claim is polled until approved.
+export const claimReceiverToken = async (fileId: string, onPending?: () => void): Promise<string> => {
+  let claim = await claimOnce(fileId);
+  if (!claim.approved) {
+    onPending?.();
+  }
+  while (!claim.approved) {
+    await sleep(APPROVAL_POLL_MS);
+    claim = await claimOnce(fileId);
+  }
+  return claim.token;
+};
+
+// Approve or reject a pending receiver claim (sender side).
+export const reviewReceiver = async (accessId: string, receiver: string, approve: boolean) => {
+  const { data, response } = await fetchJsonWithRetry<{ success?: boolean; message?: string }>(
+    `/api/fileflow/${accessId}/receivers/${encodeURIComponent(receiver)}`,
+    {
+      method: 'post',
+      headers: { 'Content-Type': 'application/json', 'X-Sender-Token': getSenderToken(accessId) },
+      body: JSON.stringify({ approve }),
+    },
+    { timeoutMs: 6000, retries: 1 },
+  );
+  if (!response.ok || !data?.success) {
+    throw new Error(describeError(data, `Review failed with status ${response.status}`));
+  }
+};
+
+// Redeem a pre-authorized download link (`?exp=&uses=&sig=`) for a receiver token.
+export const redeemDownloadLink = async (fileId: string, search: string): Promise<string> => {
+  const { data, response } = await fetchJsonWithRetry<{ success?: boolean; message?: string; data?: { token?: string } }>(
+    `/api/fileflow/${fileId}/redeem${search}`,
+    { method: 'post' },
+    { timeoutMs: 6000, retries: 0 },
+  );
+  if (!response.ok || !data?.success || !data.data?.token) {
+    throw new Error(describeError(data, `Redeem failed with status ${response.status}`));
+  }
+  localStorage.setItem(receiverTokenKey(fileId), data.data.token);
+  return data.data.token;
+};
+
+// Mint a short-lived link that moves this device's claim to another device.
+export const createHandoffLink = async (fileId: string): Promise<string> => {
+  const { data, response } = await fetchJsonWithRetry<{ success?: boolean; message?: string; data?: { url?: string } }>(
+    `/api/fileflow/${fileId}/handoff?token=${encodeURIComponent(getReceiverToken(fileId))}`,
+    { method: 'post' },
+    { timeoutMs: 6000, retries: 1 },
+  );
+  if (!response.ok || !data?.success || !data.data?.url) {
+    throw new Error(describeError(data, `Handoff failed with status ${response.status}`));
+  }
+  return `${window.location.origin}${data.data.url}`;
+};
+
+// Take over a claim handed off from another device; progress carries over.
+export const redeemHandoff = async (fileId: string, handoff: string): Promise<string> => {
+  const { data, response } = await fetchJsonWithRetry<{ success?: boolean; message?: string; data?: { token?: string } }>(
+    `/api/fileflow/${fileId}/handoff/redeem?handoff=${encodeURIComponent(handoff)}`,
+    { method: 'post' },
+    { timeoutMs: 6000, retries: 0 },
+  );
+  if (!response.ok || !data?.success || !data.data?.token) {
+    throw new Error(describeError(data, `Handoff failed with status ${response.status}`));
+  }
+  localStorage.setItem(receiverTokenKey(fileId), data.data.token);
+  return data.data.token;
+};
+
+export type ReceiverEventHandlers = Record<string, (data: any) => void>;
+
+// Follow the server's receiver event stream (sender-connected, metadata-updated,
+// block-available, transfer-paused, transfer-resumed, transfer-cancelled). Returns a function closing the stream.
+export const subscribeReceiverEvents = (fileId: string, handlers: ReceiverEventHandlers): (() => void) => {
+  if (typeof EventSource === 'undefined') return () => {};
+  const source = new EventSource(withTenant(`/api/fileflow/${fileId}/receiver-events?token=${encodeURIComponent(getReceiverToken(fileId))}`));
+  for (const [kind, handler] of Object.entries(handlers)) {
+    source.addEventListener(kind, event => {
+      try {
+        handler(JSON.parse((event as MessageEvent).data));
+      } catch {
+        // Ignore malformed events
+      }
+    });
+  }
+  return () => source.close();
+};
+
 export const downloadFile = async (fileId: string, start: number, fileName: Ref<string>): Promise<[RegExpMatchArray, Response]> => {
   let attempt = 0;
 
   while (attempt <= MAX_RETRIES) {
     try {
       const response = await fetchWithTimeout(
-        `/api/fileflow/${fileId}/file?rid=${localStorage.getItem("rid")}&start=${start}`,
+        `/api/fileflow/${fileId}/file?token=${encodeURIComponent(getReceiverToken(fileId))}&start=${start}`,
         { method: "get" },
         18000
       );
@@ -30,6 +30,8 @@ export default defineConfig({
         index: fileURLToPath(new URL('./index.html', import.meta.url)),
         upload: fileURLToPath(new URL('./upload/index.html', import.meta.url)),
         download: fileURLToPath(new URL('./download/index.html', import.meta.url)),
+        'upload-en': fileURLToPath(new URL('./upload/index.en.html', import.meta.url)),
+        'download-en': fileURLToPath(new URL('./download/index.en.html', import.meta.url)),
       }
     }
   }